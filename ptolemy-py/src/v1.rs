@@ -31,6 +31,7 @@ pub struct Input(pub IO);
 impl Input {
     pub fn to_record(&self, tier: &Tier) -> PyResult<Record> {
         Ok(Record {
+            workspace_id: None,
             record_data: Some(RecordData::Input(InputRecord {
                 tier: tier.clone().into(),
                 subject_id: self.0.subject_id.to_string(),
@@ -50,6 +51,7 @@ pub struct Output(pub IO);
 impl Output {
     pub fn to_record(&self, tier: &Tier) -> PyResult<Record> {
         Ok(Record {
+            workspace_id: None,
             record_data: Some(RecordData::Output(OutputRecord {
                 tier: tier.clone().into(),
                 subject_id: self.0.subject_id.to_string(),
@@ -69,6 +71,7 @@ pub struct Feedback(pub IO);
 impl Feedback {
     pub fn to_record(&self, tier: &Tier) -> PyResult<Record> {
         Ok(Record {
+            workspace_id: None,
             record_data: Some(RecordData::Feedback(FeedbackRecord {
                 tier: tier.clone().into(),
                 subject_id: self.0.subject_id.to_string(),
@@ -88,8 +91,8 @@ pub struct Runtime {
     #[pyo3(attribute("id_"))]
     pub id: PyUUIDWrapper,
 
-    pub start_time: Option<f32>,
-    pub end_time: Option<f32>,
+    pub start_time: Option<f64>,
+    pub end_time: Option<f64>,
 
     pub error_type: Option<String>,
     pub error_content: Option<String>,
@@ -108,15 +111,18 @@ impl Runtime {
             .ok_or(PyValueError::new_err("End time not set."))?;
 
         Ok(Record {
+            workspace_id: None,
             record_data: Some(RecordData::Runtime(RuntimeRecord {
                 tier: tier.clone().into(),
                 subject_id: self.subject_id.to_string(),
                 event_id: self.event_id.to_string(),
                 id: self.id.to_string(),
-                start_time,
-                end_time,
+                start_time: start_time as f32,
+                end_time: end_time as f32,
                 error_type: self.error_type.clone(),
                 error_content: self.error_content.clone(),
+                start_time_us: Some((start_time * 1e6) as i64),
+                end_time_us: Some((end_time * 1e6) as i64),
             })),
         })
     }
@@ -136,6 +142,7 @@ pub struct Metadata {
 impl Metadata {
     pub fn to_record(&self, tier: &Tier) -> PyResult<Record> {
         Ok(Record {
+            workspace_id: None,
             record_data: Some(RecordData::Metadata(MetadataRecord {
                 tier: tier.clone().into(),
                 subject_id: self.subject_id.to_string(),
@@ -195,6 +202,7 @@ impl Trace {
         let parameters = self.parameters.as_ref().map(|i| i.0.clone().into());
 
         Ok(Record {
+            workspace_id: None,
             record_data: Some(RecordData::Event(EventRecord {
                 tier: tier.clone().into(),
                 subject_id: self.subject_id.to_string(),
@@ -1,34 +1,165 @@
 use ptolemy::api::error::ApiError;
-use ptolemy::api::{config::PtolemyConfig, routes::get_router, state::AppState};
+use ptolemy::api::{
+    config::PtolemyConfig,
+    routes::{get_grpc_router, get_http_router, get_router},
+    server::serve,
+    state::{watch_config, AppState},
+    tls::TlsAcceptor,
+    tracing::Telemetry,
+};
+
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to install SIGTERM handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => tracing::info!("Received SIGINT"),
+        _ = terminate => tracing::info!("Received SIGTERM"),
+    }
+}
 
 #[tokio::main]
 async fn main() -> Result<(), ApiError> {
-    tracing_subscriber::fmt()
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
+    // Logging is configured from the config file, so it's read before a
+    // subscriber exists to report errors in it.
+    let config = PtolemyConfig::figment().extract::<PtolemyConfig>();
+    let (logging, spans) = config
+        .as_ref()
+        .map(|c| (c.logging.clone(), c.telemetry.clone()))
+        .unwrap_or_default();
+    let telemetry = Telemetry::init(&logging, &spans)?;
+    let config = config.map_err(|e| {
+        tracing::error!("{:?}", e);
+        ApiError::ConfigError
+    })?;
+
+    let result = run(config).await;
+    telemetry.shutdown().await;
+    result
+}
 
-    let config = PtolemyConfig::from_file()?;
+async fn run(config: PtolemyConfig) -> Result<(), ApiError> {
+    let shutdown_timeout = config.shutdown_timeout();
+    let server_config = config.server.clone();
 
     // create state
     let state = std::sync::Arc::new(AppState::new(config).await?);
+    let mut watchers = vec![tokio::spawn(watch_config(state.clone()))];
+
+    let tls = match server_config.tls.clone() {
+        None => None,
+        Some(tls_config) => {
+            let tls = std::sync::Arc::new(TlsAcceptor::new(tls_config)?);
+            watchers.push(tokio::spawn(tls.clone().watch()));
+            Some(tls)
+        }
+    };
+
+    let listeners = match server_config.grpc_port {
+        None => vec![(
+            "HTTP/gRPC",
+            server_config.port,
+            get_router(state.clone()).await,
+        )],
+        Some(grpc_port) => vec![
+            (
+                "HTTP",
+                server_config.port,
+                get_http_router(state.clone()).await,
+            ),
+            ("gRPC", grpc_port, get_grpc_router(state.clone()).await),
+        ],
+    };
+
+    let (stop_tx, stop_rx) = tokio::sync::watch::channel(());
+    let mut servers = tokio::task::JoinSet::new();
+
+    for (kind, port, router) in listeners {
+        let listener = tokio::net::TcpListener::bind((server_config.host.as_str(), port))
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to bind {}:{}: {}", server_config.host, port, e);
+                ApiError::ConfigError
+            })?;
+
+        tracing::info!(
+            "Ptolemy {} running on {}{} <3",
+            kind,
+            listener.local_addr().map_err(|_| ApiError::InternalError)?,
+            if tls.is_some() { " (TLS)" } else { "" }
+        );
 
-    let service = get_router(state.clone())
-        .await
-        .into_make_service_with_connect_info::<std::net::SocketAddr>();
+        let mut stop = stop_rx.clone();
+        servers.spawn(serve(
+            listener,
+            router,
+            server_config.clone(),
+            tls.clone(),
+            async move {
+                let _ = stop.changed().await;
+            },
+        ));
+    }
+
+    tokio::select! {
+        result = servers.join_next() => {
+            tracing::error!("Server exited unexpectedly: {:?}", result);
+            return Err(ApiError::InternalError);
+        }
+        _ = shutdown_signal() => {}
+    }
 
-    let server_url = format!("[::]:{}", 7865);
-    let listener = tokio::net::TcpListener::bind(&server_url).await.unwrap();
+    tracing::info!(
+        "Shutting down gracefully (timeout: {}s)",
+        shutdown_timeout.as_secs()
+    );
+    watchers.iter().for_each(|w| w.abort());
 
-    tracing::info!("Ptolemy running on {} <3", server_url);
+    // Stop accepting connections and drain sinks at the same time, so idle
+    // keep-alive connections can't hold up flushing buffered records.
+    let _ = stop_tx.send(());
+    let drain = async {
+        let servers = async {
+            let mut results = Vec::new();
+            while let Some(result) = servers.join_next().await {
+                results.push(result);
+            }
+            results
+        };
+        tokio::join!(servers, state.shutdown()).0
+    };
 
-    match axum::serve(listener, service)
-        // .with_graceful_shutdown(shutdown_signal(state, sink_handle))
-        .await
-    {
-        Ok(_) => Ok(()),
-        Err(e) => {
-            tracing::error!("Axum server error: {:?}", e);
-            Err(ApiError::InternalError)
+    match tokio::time::timeout(shutdown_timeout, drain).await {
+        Ok(results) => match results.into_iter().find_map(Result::err) {
+            None => {
+                tracing::info!("Shutdown complete");
+                Ok(())
+            }
+            Some(e) => {
+                tracing::error!("Server task failed: {:?}", e);
+                Err(ApiError::InternalError)
+            }
+        },
+        Err(_) => {
+            tracing::error!(
+                "Graceful shutdown timed out after {}s",
+                shutdown_timeout.as_secs()
+            );
+            Err(ApiError::TimeoutError)
         }
     }
 }
@@ -10,10 +10,23 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .protoc_arg("--experimental_allow_proto3_optional")
             .build_server(true)
             .out_dir("src/generated")
+            .file_descriptor_set_path("src/generated/record_publisher_descriptor.bin")
             .compile_protos(
                 &["proto/record_publisher.proto"],
                 &["proto/record_publisher"],
             )?;
+
+        // Only the descriptors are kept, for gRPC reflection; the OTLP types
+        // themselves come from `opentelemetry-proto`.
+        tonic_build::configure()
+            .build_client(false)
+            .build_server(false)
+            .out_dir(std::env::var("OUT_DIR")?)
+            .file_descriptor_set_path("src/generated/otlp_trace_descriptor.bin")
+            .compile_protos(
+                &["proto/opentelemetry/proto/collector/trace/v1/trace_service.proto"],
+                &["proto"],
+            )?;
     }
 
     Ok(())
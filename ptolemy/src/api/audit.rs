@@ -0,0 +1,520 @@
+use super::{auth::Identity, config::audit::AuditConfig, crypto::generate_sha256, error::ApiError};
+
+use axum::extract::{ConnectInfo, FromRequestParts};
+use chrono::{DateTime, Utc};
+use http::{request::Parts, Extensions};
+use serde::{Deserialize, Serialize};
+use std::convert::Infallible;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+use std::sync::{
+    mpsc::{Receiver, RecvTimeoutError, SyncSender, TrySendError},
+    Mutex,
+};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Events waiting to be written. Events recorded while it's full are dropped.
+const QUEUE_SIZE: usize = 4096;
+
+/// How long identical auth failures are collected into one entry.
+const COALESCE_WINDOW: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditAction {
+    Login,
+    Logout,
+    TokenRefresh,
+    PasswordChange,
+    AuthFailure,
+    ApiKeyCreate,
+    ApiKeyRevoke,
+    ConfigReload,
+    UserCreate,
+    UserDelete,
+    WorkspaceCreate,
+    WorkspaceDelete,
+    MemberSet,
+    MemberRemove,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditOutcome {
+    Success,
+    Failure,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct AuditEvent {
+    pub timestamp: DateTime<Utc>,
+    /// `None` for the server itself and unauthenticated callers.
+    pub actor: Option<Identity>,
+    pub action: AuditAction,
+    pub target: Option<String>,
+    pub outcome: AuditOutcome,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub source_ip: Option<IpAddr>,
+    /// How many identical auth failures this entry stands for, when more
+    /// than one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<u64>,
+}
+
+impl AuditEvent {
+    /// A successful event with no actor, target or source.
+    pub fn new(action: AuditAction) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            actor: None,
+            action,
+            target: None,
+            outcome: AuditOutcome::Success,
+            error: None,
+            source_ip: None,
+            count: None,
+        }
+    }
+
+    pub fn actor(mut self, actor: Identity) -> Self {
+        self.actor = Some(actor);
+        self
+    }
+
+    pub fn target(mut self, target: impl ToString) -> Self {
+        self.target = Some(target.to_string());
+        self
+    }
+
+    pub fn failure(mut self, error: &ApiError) -> Self {
+        self.outcome = AuditOutcome::Failure;
+        self.error = Some(error.to_string());
+        self
+    }
+}
+
+/// The caller and source address of a request, for building audit events.
+/// Extracting it never fails.
+#[derive(Debug, Clone, Default)]
+pub struct AuditContext {
+    pub actor: Option<Identity>,
+    pub source_ip: Option<IpAddr>,
+}
+
+impl AuditContext {
+    pub fn from_extensions(extensions: &Extensions) -> Self {
+        Self {
+            actor: extensions.get::<Identity>().cloned(),
+            source_ip: extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip()),
+        }
+    }
+
+    pub fn event(&self, action: AuditAction) -> AuditEvent {
+        AuditEvent {
+            actor: self.actor.clone(),
+            source_ip: self.source_ip,
+            ..AuditEvent::new(action)
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for AuditContext {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(Self::from_extensions(&parts.extensions))
+    }
+}
+
+/// A line of the audit log. Each entry's hash covers the previous entry's
+/// hash, so editing or removing an entry breaks every hash after it.
+#[derive(Debug, Serialize, Deserialize)]
+struct ChainedEvent {
+    seq: u64,
+    prev_hash: String,
+    hash: String,
+    event: serde_json::Value,
+}
+
+fn chain_hash(seq: u64, prev_hash: &str, event: &serde_json::Value) -> String {
+    let data = format!("{}:{}:{}", seq, prev_hash, event);
+    hex::encode(generate_sha256(data.as_bytes()))
+}
+
+#[derive(Debug)]
+struct AuditWriter {
+    path: PathBuf,
+    file: File,
+    seq: u64,
+    last_hash: String,
+}
+
+impl AuditWriter {
+    /// Appends `event` without syncing it to disk.
+    fn append(&mut self, event: &AuditEvent) -> Result<(), ApiError> {
+        let event =
+            serde_json::to_value(event).map_err(|e| ApiError::SerializationError(e.to_string()))?;
+        let seq = self.seq + 1;
+        let entry = ChainedEvent {
+            seq,
+            hash: chain_hash(seq, &self.last_hash, &event),
+            prev_hash: std::mem::take(&mut self.last_hash),
+            event,
+        };
+
+        let mut line =
+            serde_json::to_vec(&entry).map_err(|e| ApiError::SerializationError(e.to_string()))?;
+        line.push(b'\n');
+
+        let written = self.file.write_all(&line);
+
+        // Chain on from this entry even if the write failed part way, so the
+        // gap shows up when the log is verified.
+        self.seq = seq;
+        self.last_hash = entry.hash;
+
+        written.map_err(|e| {
+            tracing::error!("Failed to write audit log {:?}: {}", self.path, e);
+            ApiError::InternalError
+        })
+    }
+
+    fn write(&mut self, event: &AuditEvent) {
+        if let Err(e) = self.append(event) {
+            tracing::error!("Failed to record audit event {:?}: {}", event, e);
+        }
+    }
+
+    fn sync(&mut self) {
+        if let Err(e) = self.file.sync_data() {
+            tracing::error!("Failed to sync audit log {:?}: {}", self.path, e);
+        }
+    }
+
+    /// Writes events until every sender is gone, syncing after each batch.
+    /// Auth failures from the same source, for the same target and error,
+    /// are written once per `COALESCE_WINDOW` with a count.
+    fn run(mut self, events: Receiver<AuditEvent>) {
+        let mut failures: Vec<AuditEvent> = Vec::new();
+        let mut window_start: Option<Instant> = None;
+
+        loop {
+            let received = match window_start {
+                None => events.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                Some(start) => events.recv_timeout(COALESCE_WINDOW.saturating_sub(start.elapsed())),
+            };
+
+            let closed = matches!(received, Err(RecvTimeoutError::Disconnected));
+            let batch = received.into_iter().chain(events.try_iter());
+
+            for event in batch {
+                if event.action != AuditAction::AuthFailure {
+                    self.write(&event);
+                    continue;
+                }
+
+                let same = failures.iter_mut().find(|f| {
+                    (&f.source_ip, &f.target, &f.error)
+                        == (&event.source_ip, &event.target, &event.error)
+                });
+                match same {
+                    Some(failure) => *failure.count.get_or_insert(1) += 1,
+                    None => failures.push(event),
+                }
+                window_start.get_or_insert_with(Instant::now);
+            }
+
+            if closed || window_start.is_some_and(|start| start.elapsed() >= COALESCE_WINDOW) {
+                for failure in failures.drain(..) {
+                    self.write(&failure);
+                }
+                window_start = None;
+            }
+
+            self.sync();
+
+            if closed {
+                break;
+            }
+        }
+    }
+}
+
+/// Where the chain of the audit log at `path` breaks, as a line number and
+/// a reason. Returns the number of entries and the last hash otherwise.
+pub fn verify(path: &Path) -> Result<(u64, String), (usize, String)> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            return Ok((0, GENESIS_HASH.to_string()))
+        }
+        Err(e) => return Err((0, e.to_string())),
+    };
+
+    let (mut seq, mut last_hash) = (0, GENESIS_HASH.to_string());
+
+    for (i, line) in BufReader::new(file).lines().enumerate() {
+        let line_no = i + 1;
+        let line = line.map_err(|e| (line_no, e.to_string()))?;
+        let entry: ChainedEvent =
+            serde_json::from_str(&line).map_err(|e| (line_no, e.to_string()))?;
+
+        if entry.seq != seq + 1 {
+            return Err((line_no, format!("expected seq {}", seq + 1)));
+        }
+        if entry.prev_hash != last_hash {
+            return Err((line_no, "previous hash does not match".to_string()));
+        }
+        if entry.hash != chain_hash(entry.seq, &entry.prev_hash, &entry.event) {
+            return Err((line_no, "hash does not match".to_string()));
+        }
+
+        seq = entry.seq;
+        last_hash = entry.hash;
+    }
+
+    Ok((seq, last_hash))
+}
+
+/// Last sequence number and hash to chain on from, even if the log has been
+/// tampered with or its last write was torn: the last line that parses.
+fn last_entry(path: &Path) -> Option<(u64, String)> {
+    let file = File::open(path).ok()?;
+    let lines: Vec<String> = BufReader::new(file).lines().map_while(Result::ok).collect();
+    lines
+        .iter()
+        .rev()
+        .find_map(|line| serde_json::from_str::<ChainedEvent>(line).ok())
+        .map(|entry| (entry.seq, entry.hash))
+}
+
+/// Starts a new line if the last write was torn, so the next entry doesn't
+/// end up on the same line as it.
+fn end_torn_line(file: &mut File) -> std::io::Result<()> {
+    use std::io::{Read, Seek, SeekFrom};
+
+    if file.metadata()?.len() == 0 {
+        return Ok(());
+    }
+
+    let mut last = [0u8];
+    file.seek(SeekFrom::End(-1))?;
+    file.read_exact(&mut last)?;
+    if last[0] != b'\n' {
+        file.write_all(b"\n")?;
+    }
+
+    Ok(())
+}
+
+/// Appends administrative and authentication events to a hash chained log
+/// file, from a thread of its own. Does nothing unless auditing is enabled.
+///
+/// The chain isn't keyed, so it shows entries being edited or removed from
+/// the middle of the log, but not the end of the log being cut off or the
+/// whole file being rewritten. Ship the log somewhere append-only to catch
+/// those.
+#[derive(Debug)]
+pub struct AuditLog {
+    sender: Mutex<Option<SyncSender<AuditEvent>>>,
+    thread: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl AuditLog {
+    pub fn open(config: &AuditConfig) -> Result<Self, ApiError> {
+        if !config.enabled {
+            return Ok(Self {
+                sender: Mutex::new(None),
+                thread: Mutex::new(None),
+            });
+        }
+
+        let path = PathBuf::from(&config.path);
+        let open_error = |e: std::io::Error| {
+            tracing::error!("Failed to open audit log {}: {}", config.path, e);
+            ApiError::ConfigError
+        };
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(open_error)?;
+        }
+
+        let (seq, last_hash) = match verify(&path) {
+            Ok(last) => last,
+            Err((line, reason)) => {
+                tracing::error!(
+                    "Audit log {} failed verification at line {}: {}",
+                    config.path,
+                    line,
+                    reason
+                );
+                let (seq, last_hash) = last_entry(&path).unwrap_or((0, GENESIS_HASH.to_string()));
+                tracing::warn!("Chaining audit log {} on from entry {}", config.path, seq);
+                (seq, last_hash)
+            }
+        };
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&path)
+            .map_err(open_error)?;
+        end_torn_line(&mut file).map_err(open_error)?;
+
+        let writer = AuditWriter {
+            path,
+            file,
+            seq,
+            last_hash,
+        };
+        let (sender, events) = std::sync::mpsc::sync_channel(QUEUE_SIZE);
+        let thread = std::thread::Builder::new()
+            .name("audit-log".to_string())
+            .spawn(move || writer.run(events))
+            .map_err(open_error)?;
+
+        tracing::info!("Writing audit events to {}", config.path);
+
+        Ok(Self {
+            sender: Mutex::new(Some(sender)),
+            thread: Mutex::new(Some(thread)),
+        })
+    }
+
+    /// Queues an event to be written. Failing to write it is logged, not
+    /// returned, so auditing never fails the request being audited.
+    pub fn record(&self, event: AuditEvent) {
+        let sender = self.sender.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(sender) = sender.as_ref() else {
+            return;
+        };
+
+        match sender.try_send(event) {
+            Ok(()) => {}
+            Err(TrySendError::Full(event)) => {
+                tracing::error!("Audit log queue is full, dropping {:?}", event)
+            }
+            Err(TrySendError::Disconnected(event)) => {
+                tracing::error!("Audit log writer has stopped, dropping {:?}", event)
+            }
+        }
+    }
+
+    /// Writes every queued event and stops the writer. Events recorded
+    /// afterwards are dropped.
+    pub fn close(&self) {
+        drop(self.sender.lock().unwrap_or_else(|e| e.into_inner()).take());
+
+        let thread = self.thread.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if let Some(Err(e)) = thread.map(JoinHandle::join) {
+            tracing::error!("Audit log writer panicked: {:?}", e);
+        }
+    }
+
+    /// Records `event` as a failure if `result` is an error.
+    pub fn record_result<T>(&self, event: AuditEvent, result: &Result<T, ApiError>) {
+        match result {
+            Ok(_) => self.record(event),
+            Err(e) => self.record(event.failure(e)),
+        }
+    }
+}
+
+impl Drop for AuditLog {
+    fn drop(&mut self) {
+        self.close();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    #[test]
+    fn test_hash_chain() {
+        let dir = TempDir::new("audit");
+        let config = AuditConfig {
+            enabled: true,
+            path: dir.path().join("audit.log").to_string_lossy().to_string(),
+        };
+        let path = PathBuf::from(&config.path);
+
+        let audit = AuditLog::open(&config).unwrap();
+        audit.record(AuditEvent::new(AuditAction::ConfigReload));
+        audit.record(
+            AuditEvent::new(AuditAction::Login)
+                .target("ada")
+                .failure(&ApiError::AuthError("Invalid password".to_string())),
+        );
+        drop(audit);
+
+        // Reopening chains on from the last entry.
+        AuditLog::open(&config)
+            .unwrap()
+            .record(AuditEvent::new(AuditAction::Logout));
+        assert_eq!(verify(&path).unwrap().0, 3);
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, log.replace("\"ada\"", "\"bob\"")).unwrap();
+        assert_eq!(verify(&path).unwrap_err().0, 2);
+
+        let lines: Vec<_> = log.lines().collect();
+        std::fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        assert_eq!(verify(&path).unwrap_err().0, 2);
+
+        // A torn last line is skipped over and left where it is.
+        let torn = &lines[2][..lines[2].len() / 2];
+        std::fs::write(&path, format!("{}\n{}\n{}", lines[0], lines[1], torn)).unwrap();
+        let audit = AuditLog::open(&config).unwrap();
+        audit.record(AuditEvent::new(AuditAction::Logout));
+        audit.close();
+        let log = std::fs::read_to_string(&path).unwrap();
+        let last: ChainedEvent = serde_json::from_str(log.lines().last().unwrap()).unwrap();
+        assert_eq!((last.seq, log.lines().count()), (3, 4));
+        assert_eq!(verify(&path).unwrap_err().0, 3);
+    }
+
+    #[test]
+    fn test_auth_failures_are_coalesced() {
+        let dir = TempDir::new("audit");
+        let config = AuditConfig {
+            enabled: true,
+            path: dir.path().join("audit.log").to_string_lossy().to_string(),
+        };
+
+        let audit = AuditLog::open(&config).unwrap();
+        let failure = |target: &str| {
+            AuditContext {
+                actor: None,
+                source_ip: Some(IpAddr::from([10, 0, 0, 1])),
+            }
+            .event(AuditAction::AuthFailure)
+            .target(target)
+            .failure(&ApiError::AuthError("Invalid API key".to_string()))
+        };
+        for _ in 0..3 {
+            audit.record(failure("/v1/records"));
+        }
+        audit.record(failure("/v1/traces"));
+        audit.close();
+
+        let counts: Vec<Option<u64>> = std::fs::read_to_string(&config.path)
+            .unwrap()
+            .lines()
+            .map(|line| serde_json::from_str::<ChainedEvent>(line).unwrap())
+            .map(|entry| entry.event["count"].as_u64())
+            .collect();
+        assert_eq!(counts, vec![Some(3), None]);
+    }
+}
@@ -0,0 +1,496 @@
+use super::{
+    audit::{AuditAction, AuditContext},
+    config::PtolemyConfig,
+    crypto::{generate_sha256, ClaimType, Claims, UuidClaims},
+    error::ApiError,
+    iam::{
+        api_keys::{ApiKeyPermission, ApiKeyStore},
+        rbac::SystemRole,
+        users::UserStore,
+    },
+    jwks::SigningKeys,
+    state::PtolemyState,
+};
+
+use axum::{
+    extract::{OriginalUri, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use http::{header::AUTHORIZATION, HeaderMap};
+use jsonwebtoken::jwk::JwkSet;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// Who a request was made by. Attached to every authenticated request as an
+/// extension.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Identity {
+    User {
+        id: Uuid,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        system_role: Option<SystemRole>,
+    },
+    ServiceApiKey {
+        id: Uuid,
+        workspace_id: Uuid,
+        permissions: ApiKeyPermission,
+    },
+    /// Authentication is disabled with `auth.enabled: false`.
+    Anonymous,
+}
+
+impl Identity {
+    /// Whether this identity may publish records. Only service API keys
+    /// with write access publish when authentication is enabled.
+    pub fn can_write(&self) -> bool {
+        match self {
+            Identity::ServiceApiKey { permissions, .. } => permissions.can_write(),
+            Identity::User { .. } => false,
+            Identity::Anonymous => true,
+        }
+    }
+
+    /// The workspace records published by this identity belong to.
+    pub fn workspace_id(&self) -> Option<Uuid> {
+        match self {
+            Identity::ServiceApiKey { workspace_id, .. } => Some(*workspace_id),
+            _ => None,
+        }
+    }
+
+    pub fn system_role(&self) -> Option<SystemRole> {
+        match self {
+            Identity::User { system_role, .. } => *system_role,
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Authenticator {
+    enabled: bool,
+    jwt_secret: Option<Vec<u8>>,
+    signing_keys: SigningKeys,
+    // SHA-256 of the key -> key id, for keys set in the config file
+    api_keys: HashMap<Vec<u8>, Uuid>,
+    config_keys: HashMap<Uuid, Identity>,
+    admins: HashSet<Uuid>,
+    access_token_ttl: Duration,
+    key_store: Arc<ApiKeyStore>,
+    users: Arc<UserStore>,
+}
+
+impl Authenticator {
+    pub fn from_config(
+        config: &PtolemyConfig,
+        key_store: Arc<ApiKeyStore>,
+        users: Arc<UserStore>,
+    ) -> Result<Self, ApiError> {
+        let auth = config.auth.clone().unwrap_or_default();
+        if !auth.enabled {
+            return Ok(Self {
+                enabled: false,
+                jwt_secret: None,
+                signing_keys: SigningKeys::default(),
+                api_keys: HashMap::new(),
+                config_keys: HashMap::new(),
+                admins: HashSet::new(),
+                access_token_ttl: Duration::ZERO,
+                key_store,
+                users,
+            });
+        }
+
+        let jwt_secret = auth
+            .jwt_secret
+            .as_ref()
+            .map(|secret| base64::engine::general_purpose::STANDARD.decode(secret))
+            .transpose()
+            .map_err(|e| {
+                tracing::error!("JWT secret is not valid base64: {}", e);
+                ApiError::ConfigError
+            })?;
+        let signing_keys = SigningKeys::from_config(&auth.signing_keys)?;
+
+        let api_keys = auth
+            .api_keys
+            .iter()
+            .map(|key| match hex::decode(&key.key_sha256) {
+                Ok(hash) if hash.len() == 32 => Ok((hash, key.id)),
+                _ => {
+                    tracing::error!("API key {} has an invalid SHA-256", key.id);
+                    Err(ApiError::ConfigError)
+                }
+            })
+            .collect::<Result<_, _>>()?;
+
+        let config_keys = auth
+            .api_keys
+            .iter()
+            .map(|key| {
+                let identity = Identity::ServiceApiKey {
+                    id: key.id,
+                    workspace_id: key.workspace_id,
+                    permissions: key.permissions,
+                };
+                (key.id, identity)
+            })
+            .collect();
+
+        Ok(Self {
+            enabled: true,
+            jwt_secret,
+            signing_keys,
+            api_keys,
+            config_keys,
+            admins: config.iam.admins.iter().cloned().collect(),
+            access_token_ttl: auth.access_token_ttl(),
+            key_store,
+            users,
+        })
+    }
+
+    /// Resolves a service API key by id, from the config file or the key
+    /// store. Revoked and expired keys resolve to `None`.
+    fn service_api_key(&self, id: &Uuid) -> Option<Identity> {
+        if let Some(identity) = self.config_keys.get(id) {
+            return Some(identity.clone());
+        }
+
+        let key = self.key_store.get(id).filter(|key| key.is_active())?;
+        Some(Identity::ServiceApiKey {
+            id: key.id,
+            workspace_id: key.workspace_id,
+            permissions: key.permissions,
+        })
+    }
+
+    /// Resolves an active user by id. Users listed in `iam.admins` are
+    /// system admins.
+    pub fn user(&self, id: &Uuid) -> Option<Identity> {
+        let user = self.users.get(id).filter(|user| user.is_active())?;
+        let system_role = user
+            .system_role
+            .or(self.admins.contains(id).then_some(SystemRole::Admin));
+
+        Some(Identity::User {
+            id: *id,
+            system_role,
+        })
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn access_token_ttl(&self) -> Duration {
+        self.access_token_ttl
+    }
+
+    /// Public keys tokens can be verified with.
+    pub fn jwks(&self) -> JwkSet {
+        self.signing_keys.jwks()
+    }
+
+    /// Signs a `UserJWT` for `user_id`, valid for `access_token_ttl_secs`,
+    /// with the current signing key or else the JWT secret.
+    pub fn issue_access_token(&self, user_id: Uuid) -> Result<String, ApiError> {
+        let claims = Claims::new(
+            user_id,
+            ClaimType::UserJWT,
+            self.access_token_ttl.as_secs() as usize,
+        );
+
+        if let Some(key) = self.signing_keys.current() {
+            return claims.generate_signed_token(&key.header(), key.encoding_key());
+        }
+
+        match &self.jwt_secret {
+            Some(secret) => claims.generate_auth_token(secret),
+            None => {
+                tracing::warn!("Can't issue tokens without a signing key or JWT secret");
+                Err(ApiError::Unavailable)
+            }
+        }
+    }
+
+    fn decode_token(&self, token: &str) -> Result<UuidClaims, ApiError> {
+        let invalid = || ApiError::AuthError("Invalid token".to_string());
+        let header = jsonwebtoken::decode_header(token).map_err(|_| invalid())?;
+
+        match (&header.kid, &self.jwt_secret) {
+            (Some(kid), _) => {
+                let key = self.signing_keys.get(kid).ok_or_else(invalid)?;
+                UuidClaims::from_signed_token(token, key.decoding_key(), key.algorithm())
+            }
+            (None, Some(secret)) => {
+                UuidClaims::from_token(Some(token.to_string()), secret)?.ok_or_else(invalid)
+            }
+            (None, None) => Err(ApiError::AuthError(
+                "Bearer tokens are not accepted".to_string(),
+            )),
+        }
+    }
+
+    /// Resolves the identity behind an `x-api-key` header or a Bearer token.
+    pub fn authenticate(&self, headers: &HeaderMap) -> Result<Identity, ApiError> {
+        if !self.enabled {
+            return Ok(Identity::Anonymous);
+        }
+
+        if let Some(api_key) = headers.get(API_KEY_HEADER) {
+            let hash = generate_sha256(api_key.as_bytes());
+            let id = match self.api_keys.get(&hash) {
+                Some(id) => Some(*id),
+                None => self.key_store.find_by_hash(&hash).map(|key| key.id),
+            };
+
+            return id
+                .and_then(|id| self.service_api_key(&id))
+                .ok_or_else(|| ApiError::AuthError("Invalid API key".to_string()));
+        }
+
+        if let Some(authorization) = headers.get(AUTHORIZATION) {
+            let token = authorization
+                .to_str()
+                .ok()
+                .and_then(|value| value.strip_prefix("Bearer "))
+                .ok_or_else(|| ApiError::AuthError("Invalid authorization header".to_string()))?;
+
+            let claims = self.decode_token(token)?;
+
+            return match claims.claim_type() {
+                ClaimType::UserJWT => self
+                    .user(claims.sub())
+                    .ok_or_else(|| ApiError::AuthError("Unknown user".to_string())),
+                ClaimType::ServiceAPIKeyJWT => self
+                    .service_api_key(claims.sub())
+                    .ok_or_else(|| ApiError::AuthError("API key revoked".to_string())),
+            };
+        }
+
+        Err(ApiError::AuthError("Missing credentials".to_string()))
+    }
+}
+
+fn is_grpc(request: &Request) -> bool {
+    request
+        .headers()
+        .get(http::header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/grpc"))
+}
+
+/// Rejects requests without valid credentials and attaches the `Identity`
+/// of those with them.
+pub async fn authenticate(
+    State(state): State<PtolemyState>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    match state.authenticator.load().authenticate(request.headers()) {
+        Ok(identity) => {
+            request.extensions_mut().insert(identity);
+            next.run(request).await
+        }
+        Err(e) => {
+            tracing::debug!("Rejected unauthenticated request: {}", e);
+            state.audit.record(
+                AuditContext::from_extensions(request.extensions())
+                    .event(AuditAction::AuthFailure)
+                    .target(
+                        request
+                            .extensions()
+                            .get::<OriginalUri>()
+                            .map_or(request.uri().path(), |uri| uri.path()),
+                    )
+                    .failure(&e),
+            );
+
+            match is_grpc(&request) {
+                true => tonic::Status::from(e)
+                    .into_http()
+                    .map(axum::body::Body::new),
+                false => e.into_response(),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::config::auth::{ApiKeyConfig, AuthConfig};
+    use crate::api::config::iam::IamConfig;
+    use crate::api::crypto::PasswordHandler;
+    use crate::api::iam::{api_keys::NewApiKey, users::NewUser};
+    use crate::test_util::TempDir;
+
+    const SECRET: &[u8] = b"secret";
+
+    struct Stores {
+        api_keys: Arc<ApiKeyStore>,
+        users: Arc<UserStore>,
+        _dir: TempDir,
+    }
+
+    fn stores() -> Stores {
+        let dir = TempDir::new("iam");
+        let config = IamConfig {
+            path: dir.path_string(),
+            ..Default::default()
+        };
+
+        Stores {
+            api_keys: Arc::new(ApiKeyStore::open(&config).unwrap()),
+            users: Arc::new(UserStore::open(&config, PasswordHandler::new()).unwrap()),
+            _dir: dir,
+        }
+    }
+
+    fn authenticator(api_key: &str, key_id: Uuid, stores: &Stores) -> Authenticator {
+        let config = PtolemyConfig {
+            auth: Some(AuthConfig {
+                jwt_secret: Some(base64::engine::general_purpose::STANDARD.encode(SECRET)),
+                api_keys: vec![ApiKeyConfig {
+                    id: key_id,
+                    workspace_id: Uuid::nil(),
+                    key_sha256: hex::encode(generate_sha256(api_key.as_bytes())),
+                    permissions: ApiKeyPermission::WriteOnly,
+                }],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+
+        Authenticator::from_config(&config, stores.api_keys.clone(), stores.users.clone()).unwrap()
+    }
+
+    fn headers(name: http::HeaderName, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(name, value.parse().unwrap());
+        headers
+    }
+
+    fn bearer(token: &str) -> HeaderMap {
+        headers(AUTHORIZATION, &format!("Bearer {}", token))
+    }
+
+    #[test]
+    fn test_api_key() {
+        let key_id = Uuid::new_v4();
+        let auth = authenticator("pt-sk-valid", key_id, &stores());
+        let name = http::HeaderName::from_static(API_KEY_HEADER);
+
+        assert_eq!(
+            auth.authenticate(&headers(name.clone(), "pt-sk-valid")),
+            Ok(Identity::ServiceApiKey {
+                id: key_id,
+                workspace_id: Uuid::nil(),
+                permissions: ApiKeyPermission::WriteOnly
+            })
+        );
+        assert!(auth.authenticate(&headers(name, "pt-sk-invalid")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_stored_api_key() {
+        let stores = stores();
+        let auth = authenticator("pt-sk-valid", Uuid::new_v4(), &stores);
+        let name = http::HeaderName::from_static(API_KEY_HEADER);
+
+        let new_key = NewApiKey {
+            name: "dashboards".to_string(),
+            permissions: ApiKeyPermission::ReadOnly,
+            expires_at: None,
+        };
+        let (key, api_key) = stores
+            .api_keys
+            .create(Uuid::new_v4(), new_key)
+            .await
+            .unwrap();
+        let token = Claims::new(key.id, ClaimType::ServiceAPIKeyJWT, 60)
+            .generate_auth_token(SECRET)
+            .unwrap();
+
+        let identity = auth.authenticate(&headers(name.clone(), &api_key)).unwrap();
+        assert!(!identity.can_write());
+        assert_eq!(auth.authenticate(&bearer(&token)), Ok(identity));
+
+        stores.api_keys.revoke(&key.workspace_id, &key.id).unwrap();
+        assert!(auth.authenticate(&headers(name, &api_key)).is_err());
+        assert!(auth.authenticate(&bearer(&token)).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bearer_token() {
+        let stores = stores();
+        let auth = authenticator("pt-sk-valid", Uuid::new_v4(), &stores);
+
+        let user = stores
+            .users
+            .create(NewUser {
+                username: "ada".to_string(),
+                password: "hunter2".to_string(),
+                display_name: None,
+                system_role: None,
+            })
+            .await
+            .unwrap();
+
+        let token = auth.issue_access_token(user.id).unwrap();
+        let forged = Claims::new(user.id, ClaimType::UserJWT, 60)
+            .generate_auth_token(b"not the secret")
+            .unwrap();
+
+        assert_eq!(
+            auth.authenticate(&bearer(&token)),
+            Ok(Identity::User {
+                id: user.id,
+                system_role: None
+            })
+        );
+        assert!(auth.authenticate(&bearer(&forged)).is_err());
+
+        stores.users.delete(&user.id).unwrap();
+        assert!(auth.authenticate(&bearer(&token)).is_err());
+    }
+
+    #[test]
+    fn test_missing_credentials() {
+        let stores = stores();
+        let auth = authenticator("pt-sk-valid", Uuid::new_v4(), &stores);
+        assert!(auth.authenticate(&HeaderMap::new()).is_err());
+
+        let from_config = |auth| {
+            let config = PtolemyConfig {
+                auth,
+                ..Default::default()
+            };
+            Authenticator::from_config(&config, stores.api_keys.clone(), stores.users.clone())
+                .unwrap()
+        };
+
+        // A missing section doesn't turn authentication off.
+        let missing = from_config(None);
+        assert!(missing.is_enabled());
+        assert!(missing.authenticate(&HeaderMap::new()).is_err());
+
+        let disabled = from_config(Some(AuthConfig {
+            enabled: false,
+            ..Default::default()
+        }));
+        assert_eq!(
+            disabled.authenticate(&HeaderMap::new()),
+            Ok(Identity::Anonymous)
+        );
+    }
+}
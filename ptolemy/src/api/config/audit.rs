@@ -0,0 +1,19 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuditConfig {
+    /// Set with `ENABLE_AUDITING`.
+    pub enabled: bool,
+    /// File audit events are appended to, one JSON object per line.
+    pub path: String,
+}
+
+impl Default for AuditConfig {
+    fn default() -> Self {
+        AuditConfig {
+            enabled: false,
+            path: "/ptolemy/data/audit/audit.log".to_string(),
+        }
+    }
+}
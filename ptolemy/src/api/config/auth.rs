@@ -0,0 +1,84 @@
+use crate::api::iam::api_keys::ApiKeyPermission;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+/// Authentication for ingestion endpoints. It is on even when this section
+/// is missing, in which case only keys in the IAM store are accepted.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AuthConfig {
+    /// Setting this to `false` lets anyone publish records.
+    pub enabled: bool,
+    /// Base64 encoded secret JWTs are signed with. Bearer tokens are
+    /// rejected, and no tokens are issued, when unset.
+    pub jwt_secret: Option<String>,
+    /// Asymmetric keys tokens are signed with instead of `jwt_secret`,
+    /// published at `/.well-known/jwks.json`.
+    pub signing_keys: Vec<SigningKeyConfig>,
+    pub api_keys: Vec<ApiKeyConfig>,
+    pub access_token_ttl_secs: u64,
+    pub refresh_token_ttl_secs: u64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            jwt_secret: None,
+            signing_keys: Vec::new(),
+            api_keys: Vec::new(),
+            access_token_ttl_secs: 15 * 60,
+            refresh_token_ttl_secs: 30 * 24 * 60 * 60,
+        }
+    }
+}
+
+impl AuthConfig {
+    pub fn access_token_ttl(&self) -> Duration {
+        Duration::from_secs(self.access_token_ttl_secs)
+    }
+
+    pub fn refresh_token_ttl(&self) -> Duration {
+        Duration::from_secs(self.refresh_token_ttl_secs)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SigningAlgorithm {
+    RS256,
+    EdDSA,
+}
+
+/// A PEM encoded private key, PKCS#8 or (for RS256) PKCS#1.
+///
+/// Tokens are signed with the key with the latest `not_before` that has
+/// passed, so a rotation can be scheduled by adding the next key ahead of
+/// time. Keys are published until `not_after`, which should leave enough
+/// time for tokens signed with them to expire.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SigningKeyConfig {
+    pub kid: String,
+    pub algorithm: SigningAlgorithm,
+    pub private_key_path: String,
+    #[serde(default)]
+    pub not_before: Option<DateTime<Utc>>,
+    #[serde(default)]
+    pub not_after: Option<DateTime<Utc>>,
+}
+
+/// A service API key, stored as the hex SHA-256 of the key. Keys created
+/// through the API are kept in the IAM store instead.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKeyConfig {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub key_sha256: String,
+    #[serde(default = "default_permissions")]
+    pub permissions: ApiKeyPermission,
+}
+
+fn default_permissions() -> ApiKeyPermission {
+    ApiKeyPermission::ReadWrite
+}
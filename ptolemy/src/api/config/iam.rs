@@ -0,0 +1,40 @@
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IamConfig {
+    /// Directory API keys, users and workspaces are stored in.
+    pub path: String,
+    /// Reject new API keys without an expiry date.
+    pub require_api_key_expiry: bool,
+    /// Users granted the system admin role.
+    pub admins: Vec<Uuid>,
+    /// Set with `PTOLEMY_USER` and `PTOLEMY_PASS`.
+    pub sysadmin: Option<SysadminConfig>,
+}
+
+#[derive(Clone, PartialEq, Serialize, Deserialize)]
+pub struct SysadminConfig {
+    pub username: String,
+    pub password: String,
+}
+
+impl std::fmt::Debug for SysadminConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SysadminConfig")
+            .field("username", &self.username)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for IamConfig {
+    fn default() -> Self {
+        IamConfig {
+            path: "/ptolemy/data/iam".to_string(),
+            require_api_key_expiry: false,
+            admins: Vec::new(),
+            sysadmin: None,
+        }
+    }
+}
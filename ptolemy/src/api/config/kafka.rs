@@ -1,4 +1,4 @@
-use super::serialization_method::SerializationMethod;
+use super::{routing::RoutingConfig, serialization_method::SerializationMethod};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -28,6 +28,12 @@ pub struct KafkaConfig {
     // --- Observability ---
     pub enable_stats: Option<bool>,     // toggle metrics collection
     pub stats_interval_ms: Option<u32>, // metrics emit interval
+
+    // --- Routing ---
+    #[serde(default)]
+    pub routing: RoutingConfig,
+    #[serde(default = "super::sinks::required")]
+    pub required: bool,
 }
 
 impl Default for KafkaConfig {
@@ -49,6 +55,8 @@ impl Default for KafkaConfig {
             serialization: SerializationMethod::Json,
             enable_stats: Some(false),
             stats_interval_ms: Some(60_000),
+            routing: RoutingConfig::default(),
+            required: true,
         }
     }
 }
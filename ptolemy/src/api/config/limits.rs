@@ -0,0 +1,65 @@
+use serde::{de::Error, Deserialize, Deserializer, Serialize};
+
+/// Ingestion limits, applied separately to each API key, each workspace and
+/// each client IP. Unset limits don't apply.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitsConfig {
+    pub api_key: LimitConfig,
+    pub workspace: LimitConfig,
+    pub client_ip: LimitConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LimitConfig {
+    #[serde(deserialize_with = "positive_rate")]
+    pub records_per_sec: Option<f64>,
+    #[serde(deserialize_with = "positive_rate")]
+    pub bytes_per_sec: Option<f64>,
+    /// How many seconds of unused rate can be saved up for a burst.
+    #[serde(deserialize_with = "positive")]
+    pub burst_secs: f64,
+    /// Quotas reset at midnight UTC, and on restart.
+    pub daily_records: Option<u64>,
+    pub daily_bytes: Option<u64>,
+}
+
+impl Default for LimitConfig {
+    fn default() -> Self {
+        Self {
+            records_per_sec: None,
+            bytes_per_sec: None,
+            burst_secs: 1.0,
+            daily_records: None,
+            daily_bytes: None,
+        }
+    }
+}
+
+impl LimitConfig {
+    pub fn is_empty(&self) -> bool {
+        self.records_per_sec.is_none()
+            && self.bytes_per_sec.is_none()
+            && self.daily_records.is_none()
+            && self.daily_bytes.is_none()
+    }
+}
+
+/// Rates and bursts divide the time to wait, so they have to be positive.
+fn positive<'de, D: Deserializer<'de>>(deserializer: D) -> Result<f64, D::Error> {
+    let value = f64::deserialize(deserializer)?;
+    match value.is_finite() && value > 0.0 {
+        true => Ok(value),
+        false => Err(D::Error::custom(format!(
+            "expected a positive number, got {}",
+            value
+        ))),
+    }
+}
+
+fn positive_rate<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<f64>, D::Error> {
+    Option::<f64>::deserialize(deserializer)?
+        .map(|rate| positive(serde::de::value::F64Deserializer::new(rate)))
+        .transpose()
+}
@@ -0,0 +1,63 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// One line per event.
+    Text,
+    /// Multiple lines per event, for reading locally.
+    Pretty,
+    /// One JSON object per line, with the spans each event happened in.
+    Json,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Minutely,
+    Hourly,
+    Daily,
+    Never,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LogFileConfig {
+    pub directory: String,
+    pub prefix: String,
+    pub rotation: LogRotation,
+    /// Log files kept, deleting the oldest. All are kept when unset.
+    pub max_files: Option<usize>,
+}
+
+impl Default for LogFileConfig {
+    fn default() -> Self {
+        Self {
+            directory: "/ptolemy/logs".to_string(),
+            prefix: "ptolemy.log".to_string(),
+            rotation: LogRotation::Daily,
+            max_files: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LoggingConfig {
+    pub format: LogFormat,
+    /// Which events are logged, in `RUST_LOG` syntax. `RUST_LOG` takes
+    /// precedence when set.
+    pub filter: String,
+    /// Also log to a file, in the same format.
+    pub file: Option<LogFileConfig>,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::Text,
+            filter: "info".to_string(),
+            file: None,
+        }
+    }
+}
@@ -3,14 +3,37 @@ use figment::{
     Figment,
 };
 use serde::{Deserialize, Serialize};
+use std::time::Duration;
 
 use super::error::ApiError;
+use crate::writer::{OverflowConfig, WriterConfig};
 
+use self::audit::AuditConfig;
+use self::auth::AuthConfig;
+use self::iam::IamConfig;
 use self::kafka::KafkaConfig;
+use self::limits::LimitsConfig;
+use self::logging::LoggingConfig;
+use self::otlp::OtlpConfig;
+use self::server::ServerConfig;
+use self::sinks::SinkConfig;
+use self::spool::SpoolConfig;
 use self::stdout::StdoutConfig;
+use self::telemetry::TelemetryConfig;
 
+pub mod audit;
+pub mod auth;
+pub mod iam;
 pub mod kafka;
+pub mod limits;
+pub mod logging;
+pub mod otlp;
+pub mod routing;
+pub mod server;
+pub mod sinks;
+pub mod spool;
 pub mod stdout;
+pub mod telemetry;
 
 pub mod serialization_method {
     use serde::{Deserialize, Serialize};
@@ -25,34 +48,174 @@ pub mod serialization_method {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PtolemyConfig {
+    pub server: ServerConfig,
+    pub auth: Option<AuthConfig>,
+    pub iam: IamConfig,
+    pub audit: AuditConfig,
+    pub limits: LimitsConfig,
+    pub otlp: OtlpConfig,
+    pub logging: LoggingConfig,
+    pub telemetry: TelemetryConfig,
     pub buffer_size: usize,
+    pub batch_size: usize,
+    pub flush_interval_ms: Option<u64>,
     pub sink_timeout_secs: usize,
+    pub health_check_interval_secs: Option<u64>,
+    pub config_poll_interval_secs: Option<u64>,
+    pub shutdown_timeout: u64, // seconds
+    pub spool: Option<SpoolConfig>,
+    pub overflow: Option<OverflowConfig>,
     pub stdout: Option<StdoutConfig>,
     pub kafka: Option<KafkaConfig>,
+    pub sinks: Vec<SinkConfig>,
 }
 
 impl Default for PtolemyConfig {
     fn default() -> Self {
         Self {
+            server: ServerConfig::default(),
+            auth: None,
+            iam: IamConfig::default(),
+            audit: AuditConfig::default(),
+            limits: LimitsConfig::default(),
+            otlp: OtlpConfig::default(),
+            logging: LoggingConfig::default(),
+            telemetry: TelemetryConfig::default(),
             buffer_size: 1024,
+            batch_size: 100,
+            flush_interval_ms: Some(500),
             sink_timeout_secs: 10,
+            health_check_interval_secs: Some(30),
+            config_poll_interval_secs: Some(5),
+            shutdown_timeout: 10,
+            spool: None,
+            overflow: None,
             stdout: None,
             kafka: None,
+            sinks: Vec::new(),
         }
     }
 }
 
 impl PtolemyConfig {
+    pub fn path() -> String {
+        std::env::var("PTOLEMY_CONFIG").unwrap_or_else(|_| "ptolemy.yml".into())
+    }
+
     pub fn from_file() -> Result<Self, ApiError> {
-        let config_path = std::env::var("PTOLEMY_CONFIG").unwrap_or_else(|_| "ptolemy.yml".into());
+        Self::figment().extract().map_err(|e| {
+            tracing::error!("{:?}", e);
+            ApiError::ConfigError
+        })
+    }
 
+    /// Defaults, overridden by the config file, overridden by the
+    /// environment.
+    pub fn figment() -> Figment {
         Figment::from(Serialized::defaults(Self::default()))
-            .merge(Yaml::file(config_path))
+            .merge(Yaml::file(Self::path()))
             .merge(Env::prefixed("PTOLEMY_"))
-            .extract()
-            .map_err(|e| {
-                tracing::error!("{:?}", e);
-                ApiError::ConfigError
-            })
+            .merge(Env::raw().only(&["SHUTDOWN_TIMEOUT"]))
+            .merge(
+                Env::raw()
+                    .only(&["JWT_SECRET"])
+                    .map(|_| "auth.jwt_secret".into()),
+            )
+            .merge(
+                Env::raw()
+                    .only(&["ENABLE_AUDITING"])
+                    .map(|_| "audit.enabled".into()),
+            )
+            .merge(
+                Env::raw()
+                    .only(&["PTOLEMY_USER", "PTOLEMY_PASS"])
+                    .map(|key| match key == "PTOLEMY_USER" {
+                        true => "iam.sysadmin.username".into(),
+                        false => "iam.sysadmin.password".into(),
+                    }),
+            )
+    }
+
+    /// Every configured sink, including those set with the `stdout` and
+    /// `kafka` keys.
+    pub fn sink_configs(&self) -> Result<Vec<SinkConfig>, ApiError> {
+        let mut sinks = Vec::new();
+
+        if let Some(stdout) = &self.stdout {
+            sinks.push(SinkConfig::new("stdout", stdout.routing.clone(), stdout)?);
+        }
+
+        if let Some(kafka) = &self.kafka {
+            sinks.push(SinkConfig::new("kafka", kafka.routing.clone(), kafka)?);
+        }
+
+        sinks.extend(self.sinks.iter().cloned());
+
+        Ok(sinks)
+    }
+
+    pub fn writer_config(&self) -> WriterConfig {
+        WriterConfig {
+            buffer_size: self.buffer_size,
+            batch_size: self.batch_size,
+            flush_interval: self.flush_interval_ms.map(Duration::from_millis),
+        }
+    }
+
+    pub fn sink_timeout(&self) -> Duration {
+        Duration::from_secs(self.sink_timeout_secs as u64)
+    }
+
+    pub fn health_check_interval(&self) -> Option<Duration> {
+        self.health_check_interval_secs.map(Duration::from_secs)
+    }
+
+    pub fn shutdown_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_timeout)
+    }
+
+    pub fn config_poll_interval(&self) -> Option<Duration> {
+        self.config_poll_interval_secs.map(Duration::from_secs)
+    }
+
+    /// Settings that differ from `other` but only take effect on restart.
+    pub fn restart_required(&self, other: &Self) -> Vec<&'static str> {
+        let mut changed = Vec::new();
+
+        if self.server != other.server {
+            changed.push("server");
+        }
+        if self.buffer_size != other.buffer_size {
+            changed.push("buffer_size");
+        }
+        if self.batch_size != other.batch_size {
+            changed.push("batch_size");
+        }
+        if self.flush_interval_ms != other.flush_interval_ms {
+            changed.push("flush_interval_ms");
+        }
+        if self.sink_timeout_secs != other.sink_timeout_secs {
+            changed.push("sink_timeout_secs");
+        }
+        if self.spool != other.spool {
+            changed.push("spool");
+        }
+        if self.overflow != other.overflow {
+            changed.push("overflow");
+        }
+        if self.iam.path != other.iam.path {
+            changed.push("iam.path");
+        }
+        if self.audit != other.audit {
+            changed.push("audit");
+        }
+        if self.logging != other.logging {
+            changed.push("logging");
+        }
+        if self.telemetry != other.telemetry {
+            changed.push("telemetry");
+        }
+
+        changed
     }
 }
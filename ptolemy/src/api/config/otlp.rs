@@ -0,0 +1,72 @@
+use crate::models::Tier;
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// How OpenTelemetry spans received over OTLP are turned into records.
+///
+/// A span's tier is, in order of preference: the tier named by its
+/// `tier_attribute`, the tier its span kind maps to in `tiers`, `root_tier`
+/// for spans without a parent, and `default_tier`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OtlpConfig {
+    pub enabled: bool,
+    pub tier_attribute: String,
+    /// Attributes naming the kind of operation a span is, checked in order.
+    pub span_kind_attributes: Vec<String>,
+    /// Tiers by span kind, e.g. `LLM: COMPONENT`.
+    pub tiers: HashMap<String, Tier>,
+    pub root_tier: Tier,
+    pub default_tier: Tier,
+    /// Attributes recorded as inputs. Each also matches the attributes nested
+    /// under it, so `gen_ai.prompt` matches `gen_ai.prompt.0.content`.
+    pub input_attributes: Vec<String>,
+    /// Attributes recorded as outputs, matched like `input_attributes`.
+    pub output_attributes: Vec<String>,
+    /// Attributes gathered into the event's parameters, matched like
+    /// `input_attributes`.
+    pub parameter_attributes: Vec<String>,
+}
+
+fn strings(values: &[&str]) -> Vec<String> {
+    values.iter().map(|v| v.to_string()).collect()
+}
+
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        let tiers = [
+            ("AGENT", Tier::Subsystem),
+            ("CHAIN", Tier::Subsystem),
+            ("LLM", Tier::Component),
+            ("TOOL", Tier::Component),
+            ("RETRIEVER", Tier::Component),
+            ("EMBEDDING", Tier::Component),
+            ("RERANKER", Tier::Component),
+            ("GUARDRAIL", Tier::Component),
+            ("EVALUATOR", Tier::Component),
+            ("chat", Tier::Component),
+            ("text_completion", Tier::Component),
+            ("embeddings", Tier::Component),
+        ];
+
+        Self {
+            enabled: true,
+            tier_attribute: "ptolemy.tier".to_string(),
+            span_kind_attributes: strings(&["openinference.span.kind", "gen_ai.operation.name"]),
+            tiers: tiers
+                .into_iter()
+                .map(|(kind, tier)| (kind.to_string(), tier))
+                .collect(),
+            root_tier: Tier::System,
+            default_tier: Tier::Subcomponent,
+            input_attributes: strings(&["gen_ai.prompt", "input.value", "llm.input_messages"]),
+            output_attributes: strings(&[
+                "gen_ai.completion",
+                "output.value",
+                "llm.output_messages",
+            ]),
+            parameter_attributes: strings(&["gen_ai.request", "llm.invocation_parameters"]),
+        }
+    }
+}
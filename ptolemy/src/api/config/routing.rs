@@ -0,0 +1,39 @@
+use crate::models::{RecordType, Tier};
+use serde::{Deserialize, Serialize};
+
+/// Decides which records a sink receives. A record is delivered if it matches
+/// `include` (or `include` is unset) and does not match `exclude`.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RoutingConfig {
+    pub include: Option<RecordFilter>,
+    pub exclude: Option<RecordFilter>,
+}
+
+impl RoutingConfig {
+    pub fn is_empty(&self) -> bool {
+        self.include.is_none() && self.exclude.is_none()
+    }
+}
+
+/// A filter matches a record if every non-empty list matches it, and a list
+/// matches if any of its entries does. `event_names` and `field_names` accept
+/// `*` wildcards.
+///
+/// Records other than events are matched on `environments` and `event_names`
+/// using the event they belong to. Events are only remembered in memory, for
+/// the most recent 100,000, so records whose event hasn't been seen since the
+/// server started, or has been forgotten, can't be matched this way. They
+/// pass `include` and aren't caught by `exclude`, so they are delivered
+/// rather than lost.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RecordFilter {
+    pub record_types: Vec<RecordType>,
+    pub tiers: Vec<Tier>,
+    pub environments: Vec<String>,
+    pub event_names: Vec<String>,
+    pub field_names: Vec<String>,
+    /// Workspace ids, for sending each workspace's records to its own sink.
+    pub workspaces: Vec<String>,
+}
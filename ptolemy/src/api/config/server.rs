@@ -0,0 +1,70 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Serve gRPC on its own port instead of alongside HTTP on `port`.
+    pub grpc_port: Option<u16>,
+    /// Connections accepted per listener before new ones wait for a slot.
+    pub max_connections: Option<usize>,
+    /// Interval for TCP keep-alive probes and HTTP/2 pings. `None` disables
+    /// both.
+    pub keep_alive_secs: Option<u64>,
+    pub request_body_limit_bytes: usize,
+    pub tls: Option<TlsConfig>,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "::".to_string(),
+            port: 7865,
+            grpc_port: None,
+            max_connections: None,
+            keep_alive_secs: Some(60),
+            request_body_limit_bytes: 4 * 1024 * 1024,
+            tls: None,
+        }
+    }
+}
+
+impl ServerConfig {
+    pub fn keep_alive(&self) -> Option<Duration> {
+        self.keep_alive_secs.map(Duration::from_secs)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    /// PEM certificate chain, leaf first.
+    pub cert_path: String,
+    pub key_path: String,
+    /// PEM bundle of CAs trusted to sign client certificates. Setting this
+    /// turns on client certificate verification (mTLS).
+    pub client_ca_path: Option<String>,
+    /// Reject clients that don't present a certificate. Only applies when
+    /// `client_ca_path` is set.
+    #[serde(default = "default_require_client_cert")]
+    pub require_client_cert: bool,
+    /// How often to check the certificate files for changes. `None` disables
+    /// hot reloading.
+    #[serde(default = "default_tls_reload_interval_secs")]
+    pub reload_interval_secs: Option<u64>,
+}
+
+fn default_require_client_cert() -> bool {
+    true
+}
+
+fn default_tls_reload_interval_secs() -> Option<u64> {
+    Some(60)
+}
+
+impl TlsConfig {
+    pub fn reload_interval(&self) -> Option<Duration> {
+        self.reload_interval_secs.map(Duration::from_secs)
+    }
+}
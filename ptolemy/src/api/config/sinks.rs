@@ -0,0 +1,73 @@
+use super::super::error::ApiError;
+use super::routing::RoutingConfig;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// An entry in the `sinks` list. Everything besides `type`, `name`,
+/// `routing` and `required` is handed to the sink's factory as its settings.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SinkConfig {
+    #[serde(rename = "type")]
+    pub sink_type: String,
+    /// Tells apart sinks of the same type. Defaults to the type.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub routing: RoutingConfig,
+    /// Whether the server is only ready while this sink is healthy.
+    #[serde(default = "required")]
+    pub required: bool,
+    #[serde(flatten)]
+    pub settings: Map<String, Value>,
+}
+
+/// Sinks are required for readiness unless configured otherwise.
+pub(super) fn required() -> bool {
+    true
+}
+
+impl SinkConfig {
+    pub fn new(
+        sink_type: impl Into<String>,
+        routing: RoutingConfig,
+        settings: impl Serialize,
+    ) -> Result<Self, ApiError> {
+        let mut settings = match serde_json::to_value(settings) {
+            Ok(Value::Object(mut settings)) => {
+                settings.remove("routing");
+                settings
+            }
+            Ok(Value::Null) => Map::new(),
+            Ok(_) | Err(_) => {
+                tracing::error!("Sink settings must serialize to a map");
+                return Err(ApiError::ConfigError);
+            }
+        };
+
+        let required = match settings.remove("required") {
+            Some(Value::Bool(required)) => required,
+            _ => required(),
+        };
+
+        Ok(Self {
+            sink_type: sink_type.into(),
+            name: None,
+            routing,
+            required,
+            settings,
+        })
+    }
+
+    /// The name the sink is registered, health checked and spooled under.
+    pub fn name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.sink_type)
+    }
+
+    /// Deserializes the sink's settings into its own config type.
+    pub fn settings<T: DeserializeOwned>(&self) -> Result<T, ApiError> {
+        serde_json::from_value(Value::Object(self.settings.clone())).map_err(|e| {
+            tracing::error!("Invalid settings for sink {}: {}", self.name(), e);
+            ApiError::ConfigError
+        })
+    }
+}
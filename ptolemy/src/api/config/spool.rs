@@ -0,0 +1,21 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SpoolConfig {
+    pub path: String,
+    pub segment_size_bytes: u64,
+    pub max_size_bytes: Option<u64>, // reject new records once reached
+    pub fsync: bool,                 // sync every append to disk before acking
+}
+
+impl Default for SpoolConfig {
+    fn default() -> Self {
+        SpoolConfig {
+            path: "/ptolemy/data/spool".to_string(),
+            segment_size_bytes: 64 * 1024 * 1024,
+            max_size_bytes: None,
+            fsync: true,
+        }
+    }
+}
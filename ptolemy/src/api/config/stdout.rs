@@ -1,15 +1,21 @@
-use super::serialization_method::SerializationMethod;
+use super::{routing::RoutingConfig, serialization_method::SerializationMethod};
 use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StdoutConfig {
     serialization: SerializationMethod,
+    #[serde(default)]
+    pub routing: RoutingConfig,
+    #[serde(default = "super::sinks::required")]
+    pub required: bool,
 }
 
 impl Default for StdoutConfig {
     fn default() -> StdoutConfig {
         StdoutConfig {
             serialization: SerializationMethod::Json,
+            routing: RoutingConfig::default(),
+            required: true,
         }
     }
 }
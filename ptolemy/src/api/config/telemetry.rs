@@ -0,0 +1,28 @@
+use serde::{Deserialize, Serialize};
+
+/// Tracing of the server itself, as opposed to the traces it ingests.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// OTLP/gRPC collector to export the server's own spans to, e.g.
+    /// `http://localhost:4317`. Spans aren't exported when unset. Pointing
+    /// this at the server's own receiver makes every export produce more
+    /// spans to export.
+    pub otlp_endpoint: Option<String>,
+    pub service_name: String,
+    /// Share of traces exported when the caller didn't already decide.
+    pub sample_ratio: f64,
+    /// Which spans are exported, in `RUST_LOG` syntax.
+    pub filter: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: None,
+            service_name: "ptolemy".to_string(),
+            sample_ratio: 1.0,
+            filter: "info".to_string(),
+        }
+    }
+}
@@ -4,7 +4,7 @@ use argon2::{
     Argon2,
 };
 use base64::Engine;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
 use ring::{
     digest::{digest, SHA256},
     rand::{SecureRandom, SystemRandom},
@@ -128,7 +128,16 @@ where
     }
 
     pub fn generate_auth_token(&self, secret: &[u8]) -> ClaimsResult<String> {
-        encode(&Header::default(), &self, &EncodingKey::from_secret(secret)).map_err(|e| {
+        self.generate_signed_token(&Header::default(), &EncodingKey::from_secret(secret))
+    }
+
+    /// Signs the claims with `key`, using the algorithm and `kid` in `header`.
+    pub fn generate_signed_token(
+        &self,
+        header: &Header,
+        key: &EncodingKey,
+    ) -> ClaimsResult<String> {
+        encode(header, &self, key).map_err(|e| {
             error!("Failed to generate auth token: {}", e);
             ApiError::InternalError
         })
@@ -140,17 +149,21 @@ where
             None => return Ok(None),
         };
 
-        let claims = decode::<Self>(
-            &token,
-            &DecodingKey::from_secret(secret),
-            &Validation::default(),
-        )
-        .map_err(|e| {
+        Self::from_signed_token(&token, &DecodingKey::from_secret(secret), Algorithm::HS256)
+            .map(Some)
+    }
+
+    pub fn from_signed_token(
+        token: &str,
+        key: &DecodingKey,
+        algorithm: Algorithm,
+    ) -> ClaimsResult<Self> {
+        let claims = decode::<Self>(token, key, &Validation::new(algorithm)).map_err(|e| {
             info!("Failed to decode auth token: {}", e);
             ApiError::AuthError("Invalid token".to_string())
         })?;
 
-        Ok(Some(claims.claims))
+        Ok(claims.claims)
     }
 }
 
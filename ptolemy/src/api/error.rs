@@ -1,7 +1,8 @@
 use axum::http::StatusCode;
 use serde::{Deserialize, Serialize};
+use tonic_types::{ErrorDetails, StatusExt};
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum ApiError {
     ConfigError,
     DatabaseError,
@@ -13,7 +14,16 @@ pub enum ApiError {
     UpdateError,
     BadQuery,
     InternalError,
+    TimeoutError,
+    Unavailable,
+    ResourceExhausted,
+    /// Over a rate limit or quota; try again after the duration.
+    RateLimited(std::time::Duration),
     AuthError(String),
+    PermissionDenied,
+    Conflict,
+    /// A published record that can't be parsed, and why.
+    InvalidRecord(String),
     SerializationError(String),
 }
 
@@ -36,7 +46,14 @@ impl ApiError {
             ApiError::UpdateError => "update_error",
             ApiError::BadQuery => "bad_query",
             ApiError::InternalError => "internal_error",
+            ApiError::TimeoutError => "timeout_error",
+            ApiError::Unavailable => "unavailable",
+            ApiError::ResourceExhausted => "resource_exhausted",
+            ApiError::RateLimited(_) => "rate_limited",
             ApiError::AuthError(_) => "auth_error",
+            ApiError::PermissionDenied => "permission_denied",
+            ApiError::Conflict => "conflict",
+            ApiError::InvalidRecord(_) => "invalid_record",
             ApiError::SerializationError(_) => "serialization_error",
         }
     }
@@ -53,8 +70,65 @@ impl ApiError {
             ApiError::ConnectionError => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::UpdateError => StatusCode::INTERNAL_SERVER_ERROR,
             ApiError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::TimeoutError => StatusCode::GATEWAY_TIMEOUT,
+            ApiError::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ApiError::ResourceExhausted => StatusCode::TOO_MANY_REQUESTS,
+            ApiError::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
             ApiError::AuthError(_) => StatusCode::UNAUTHORIZED,
+            ApiError::PermissionDenied => StatusCode::FORBIDDEN,
+            ApiError::Conflict => StatusCode::CONFLICT,
+            ApiError::InvalidRecord(_) => StatusCode::BAD_REQUEST,
             ApiError::SerializationError(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
+
+impl From<ApiError> for tonic::Status {
+    fn from(value: ApiError) -> Self {
+        let message = value.category().to_string();
+
+        match value {
+            ApiError::NotFoundError => tonic::Status::not_found(message),
+            ApiError::BadQuery => tonic::Status::invalid_argument(message),
+            ApiError::TimeoutError => tonic::Status::deadline_exceeded(message),
+            ApiError::Unavailable | ApiError::ConnectionError => {
+                tonic::Status::unavailable(message)
+            }
+            ApiError::ResourceExhausted => tonic::Status::resource_exhausted(message),
+            ApiError::RateLimited(retry_after) => tonic::Status::with_error_details(
+                tonic::Code::ResourceExhausted,
+                message,
+                ErrorDetails::with_retry_info(Some(retry_after)),
+            ),
+            ApiError::AuthError(e) => tonic::Status::unauthenticated(e),
+            ApiError::PermissionDenied => tonic::Status::permission_denied(message),
+            ApiError::Conflict => tonic::Status::already_exists(message),
+            ApiError::InvalidRecord(e) => tonic::Status::invalid_argument(e),
+            _ => tonic::Status::internal(message),
+        }
+    }
+}
+
+impl axum::response::IntoResponse for ApiError {
+    fn into_response(self) -> axum::response::Response {
+        let body = match &self {
+            ApiError::AuthError(e)
+            | ApiError::InvalidRecord(e)
+            | ApiError::SerializationError(e) => {
+                serde_json::json!({"error": self.category(), "message": e})
+            }
+            _ => serde_json::json!({"error": self.category()}),
+        };
+
+        let mut response = (self.http_status_code(), axum::Json(body)).into_response();
+        if let ApiError::RateLimited(retry_after) = self {
+            // Retry-After is in whole seconds, so round up.
+            let secs = retry_after.as_secs() + u64::from(retry_after.subsec_nanos() > 0);
+            response
+                .headers_mut()
+                .insert(axum::http::header::RETRY_AFTER, secs.into());
+        }
+
+        response
+    }
+}
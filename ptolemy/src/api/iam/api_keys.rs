@@ -0,0 +1,275 @@
+use super::store::{Row, Table};
+use crate::api::{
+    config::iam::IamConfig,
+    consts::SERVICE_API_KEY_PREFIX,
+    crypto::{generate_api_key, generate_sha256},
+    error::ApiError,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::RwLock;
+use uuid::Uuid;
+
+const API_KEYS_FILE: &str = "api_keys.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyPermission {
+    ReadOnly,
+    WriteOnly,
+    ReadWrite,
+}
+
+impl ApiKeyPermission {
+    pub fn can_read(&self) -> bool {
+        matches!(self, Self::ReadOnly | Self::ReadWrite)
+    }
+
+    pub fn can_write(&self) -> bool {
+        matches!(self, Self::WriteOnly | Self::ReadWrite)
+    }
+}
+
+/// A service API key, owned by a workspace. The key itself is only known
+/// when it is created.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub workspace_id: Uuid,
+    pub name: String,
+    /// Prefix and last four characters of the key, e.g. `pt-sk-...x9Zq`.
+    pub key_preview: String,
+    pub permissions: ApiKeyPermission,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    pub fn is_active(&self) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|expires| expires > Utc::now())
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewApiKey {
+    pub name: String,
+    pub permissions: ApiKeyPermission,
+    #[serde(default)]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredApiKey {
+    key_sha256: String,
+    #[serde(flatten)]
+    key: ApiKey,
+}
+
+impl Row for StoredApiKey {
+    type Key = Uuid;
+
+    fn key(&self) -> Uuid {
+        self.key.id
+    }
+}
+
+/// Service API keys, persisted as their SHA-256 in `api_keys.json` in the
+/// IAM directory. Revoked keys are kept so they still show up in listings.
+#[derive(Debug)]
+pub struct ApiKeyStore {
+    table: Table<StoredApiKey>,
+    // SHA-256 of the key -> key id. Keys are never removed, so this only
+    // grows along with the table.
+    by_hash: RwLock<HashMap<Vec<u8>, Uuid>>,
+}
+
+impl ApiKeyStore {
+    pub fn open(config: &IamConfig) -> Result<Self, ApiError> {
+        let table = Table::<StoredApiKey>::open(config, API_KEYS_FILE)?;
+        let by_hash = table
+            .rows()
+            .values()
+            .filter_map(|stored| Some((hex::decode(&stored.key_sha256).ok()?, stored.key.id)))
+            .collect();
+
+        Ok(Self {
+            table,
+            by_hash: RwLock::new(by_hash),
+        })
+    }
+
+    /// Creates a key, returning it along with the only copy of the key
+    /// itself.
+    pub async fn create(
+        &self,
+        workspace_id: Uuid,
+        new: NewApiKey,
+    ) -> Result<(ApiKey, String), ApiError> {
+        if new.expires_at.is_some_and(|expires| expires <= Utc::now()) {
+            return Err(ApiError::BadQuery);
+        }
+
+        let api_key = generate_api_key(SERVICE_API_KEY_PREFIX).await;
+        let key = ApiKey {
+            id: Uuid::new_v4(),
+            workspace_id,
+            name: new.name,
+            key_preview: format!(
+                "{}-...{}",
+                SERVICE_API_KEY_PREFIX,
+                &api_key[api_key.len() - 4..]
+            ),
+            permissions: new.permissions,
+            created_at: Utc::now(),
+            expires_at: new.expires_at,
+            revoked_at: None,
+        };
+
+        let hash = generate_sha256(api_key.as_bytes());
+        self.table.update(|keys| {
+            keys.insert(
+                key.id,
+                StoredApiKey {
+                    key_sha256: hex::encode(&hash),
+                    key: key.clone(),
+                },
+            );
+            Ok(())
+        })?;
+        self.by_hash.write().unwrap().insert(hash, key.id);
+
+        Ok((key, api_key))
+    }
+
+    /// A workspace's keys, oldest first.
+    pub fn list(&self, workspace_id: &Uuid) -> Vec<ApiKey> {
+        let mut keys: Vec<_> = self
+            .table
+            .rows()
+            .values()
+            .filter(|stored| stored.key.workspace_id == *workspace_id)
+            .map(|stored| stored.key.clone())
+            .collect();
+        keys.sort_by_key(|key| (key.created_at, key.id));
+        keys
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<ApiKey> {
+        self.table.rows().get(id).map(|stored| stored.key.clone())
+    }
+
+    /// The active key with the given SHA-256, if any.
+    pub fn find_by_hash(&self, hash: &[u8]) -> Option<ApiKey> {
+        let id = *self.by_hash.read().unwrap().get(hash)?;
+        self.get(&id).filter(ApiKey::is_active)
+    }
+
+    /// Revokes one of a workspace's keys. Revoking it again is a no-op.
+    pub fn revoke(&self, workspace_id: &Uuid, id: &Uuid) -> Result<ApiKey, ApiError> {
+        self.table.update(|keys| {
+            let stored = keys
+                .get_mut(id)
+                .filter(|stored| stored.key.workspace_id == *workspace_id)
+                .ok_or(ApiError::NotFoundError)?;
+            stored.key.revoked_at.get_or_insert_with(Utc::now);
+            Ok(stored.key.clone())
+        })
+    }
+
+    /// Revokes every key of a workspace, for when it is deleted.
+    pub fn revoke_workspace(&self, workspace_id: &Uuid) -> Result<(), ApiError> {
+        let now = Utc::now();
+
+        self.table.update(|keys| {
+            for stored in keys.values_mut() {
+                if stored.key.workspace_id == *workspace_id {
+                    stored.key.revoked_at.get_or_insert(now);
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn iam_config(dir: &TempDir) -> IamConfig {
+        IamConfig {
+            path: dir.path_string(),
+            ..Default::default()
+        }
+    }
+
+    fn new_key(expires_at: Option<DateTime<Utc>>) -> NewApiKey {
+        NewApiKey {
+            name: "ingest".to_string(),
+            permissions: ApiKeyPermission::WriteOnly,
+            expires_at,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_and_revoke() {
+        let dir = TempDir::new("iam");
+        let config = iam_config(&dir);
+        let store = ApiKeyStore::open(&config).unwrap();
+
+        let workspace_id = Uuid::new_v4();
+        let (key, api_key) = store.create(workspace_id, new_key(None)).await.unwrap();
+        let hash = generate_sha256(api_key.as_bytes());
+        assert!(key.key_preview.ends_with(&api_key[api_key.len() - 4..]));
+        assert_eq!(store.find_by_hash(&hash), Some(key.clone()));
+
+        // Keys survive a restart, and only their hashes are written to disk.
+        let reopened = ApiKeyStore::open(&config).unwrap();
+        assert_eq!(reopened.list(&workspace_id), vec![key.clone()]);
+        assert!(reopened.list(&Uuid::new_v4()).is_empty());
+        let on_disk =
+            std::fs::read_to_string(std::path::Path::new(&config.path).join(API_KEYS_FILE))
+                .unwrap();
+        assert!(!on_disk.contains(&api_key));
+
+        assert_eq!(
+            reopened.revoke(&Uuid::new_v4(), &key.id),
+            Err(ApiError::NotFoundError)
+        );
+        let revoked = reopened.revoke(&workspace_id, &key.id).unwrap();
+        assert!(revoked.revoked_at.is_some());
+        assert_eq!(reopened.find_by_hash(&hash), None);
+        assert_eq!(
+            reopened.revoke(&workspace_id, &Uuid::new_v4()),
+            Err(ApiError::NotFoundError)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_expiry() {
+        let dir = TempDir::new("iam");
+        let config = iam_config(&dir);
+        let store = ApiKeyStore::open(&config).unwrap();
+
+        let past = Utc::now() - chrono::Duration::hours(1);
+        assert_eq!(
+            store.create(Uuid::new_v4(), new_key(Some(past))).await,
+            Err(ApiError::BadQuery)
+        );
+
+        let (mut key, _) = store
+            .create(
+                Uuid::new_v4(),
+                new_key(Some(Utc::now() + chrono::Duration::hours(1))),
+            )
+            .await
+            .unwrap();
+        assert!(key.is_active());
+
+        key.expires_at = Some(past);
+        assert!(!key.is_active());
+    }
+}
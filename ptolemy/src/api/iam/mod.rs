@@ -0,0 +1,10 @@
+//! Identity and access management state: users, workspaces, their members
+//! and API keys, the roles that decide what each may do, and the files they
+//! are kept in.
+
+pub mod api_keys;
+pub mod rbac;
+mod store;
+pub mod tokens;
+pub mod users;
+pub mod workspaces;
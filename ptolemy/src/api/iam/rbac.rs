@@ -0,0 +1,113 @@
+use super::workspaces::WorkspaceStore;
+use crate::api::{auth::Identity, error::ApiError, state::PtolemyState};
+
+use axum::{
+    extract::{FromRequestParts, Path},
+    http::request::Parts,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Roles within a workspace, each including everything the ones before it
+/// may do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WorkspaceRole {
+    /// View workspace data, details and service API keys.
+    User,
+    /// Create and revoke service API keys.
+    Manager,
+    /// Manage members and delete the workspace.
+    Admin,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemRole {
+    /// Creates workspaces and manages users.
+    Admin,
+    /// Manages users only, and never sees workspace data.
+    Sysadmin,
+}
+
+pub fn require_system_role(identity: &Identity, roles: &[SystemRole]) -> Result<(), ApiError> {
+    match identity.system_role() {
+        Some(role) if roles.contains(&role) => Ok(()),
+        _ => Err(ApiError::PermissionDenied),
+    }
+}
+
+/// The caller's role in the workspace whose id is in the `workspace_id`
+/// path parameter. Extracting it fails unless the caller is a member.
+#[derive(Debug, Clone)]
+pub struct WorkspaceAccess {
+    pub workspace_id: Uuid,
+    pub identity: Identity,
+    pub role: WorkspaceRole,
+}
+
+impl WorkspaceAccess {
+    pub fn require(&self, role: WorkspaceRole) -> Result<(), ApiError> {
+        match self.role >= role {
+            true => Ok(()),
+            false => Err(ApiError::PermissionDenied),
+        }
+    }
+}
+
+fn workspace_role(
+    workspaces: &WorkspaceStore,
+    identity: &Identity,
+    workspace_id: &Uuid,
+) -> Result<WorkspaceRole, ApiError> {
+    if workspaces.get(workspace_id).is_none() {
+        return Err(ApiError::NotFoundError);
+    }
+
+    // The sysadmin manages users, but never sees workspace data.
+    let Identity::User {
+        id,
+        system_role: None | Some(SystemRole::Admin),
+    } = identity
+    else {
+        return Err(ApiError::PermissionDenied);
+    };
+
+    workspaces
+        .role(workspace_id, id)
+        .ok_or(ApiError::PermissionDenied)
+}
+
+#[async_trait::async_trait]
+impl FromRequestParts<PtolemyState> for WorkspaceAccess {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &PtolemyState,
+    ) -> Result<Self, Self::Rejection> {
+        let Path(params) = Path::<HashMap<String, String>>::from_request_parts(parts, state)
+            .await
+            .map_err(|_| ApiError::BadQuery)?;
+
+        let workspace_id = params
+            .get("workspace_id")
+            .and_then(|id| Uuid::parse_str(id).ok())
+            .ok_or(ApiError::BadQuery)?;
+
+        let identity = parts
+            .extensions
+            .get::<Identity>()
+            .cloned()
+            .ok_or_else(|| ApiError::AuthError("Missing credentials".to_string()))?;
+
+        let role = workspace_role(&state.workspaces, &identity, &workspace_id)?;
+
+        Ok(Self {
+            workspace_id,
+            identity,
+            role,
+        })
+    }
+}
@@ -0,0 +1,88 @@
+use crate::api::{config::iam::IamConfig, error::ApiError};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{RwLock, RwLockReadGuard};
+
+fn io_error(path: &Path, context: &str, e: impl std::fmt::Display) -> ApiError {
+    tracing::error!("IAM store {} {}: {}", path.display(), context, e);
+    ApiError::InternalError
+}
+
+/// Reads a JSON file, or `T::default()` if it doesn't exist yet.
+pub(super) fn load<T: DeserializeOwned + Default>(path: &Path) -> Result<T, ApiError> {
+    match fs::read(path) {
+        Ok(data) => serde_json::from_slice(&data).map_err(|e| io_error(path, "is corrupt", e)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(io_error(path, "read failed", e)),
+    }
+}
+
+/// Replaces a JSON file atomically, so a crash leaves either the old or the
+/// new contents behind.
+pub(super) fn save<T: Serialize>(path: &Path, value: &T) -> Result<(), ApiError> {
+    let data = serde_json::to_vec_pretty(value).map_err(|e| io_error(path, "encode failed", e))?;
+    let tmp = path.with_extension("tmp");
+
+    let mut file = fs::File::create(&tmp).map_err(|e| io_error(path, "write failed", e))?;
+    file.write_all(&data)
+        .and_then(|_| file.sync_all())
+        .map_err(|e| io_error(path, "write failed", e))?;
+
+    fs::rename(&tmp, path).map_err(|e| io_error(path, "rename failed", e))
+}
+
+pub(super) trait Row: std::fmt::Debug + Clone + Serialize + DeserializeOwned {
+    type Key: std::fmt::Debug + Ord + Clone;
+
+    fn key(&self) -> Self::Key;
+}
+
+/// Rows kept in memory and persisted as a JSON list in the IAM directory.
+#[derive(Debug)]
+pub(super) struct Table<T: Row> {
+    path: PathBuf,
+    rows: RwLock<BTreeMap<T::Key, T>>,
+}
+
+impl<T: Row> Table<T> {
+    pub fn open(config: &IamConfig, file: &str) -> Result<Self, ApiError> {
+        fs::create_dir_all(&config.path).map_err(|e| {
+            tracing::error!("Failed to create IAM directory {}: {}", config.path, e);
+            ApiError::ConfigError
+        })?;
+
+        let path = PathBuf::from(&config.path).join(file);
+        let rows = load::<Vec<T>>(&path)?
+            .into_iter()
+            .map(|row| (row.key(), row))
+            .collect();
+
+        Ok(Self {
+            path,
+            rows: RwLock::new(rows),
+        })
+    }
+
+    pub fn rows(&self) -> RwLockReadGuard<'_, BTreeMap<T::Key, T>> {
+        self.rows.read().unwrap()
+    }
+
+    /// Applies `f` to a copy of the rows and keeps the result only if it
+    /// could be written to disk.
+    pub fn update<R>(
+        &self,
+        f: impl FnOnce(&mut BTreeMap<T::Key, T>) -> Result<R, ApiError>,
+    ) -> Result<R, ApiError> {
+        let mut rows = self.rows.write().unwrap();
+
+        let mut updated = rows.clone();
+        let result = f(&mut updated)?;
+        save(&self.path, &updated.values().collect::<Vec<_>>())?;
+
+        *rows = updated;
+        Ok(result)
+    }
+}
@@ -0,0 +1,100 @@
+use super::store::{Row, Table};
+use crate::api::{
+    config::iam::IamConfig,
+    consts::REFRESH_TOKEN_PREFIX,
+    crypto::{generate_api_key, generate_sha256},
+    error::ApiError,
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+use uuid::Uuid;
+
+const REFRESH_TOKENS_FILE: &str = "refresh_tokens.json";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct RefreshToken {
+    token_sha256: String,
+    user_id: Uuid,
+    expires_at: DateTime<Utc>,
+}
+
+impl Row for RefreshToken {
+    type Key = String;
+
+    fn key(&self) -> String {
+        self.token_sha256.clone()
+    }
+}
+
+fn token_sha256(token: &str) -> String {
+    hex::encode(generate_sha256(token.as_bytes()))
+}
+
+/// Refresh tokens, persisted as their SHA-256 in `refresh_tokens.json` in
+/// the IAM directory. Each token can be redeemed once.
+#[derive(Debug)]
+pub struct RefreshTokenStore {
+    table: Table<RefreshToken>,
+}
+
+impl RefreshTokenStore {
+    pub fn open(config: &IamConfig) -> Result<Self, ApiError> {
+        Ok(Self {
+            table: Table::open(config, REFRESH_TOKENS_FILE)?,
+        })
+    }
+
+    pub async fn issue(&self, user_id: Uuid, valid_for: Duration) -> Result<String, ApiError> {
+        let token = generate_api_key(REFRESH_TOKEN_PREFIX).await;
+        let refresh_token = RefreshToken {
+            token_sha256: token_sha256(&token),
+            user_id,
+            expires_at: Utc::now()
+                + chrono::Duration::from_std(valid_for).map_err(|_| ApiError::ConfigError)?,
+        };
+
+        self.table.update(|tokens| {
+            // Drop expired tokens while we're writing anyway.
+            let now = Utc::now();
+            tokens.retain(|_, t| t.expires_at > now);
+
+            tokens.insert(refresh_token.key(), refresh_token);
+            Ok(())
+        })?;
+
+        Ok(token)
+    }
+
+    /// Uses up a token, returning the user it was issued to.
+    pub fn redeem(&self, token: &str) -> Result<Uuid, ApiError> {
+        let key = token_sha256(token);
+        let invalid = || ApiError::AuthError("Invalid refresh token".to_string());
+
+        if !self.table.rows().contains_key(&key) {
+            return Err(invalid());
+        }
+
+        self.table
+            .update(|tokens| Ok(tokens.remove(&key)))?
+            .filter(|t| t.expires_at > Utc::now())
+            .map(|t| t.user_id)
+            .ok_or_else(invalid)
+    }
+
+    pub fn revoke(&self, token: &str) -> Result<(), ApiError> {
+        self.table.update(|tokens| {
+            tokens.remove(&token_sha256(token));
+            Ok(())
+        })
+    }
+
+    /// Revokes every token issued to a user, logging them out everywhere.
+    pub fn revoke_user(&self, user_id: &Uuid) -> Result<(), ApiError> {
+        self.table.update(|tokens| {
+            tokens.retain(|_, t| t.user_id != *user_id);
+            Ok(())
+        })
+    }
+}
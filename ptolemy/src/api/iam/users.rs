@@ -0,0 +1,369 @@
+use super::{
+    rbac::SystemRole,
+    store::{Row, Table},
+};
+use crate::api::{config::iam::IamConfig, crypto::PasswordHandler, error::ApiError};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const USERS_FILE: &str = "users.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct User {
+    pub id: Uuid,
+    pub username: String,
+    pub display_name: Option<String>,
+    pub system_role: Option<SystemRole>,
+    pub created_at: DateTime<Utc>,
+    pub deleted_at: Option<DateTime<Utc>>,
+}
+
+impl User {
+    pub fn is_active(&self) -> bool {
+        self.deleted_at.is_none()
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct NewUser {
+    pub username: String,
+    pub password: String,
+    #[serde(default)]
+    pub display_name: Option<String>,
+    /// `sysadmin` can't be granted; there is only the configured one.
+    #[serde(default)]
+    pub system_role: Option<SystemRole>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredUser {
+    password_hash: String,
+    #[serde(flatten)]
+    user: User,
+}
+
+impl Row for StoredUser {
+    type Key = Uuid;
+
+    fn key(&self) -> Uuid {
+        self.user.id
+    }
+}
+
+/// User accounts, persisted in `users.json` in the IAM directory along with
+/// their Argon2 password hashes. Deleted users are kept, but can't log in.
+#[derive(Debug)]
+pub struct UserStore {
+    table: Table<StoredUser>,
+    passwords: PasswordHandler,
+    // Checked against for unknown usernames, so they take as long to reject
+    // as wrong passwords.
+    dummy_hash: String,
+}
+
+impl UserStore {
+    pub fn open(config: &IamConfig, passwords: PasswordHandler) -> Result<Self, ApiError> {
+        Ok(Self {
+            table: Table::open(config, USERS_FILE)?,
+            dummy_hash: passwords.hash_password(&Uuid::new_v4().to_string()),
+            passwords,
+        })
+    }
+
+    /// Hashes off the async workers, as Argon2 is slow on purpose.
+    async fn hash(&self, password: &str) -> Result<String, ApiError> {
+        let (passwords, password) = (self.passwords.clone(), password.to_string());
+        tokio::task::spawn_blocking(move || passwords.hash_password(&password))
+            .await
+            .map_err(|e| {
+                tracing::error!("Password hashing failed: {}", e);
+                ApiError::InternalError
+            })
+    }
+
+    async fn verify(&self, password: &str, hash: String) -> Result<bool, ApiError> {
+        let (passwords, password) = (self.passwords.clone(), password.to_string());
+        tokio::task::spawn_blocking(move || passwords.verify_password(&password, &hash))
+            .await
+            .map_err(|e| {
+                tracing::error!("Password verification failed: {}", e);
+                ApiError::InternalError
+            })
+    }
+
+    fn find_active(&self, username: &str) -> Option<StoredUser> {
+        self.table
+            .rows()
+            .values()
+            .find(|stored| stored.user.is_active() && stored.user.username == username)
+            .cloned()
+    }
+
+    pub async fn create(&self, new: NewUser) -> Result<User, ApiError> {
+        if new.username.is_empty()
+            || new.password.is_empty()
+            || new.system_role == Some(SystemRole::Sysadmin)
+        {
+            return Err(ApiError::BadQuery);
+        }
+
+        let user = User {
+            id: Uuid::new_v4(),
+            username: new.username,
+            display_name: new.display_name,
+            system_role: new.system_role,
+            created_at: Utc::now(),
+            deleted_at: None,
+        };
+        let password_hash = self.hash(&new.password).await?;
+
+        self.table.update(|users| {
+            if users
+                .values()
+                .any(|s| s.user.is_active() && s.user.username == user.username)
+            {
+                return Err(ApiError::Conflict);
+            }
+
+            users.insert(
+                user.id,
+                StoredUser {
+                    password_hash,
+                    user: user.clone(),
+                },
+            );
+            Ok(user)
+        })
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<User> {
+        self.table.rows().get(id).map(|stored| stored.user.clone())
+    }
+
+    /// Active users, oldest first.
+    pub fn list(&self) -> Vec<User> {
+        let mut users: Vec<_> = self
+            .table
+            .rows()
+            .values()
+            .filter(|stored| stored.user.is_active())
+            .map(|stored| stored.user.clone())
+            .collect();
+        users.sort_by_key(|user| (user.created_at, user.id));
+        users
+    }
+
+    /// Deletes a user. The sysadmin can only be changed through the config.
+    pub fn delete(&self, id: &Uuid) -> Result<User, ApiError> {
+        self.table.update(|users| {
+            let stored = users
+                .get_mut(id)
+                .filter(|stored| stored.user.is_active())
+                .ok_or(ApiError::NotFoundError)?;
+
+            if stored.user.system_role == Some(SystemRole::Sysadmin) {
+                return Err(ApiError::BadQuery);
+            }
+
+            stored.user.deleted_at = Some(Utc::now());
+            Ok(stored.user.clone())
+        })
+    }
+
+    /// The active user with these credentials, if any.
+    pub async fn verify_password(&self, username: &str, password: &str) -> Option<User> {
+        let stored = self.find_active(username);
+        let hash = stored
+            .as_ref()
+            .map_or_else(|| self.dummy_hash.clone(), |s| s.password_hash.clone());
+
+        let valid = self.verify(password, hash).await.unwrap_or(false);
+        stored.filter(|_| valid).map(|stored| stored.user)
+    }
+
+    pub async fn change_password(
+        &self,
+        id: &Uuid,
+        current_password: &str,
+        new_password: &str,
+    ) -> Result<(), ApiError> {
+        if new_password.is_empty() {
+            return Err(ApiError::BadQuery);
+        }
+
+        let current_hash = self
+            .table
+            .rows()
+            .get(id)
+            .filter(|stored| stored.user.is_active())
+            .map(|stored| stored.password_hash.clone())
+            .ok_or(ApiError::NotFoundError)?;
+
+        if !self.verify(current_password, current_hash.clone()).await? {
+            return Err(ApiError::AuthError("Invalid password".to_string()));
+        }
+
+        let password_hash = self.hash(new_password).await?;
+
+        self.table.update(|users| {
+            let stored = users
+                .get_mut(id)
+                .filter(|stored| stored.user.is_active())
+                .ok_or(ApiError::NotFoundError)?;
+
+            // Changed by someone else while the old one was being checked.
+            if stored.password_hash != current_hash {
+                return Err(ApiError::AuthError("Invalid password".to_string()));
+            }
+
+            stored.password_hash = password_hash;
+            Ok(())
+        })
+    }
+
+    /// Makes sure the sysadmin exists with these credentials, renaming the
+    /// current one or resetting its password if they changed.
+    pub fn bootstrap_sysadmin(&self, username: &str, password: &str) -> Result<User, ApiError> {
+        let current = self
+            .table
+            .rows()
+            .values()
+            .find(|s| s.user.is_active() && s.user.system_role == Some(SystemRole::Sysadmin))
+            .cloned();
+
+        if let Some(stored) = &current {
+            if stored.user.username == username
+                && self
+                    .passwords
+                    .verify_password(password, &stored.password_hash)
+            {
+                return Ok(stored.user.clone());
+            }
+        }
+
+        let password_hash = self.passwords.hash_password(password);
+
+        self.table.update(|users| {
+            let taken = users.values().any(|s| {
+                s.user.is_active()
+                    && s.user.username == username
+                    && s.user.system_role != Some(SystemRole::Sysadmin)
+            });
+            if taken {
+                tracing::error!("Sysadmin username {} belongs to another user", username);
+                return Err(ApiError::ConfigError);
+            }
+
+            let user = match current {
+                Some(stored) => User {
+                    username: username.to_string(),
+                    ..stored.user
+                },
+                None => User {
+                    id: Uuid::new_v4(),
+                    username: username.to_string(),
+                    display_name: None,
+                    system_role: Some(SystemRole::Sysadmin),
+                    created_at: Utc::now(),
+                    deleted_at: None,
+                },
+            };
+
+            users.insert(
+                user.id,
+                StoredUser {
+                    password_hash,
+                    user: user.clone(),
+                },
+            );
+            tracing::info!("Updated sysadmin {}", user.username);
+
+            Ok(user)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn user_store() -> (UserStore, TempDir) {
+        let dir = TempDir::new("iam");
+        let config = IamConfig {
+            path: dir.path_string(),
+            ..Default::default()
+        };
+
+        (
+            UserStore::open(&config, PasswordHandler::new()).unwrap(),
+            dir,
+        )
+    }
+
+    fn new_user(username: &str) -> NewUser {
+        NewUser {
+            username: username.to_string(),
+            password: "hunter2".to_string(),
+            display_name: None,
+            system_role: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_passwords() {
+        let (store, _dir) = user_store();
+
+        let user = store.create(new_user("ada")).await.unwrap();
+        assert_eq!(store.create(new_user("ada")).await, Err(ApiError::Conflict));
+        assert_eq!(
+            store.verify_password("ada", "hunter2").await,
+            Some(user.clone())
+        );
+        assert_eq!(store.verify_password("ada", "hunter3").await, None);
+
+        assert!(store
+            .change_password(&user.id, "wrong", "new")
+            .await
+            .is_err());
+        store
+            .change_password(&user.id, "hunter2", "new")
+            .await
+            .unwrap();
+        assert_eq!(store.verify_password("ada", "hunter2").await, None);
+        assert!(store.verify_password("ada", "new").await.is_some());
+
+        // Unknown usernames are rejected the same way.
+        assert_eq!(store.verify_password("grace", "new").await, None);
+
+        store.delete(&user.id).unwrap();
+        assert_eq!(store.verify_password("ada", "new").await, None);
+        assert!(store.create(new_user("ada")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_bootstrap_sysadmin() {
+        let (store, _dir) = user_store();
+
+        let sysadmin = store.bootstrap_sysadmin("admin", "admin").unwrap();
+        assert_eq!(
+            store.bootstrap_sysadmin("admin", "admin"),
+            Ok(sysadmin.clone())
+        );
+
+        // Changed credentials update the same account.
+        let renamed = store.bootstrap_sysadmin("root", "secret").unwrap();
+        assert_eq!(renamed.id, sysadmin.id);
+        assert!(store.verify_password("root", "secret").await.is_some());
+        assert_eq!(store.verify_password("admin", "admin").await, None);
+
+        assert_eq!(store.delete(&sysadmin.id), Err(ApiError::BadQuery));
+        store.create(new_user("ada")).await.unwrap();
+        assert_eq!(
+            store.bootstrap_sysadmin("ada", "secret"),
+            Err(ApiError::ConfigError)
+        );
+    }
+}
@@ -0,0 +1,272 @@
+use super::{
+    rbac::WorkspaceRole,
+    store::{Row, Table},
+};
+use crate::api::{config::iam::IamConfig, error::ApiError};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const WORKSPACES_FILE: &str = "workspaces.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Workspace {
+    pub id: Uuid,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Member {
+    pub user_id: Uuid,
+    pub role: WorkspaceRole,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct StoredWorkspace {
+    #[serde(flatten)]
+    workspace: Workspace,
+    members: Vec<Member>,
+}
+
+impl StoredWorkspace {
+    fn admins(&self) -> usize {
+        self.members
+            .iter()
+            .filter(|m| m.role == WorkspaceRole::Admin)
+            .count()
+    }
+}
+
+impl Row for StoredWorkspace {
+    type Key = Uuid;
+
+    fn key(&self) -> Uuid {
+        self.workspace.id
+    }
+}
+
+/// Workspaces and their members, persisted in `workspaces.json` in the IAM
+/// directory. Every workspace keeps at least one admin.
+#[derive(Debug)]
+pub struct WorkspaceStore {
+    table: Table<StoredWorkspace>,
+}
+
+impl WorkspaceStore {
+    pub fn open(config: &IamConfig) -> Result<Self, ApiError> {
+        Ok(Self {
+            table: Table::open(config, WORKSPACES_FILE)?,
+        })
+    }
+
+    /// Creates a workspace with `admin` as its first admin.
+    pub fn create(&self, name: String, admin: Uuid) -> Result<Workspace, ApiError> {
+        let workspace = Workspace {
+            id: Uuid::new_v4(),
+            name,
+            created_at: Utc::now(),
+        };
+
+        self.table.update(|workspaces| {
+            workspaces.insert(
+                workspace.id,
+                StoredWorkspace {
+                    workspace: workspace.clone(),
+                    members: vec![Member {
+                        user_id: admin,
+                        role: WorkspaceRole::Admin,
+                    }],
+                },
+            );
+            Ok(workspace)
+        })
+    }
+
+    pub fn get(&self, id: &Uuid) -> Option<Workspace> {
+        let workspaces = self.table.rows();
+        workspaces.get(id).map(|stored| stored.workspace.clone())
+    }
+
+    pub fn list(&self) -> Vec<Workspace> {
+        let workspaces = self.table.rows();
+        let mut list: Vec<_> = workspaces
+            .values()
+            .map(|stored| stored.workspace.clone())
+            .collect();
+        list.sort_by_key(|workspace| (workspace.created_at, workspace.id));
+        list
+    }
+
+    /// Workspaces `user_id` is a member of.
+    pub fn list_for_user(&self, user_id: &Uuid) -> Vec<Workspace> {
+        self.list()
+            .into_iter()
+            .filter(|workspace| self.role(&workspace.id, user_id).is_some())
+            .collect()
+    }
+
+    pub fn delete(&self, id: &Uuid) -> Result<Workspace, ApiError> {
+        self.table.update(|workspaces| {
+            workspaces
+                .remove(id)
+                .map(|stored| stored.workspace)
+                .ok_or(ApiError::NotFoundError)
+        })
+    }
+
+    pub fn role(&self, workspace_id: &Uuid, user_id: &Uuid) -> Option<WorkspaceRole> {
+        let workspaces = self.table.rows();
+        workspaces
+            .get(workspace_id)?
+            .members
+            .iter()
+            .find(|m| m.user_id == *user_id)
+            .map(|m| m.role)
+    }
+
+    pub fn members(&self, workspace_id: &Uuid) -> Result<Vec<Member>, ApiError> {
+        let workspaces = self.table.rows();
+        workspaces
+            .get(workspace_id)
+            .map(|stored| stored.members.clone())
+            .ok_or(ApiError::NotFoundError)
+    }
+
+    /// Adds a member, or changes the role of an existing one.
+    pub fn set_member(&self, workspace_id: &Uuid, member: Member) -> Result<Member, ApiError> {
+        self.table.update(|workspaces| {
+            let stored = workspaces
+                .get_mut(workspace_id)
+                .ok_or(ApiError::NotFoundError)?;
+
+            stored.members.retain(|m| m.user_id != member.user_id);
+            stored.members.push(member.clone());
+
+            match stored.admins() {
+                0 => Err(ApiError::BadQuery),
+                _ => Ok(member),
+            }
+        })
+    }
+
+    /// Removes a deleted user from every workspace, unless that would leave
+    /// one without an admin.
+    pub fn remove_user(&self, user_id: &Uuid) -> Result<(), ApiError> {
+        self.table.update(|workspaces| {
+            for stored in workspaces.values_mut() {
+                let admins = stored.admins();
+                stored.members.retain(|m| m.user_id != *user_id);
+
+                if admins > 0 && stored.admins() == 0 {
+                    tracing::error!(
+                        "User {} is the only admin of workspace {}",
+                        user_id,
+                        stored.workspace.id
+                    );
+                    return Err(ApiError::BadQuery);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    pub fn remove_member(&self, workspace_id: &Uuid, user_id: &Uuid) -> Result<(), ApiError> {
+        self.table.update(|workspaces| {
+            let stored = workspaces
+                .get_mut(workspace_id)
+                .ok_or(ApiError::NotFoundError)?;
+
+            let count = stored.members.len();
+            stored.members.retain(|m| m.user_id != *user_id);
+
+            if stored.members.len() == count {
+                return Err(ApiError::NotFoundError);
+            }
+
+            match stored.admins() {
+                0 => Err(ApiError::BadQuery),
+                _ => Ok(()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+
+    fn iam_config(dir: &TempDir) -> IamConfig {
+        IamConfig {
+            path: dir.path_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_members() {
+        let dir = TempDir::new("iam");
+        let config = iam_config(&dir);
+        let store = WorkspaceStore::open(&config).unwrap();
+        let (admin, user) = (Uuid::new_v4(), Uuid::new_v4());
+
+        let workspace = store.create("production".to_string(), admin).unwrap();
+        store
+            .set_member(
+                &workspace.id,
+                Member {
+                    user_id: user,
+                    role: WorkspaceRole::Manager,
+                },
+            )
+            .unwrap();
+
+        let reopened = WorkspaceStore::open(&config).unwrap();
+        assert_eq!(reopened.list_for_user(&user), vec![workspace.clone()]);
+        assert_eq!(
+            reopened.role(&workspace.id, &user),
+            Some(WorkspaceRole::Manager)
+        );
+
+        // The last admin can't be demoted or removed.
+        let demoted = Member {
+            user_id: admin,
+            role: WorkspaceRole::User,
+        };
+        assert_eq!(
+            reopened.set_member(&workspace.id, demoted),
+            Err(ApiError::BadQuery)
+        );
+        assert_eq!(
+            reopened.remove_member(&workspace.id, &admin),
+            Err(ApiError::BadQuery)
+        );
+        assert_eq!(
+            reopened.role(&workspace.id, &admin),
+            Some(WorkspaceRole::Admin)
+        );
+
+        reopened.remove_member(&workspace.id, &user).unwrap();
+        assert!(reopened.list_for_user(&user).is_empty());
+
+        // Deleted users leave every workspace, but not its last admin.
+        reopened
+            .set_member(
+                &workspace.id,
+                Member {
+                    user_id: user,
+                    role: WorkspaceRole::User,
+                },
+            )
+            .unwrap();
+        assert_eq!(reopened.remove_user(&admin), Err(ApiError::BadQuery));
+        reopened.remove_user(&user).unwrap();
+        assert!(reopened.list_for_user(&user).is_empty());
+        assert_eq!(
+            reopened.role(&workspace.id, &admin),
+            Some(WorkspaceRole::Admin)
+        );
+    }
+}
@@ -0,0 +1,321 @@
+use super::{
+    auth::Identity,
+    config::limits::{LimitConfig, LimitsConfig},
+    error::ApiError,
+};
+
+use chrono::{NaiveDate, Utc};
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use uuid::Uuid;
+
+/// Past this many tracked keys, idle ones are dropped.
+const MAX_TRACKED: usize = 10_000;
+
+#[derive(Debug, Default)]
+struct Tracked {
+    usage: HashMap<LimitKey, Usage>,
+    /// Size the map has to grow to before the next sweep, so sweeps are paid
+    /// for by the keys added since the last one.
+    sweep_at: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum LimitKey {
+    ApiKey(Uuid),
+    Workspace(Uuid),
+    ClientIp(IpAddr),
+}
+
+impl LimitKey {
+    fn limit<'a>(&self, limits: &'a LimitsConfig) -> &'a LimitConfig {
+        match self {
+            LimitKey::ApiKey(_) => &limits.api_key,
+            LimitKey::Workspace(_) => &limits.workspace,
+            LimitKey::ClientIp(_) => &limits.client_ip,
+        }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn new(now: Instant) -> Self {
+        Self {
+            // Starts full once refilled.
+            tokens: f64::INFINITY,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, rate: f64, capacity: f64, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated = now;
+    }
+
+    /// Whether the bucket would be back at `capacity` by `now`.
+    fn is_full(&self, rate: f64, capacity: f64, now: Instant) -> bool {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * rate >= capacity
+    }
+
+    /// How long until `amount` can be taken. Amounts larger than the bucket
+    /// only need it to be full, and leave it in debt.
+    fn wait(&self, amount: f64, rate: f64, capacity: f64) -> Option<Duration> {
+        let needed = amount.min(capacity) - self.tokens;
+        (needed > 0.0).then(|| Duration::from_secs_f64(needed / rate))
+    }
+}
+
+#[derive(Debug)]
+struct Usage {
+    records: TokenBucket,
+    bytes: TokenBucket,
+    day: NaiveDate,
+    daily_records: u64,
+    daily_bytes: u64,
+}
+
+impl Usage {
+    fn new(now: Instant, today: NaiveDate) -> Self {
+        Self {
+            records: TokenBucket::new(now),
+            bytes: TokenBucket::new(now),
+            day: today,
+            daily_records: 0,
+            daily_bytes: 0,
+        }
+    }
+
+    fn refill(&mut self, limit: &LimitConfig, now: Instant, today: NaiveDate) {
+        if let Some(rate) = limit.records_per_sec {
+            self.records.refill(rate, rate * limit.burst_secs, now);
+        }
+        if let Some(rate) = limit.bytes_per_sec {
+            self.bytes.refill(rate, rate * limit.burst_secs, now);
+        }
+        if self.day != today {
+            self.day = today;
+            self.daily_records = 0;
+            self.daily_bytes = 0;
+        }
+    }
+
+    /// How long until `records` and `bytes` fit within `limit`, if they
+    /// don't now.
+    fn wait(&self, limit: &LimitConfig, records: u64, bytes: u64) -> Option<Duration> {
+        let over_quota = limit
+            .daily_records
+            .is_some_and(|quota| self.daily_records + records > quota)
+            || limit
+                .daily_bytes
+                .is_some_and(|quota| self.daily_bytes + bytes > quota);
+        if over_quota {
+            return Some(until_tomorrow());
+        }
+
+        let records_wait = limit.records_per_sec.and_then(|rate| {
+            self.records
+                .wait(records as f64, rate, rate * limit.burst_secs)
+        });
+        let bytes_wait = limit
+            .bytes_per_sec
+            .and_then(|rate| self.bytes.wait(bytes as f64, rate, rate * limit.burst_secs));
+
+        records_wait.max(bytes_wait)
+    }
+
+    fn take(&mut self, limit: &LimitConfig, records: u64, bytes: u64) {
+        self.records.tokens -= records as f64;
+        self.bytes.tokens -= bytes as f64;
+        if limit.daily_records.is_some() {
+            self.daily_records += records;
+        }
+        if limit.daily_bytes.is_some() {
+            self.daily_bytes += bytes;
+        }
+    }
+
+    /// Whether forgetting this usage wouldn't let anything more through,
+    /// since a new one starts with full buckets.
+    fn is_idle(&self, limit: &LimitConfig, now: Instant, today: NaiveDate) -> bool {
+        let quota_used = self.day == today && (self.daily_records > 0 || self.daily_bytes > 0);
+        let full = |bucket: &TokenBucket, rate: Option<f64>| {
+            rate.is_none_or(|rate| bucket.is_full(rate, rate * limit.burst_secs, now))
+        };
+        !quota_used
+            && full(&self.records, limit.records_per_sec)
+            && full(&self.bytes, limit.bytes_per_sec)
+    }
+}
+
+fn until_tomorrow() -> Duration {
+    let now = Utc::now();
+    let midnight = (now.date_naive() + chrono::Days::new(1))
+        .and_hms_opt(0, 0, 0)
+        .unwrap()
+        .and_utc();
+    (midnight - now).to_std().unwrap_or_default()
+}
+
+/// Token bucket rate limits and daily quotas on published records, kept in
+/// memory.
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    tracked: Mutex<Tracked>,
+}
+
+impl RateLimiter {
+    /// Takes `records` and `bytes` from every limit that applies to the
+    /// caller, or none of them if any would be exceeded.
+    pub fn check(
+        &self,
+        limits: &LimitsConfig,
+        identity: &Identity,
+        client_ip: Option<IpAddr>,
+        records: u64,
+        bytes: u64,
+    ) -> Result<(), ApiError> {
+        let keys = [
+            match identity {
+                Identity::ServiceApiKey { id, .. } => {
+                    Some((LimitKey::ApiKey(*id), &limits.api_key))
+                }
+                _ => None,
+            },
+            identity
+                .workspace_id()
+                .map(|id| (LimitKey::Workspace(id), &limits.workspace)),
+            client_ip.map(|ip| (LimitKey::ClientIp(ip), &limits.client_ip)),
+        ];
+        let keys: Vec<_> = keys
+            .into_iter()
+            .flatten()
+            .filter(|(_, limit)| !limit.is_empty())
+            .collect();
+
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        let (now, today) = (Instant::now(), Utc::now().date_naive());
+        let mut tracked = self.tracked.lock().unwrap_or_else(|e| e.into_inner());
+
+        if tracked.usage.len() > tracked.sweep_at.max(MAX_TRACKED) {
+            tracked
+                .usage
+                .retain(|key, u| !u.is_idle(key.limit(limits), now, today));
+            tracked.sweep_at = tracked.usage.len() * 2;
+        }
+        let usage = &mut tracked.usage;
+
+        let mut wait = None;
+        for (key, limit) in &keys {
+            let u = usage.entry(*key).or_insert_with(|| Usage::new(now, today));
+            u.refill(limit, now, today);
+            wait = wait.max(u.wait(limit, records, bytes));
+        }
+
+        if let Some(wait) = wait {
+            return Err(ApiError::RateLimited(wait));
+        }
+
+        for (key, limit) in &keys {
+            if let Some(u) = usage.get_mut(key) {
+                u.take(limit, records, bytes);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api::iam::api_keys::ApiKeyPermission;
+
+    fn api_key(workspace_id: Uuid) -> Identity {
+        Identity::ServiceApiKey {
+            id: Uuid::new_v4(),
+            workspace_id,
+            permissions: ApiKeyPermission::WriteOnly,
+        }
+    }
+
+    #[test]
+    fn test_rates_must_be_positive() {
+        let limit = |json| serde_json::from_value::<LimitConfig>(json);
+
+        assert!(limit(serde_json::json!({"records_per_sec": 10.5, "burst_secs": 2})).is_ok());
+        assert!(limit(serde_json::json!({"records_per_sec": null})).is_ok());
+        assert!(limit(serde_json::json!({"records_per_sec": 0})).is_err());
+        assert!(limit(serde_json::json!({"bytes_per_sec": -1.0})).is_err());
+        assert!(limit(serde_json::json!({"burst_secs": 0})).is_err());
+    }
+
+    #[test]
+    fn test_rate_limits() {
+        let limits = LimitsConfig {
+            api_key: LimitConfig {
+                records_per_sec: Some(10.0),
+                ..Default::default()
+            },
+            workspace: LimitConfig {
+                daily_bytes: Some(1000),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let limiter = RateLimiter::default();
+        let workspace_id = Uuid::new_v4();
+        let (key, other_key) = (api_key(workspace_id), api_key(workspace_id));
+
+        // A full bucket lets a burst through, then makes the key wait.
+        assert!(limiter.check(&limits, &key, None, 10, 100).is_ok());
+        match limiter.check(&limits, &key, None, 5, 100) {
+            Err(ApiError::RateLimited(wait)) => {
+                assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500))
+            }
+            other => panic!("expected rate limit, got {:?}", other),
+        }
+
+        // Other keys share the workspace quota, but not the key's rate.
+        assert!(limiter.check(&limits, &other_key, None, 10, 800).is_ok());
+        assert!(matches!(
+            limiter.check(&limits, &other_key, None, 0, 200),
+            Err(ApiError::RateLimited(_))
+        ));
+
+        // Without limits for it, anonymous publishing isn't limited.
+        assert!(limiter
+            .check(&limits, &Identity::Anonymous, None, 1000, 1000)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_only_refilled_usage_is_idle() {
+        let limit = LimitConfig {
+            records_per_sec: Some(10.0),
+            ..Default::default()
+        };
+        let (now, today) = (Instant::now(), Utc::now().date_naive());
+
+        let mut usage = Usage::new(now, today);
+        usage.refill(&limit, now, today);
+        assert!(usage.is_idle(&limit, now, today));
+
+        // A partly drained bucket would refill if forgotten.
+        usage.take(&limit, 5, 0);
+        assert!(!usage.is_idle(&limit, now, today));
+        assert!(!usage.is_idle(&limit, now + Duration::from_millis(400), today));
+        assert!(usage.is_idle(&limit, now + Duration::from_millis(500), today));
+    }
+}
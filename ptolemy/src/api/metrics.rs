@@ -0,0 +1,330 @@
+use super::state::PtolemyState;
+use crate::error::ParseError;
+use crate::generated::record_publisher::{record::RecordData, Record};
+
+use axum::{
+    extract::{MatchedPath, Request, State},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use http::header::CONTENT_TYPE;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+
+struct Metrics {
+    registry: Registry,
+    records_received: IntCounterVec,
+    records_rejected: IntCounterVec,
+    sink_send_duration: HistogramVec,
+    sink_errors: IntCounterVec,
+    writer_queue_depth: IntGaugeVec,
+    writer_spilled_bytes: IntGaugeVec,
+    request_duration: HistogramVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new_custom(Some("ptolemy".to_string()), None).unwrap();
+
+        let records_received = IntCounterVec::new(
+            Opts::new("records_received_total", "Records accepted for publishing."),
+            &["record_type", "tier"],
+        )
+        .unwrap();
+        let records_rejected = IntCounterVec::new(
+            Opts::new(
+                "records_rejected_total",
+                "Records or spans dropped because they couldn't be parsed.",
+            ),
+            &["reason"],
+        )
+        .unwrap();
+        let sink_send_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "sink_send_duration_seconds",
+                "Time taken to send a batch to a sink.",
+            ),
+            &["sink"],
+        )
+        .unwrap();
+        let sink_errors = IntCounterVec::new(
+            Opts::new("sink_errors_total", "Batches a sink failed to take."),
+            &["sink"],
+        )
+        .unwrap();
+        let writer_queue_depth = IntGaugeVec::new(
+            Opts::new(
+                "writer_queue_depth",
+                "Records waiting in a sink writer's buffer.",
+            ),
+            &["sink"],
+        )
+        .unwrap();
+        let writer_spilled_bytes = IntGaugeVec::new(
+            Opts::new(
+                "writer_spilled_bytes",
+                "Bytes a sink writer has spilled to disk.",
+            ),
+            &["sink"],
+        )
+        .unwrap();
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "request_duration_seconds",
+                "Time taken to handle an HTTP request or gRPC call.",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+
+        let collectors: [Box<dyn prometheus::core::Collector>; 7] = [
+            Box::new(records_received.clone()),
+            Box::new(records_rejected.clone()),
+            Box::new(sink_send_duration.clone()),
+            Box::new(sink_errors.clone()),
+            Box::new(writer_queue_depth.clone()),
+            Box::new(writer_spilled_bytes.clone()),
+            Box::new(request_duration.clone()),
+        ];
+        for collector in collectors {
+            registry.register(collector).unwrap();
+        }
+
+        #[cfg(target_os = "linux")]
+        registry
+            .register(Box::new(
+                prometheus::process_collector::ProcessCollector::for_self(),
+            ))
+            .unwrap();
+
+        Self {
+            registry,
+            records_received,
+            records_rejected,
+            sink_send_duration,
+            sink_errors,
+            writer_queue_depth,
+            writer_spilled_bytes,
+            request_duration,
+        }
+    }
+}
+
+static METRICS: LazyLock<Metrics> = LazyLock::new(Metrics::new);
+
+fn record_labels(record: &Record) -> Option<(&'static str, &'static str)> {
+    let (record_type, tier) = match record.record_data.as_ref()? {
+        RecordData::Event(e) => ("event", e.tier()),
+        RecordData::Runtime(r) => ("runtime", r.tier()),
+        RecordData::Input(i) => ("input", i.tier()),
+        RecordData::Output(o) => ("output", o.tier()),
+        RecordData::Feedback(f) => ("feedback", f.tier()),
+        RecordData::Metadata(m) => ("metadata", m.tier()),
+    };
+
+    Some((record_type, tier.as_str_name()))
+}
+
+pub fn records_received(records: &[Record]) {
+    for record in records {
+        let (record_type, tier) = record_labels(record).unwrap_or(("unknown", "UNDECLARED_TIER"));
+        METRICS
+            .records_received
+            .with_label_values(&[record_type, tier])
+            .inc();
+    }
+}
+
+pub fn record_rejected(error: &ParseError) {
+    let reason = match error {
+        ParseError::UndefinedLogType => "UndefinedLogType",
+        ParseError::UndefinedTier => "UndefinedTier",
+        ParseError::MissingField => "MissingField",
+        ParseError::UnexpectedField => "UnexpectedField",
+        ParseError::InvalidUuid => "InvalidUuid",
+        ParseError::InvalidType => "InvalidType",
+        ParseError::BadJSON => "BadJSON",
+        ParseError::BadTimestamp => "BadTimestamp",
+        ParseError::UnexpectedNull => "UnexpectedNull",
+        ParseError::BadEnum(_) => "BadEnum",
+    };
+
+    METRICS.records_rejected.with_label_values(&[reason]).inc();
+}
+
+pub fn sink_sent(sink: &str, duration: Duration, ok: bool) {
+    METRICS
+        .sink_send_duration
+        .with_label_values(&[sink])
+        .observe(duration.as_secs_f64());
+
+    if !ok {
+        METRICS.sink_errors.with_label_values(&[sink]).inc();
+    }
+}
+
+/// gRPC methods the server implements. Services are routed by name alone, so
+/// any other path is labelled `unmatched` to keep cardinality bounded.
+const GRPC_METHODS: [&str; 6] = [
+    "/record_publisher.RecordPublisher/Publish",
+    "/opentelemetry.proto.collector.trace.v1.TraceService/Export",
+    "/grpc.health.v1.Health/Check",
+    "/grpc.health.v1.Health/Watch",
+    "/grpc.reflection.v1.ServerReflection/ServerReflectionInfo",
+    "/grpc.reflection.v1alpha.ServerReflection/ServerReflectionInfo",
+];
+
+fn route(request: &Request, is_grpc: bool) -> &str {
+    let path = request.uri().path();
+
+    match request.extensions().get::<MatchedPath>() {
+        None => "unmatched",
+        Some(_) if is_grpc => GRPC_METHODS
+            .into_iter()
+            .find(|method| *method == path)
+            .unwrap_or("unmatched"),
+        Some(matched) => matched.as_str(),
+    }
+}
+
+/// Times every request. gRPC calls are labelled with their method path and
+/// `grpc-status`, which unary calls that fail send in the response headers.
+pub async fn track_requests(request: Request, next: Next) -> Response {
+    let is_grpc = request
+        .headers()
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/grpc"));
+    let method = request.method().to_string();
+    let route = route(&request, is_grpc).to_string();
+
+    let start = Instant::now();
+    let response = next.run(request).await;
+
+    let status = match is_grpc {
+        true => response
+            .headers()
+            .get("grpc-status")
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("0")
+            .to_string(),
+        false => response.status().as_u16().to_string(),
+    };
+
+    METRICS
+        .request_duration
+        .with_label_values(&[&method, &route, &status])
+        .observe(start.elapsed().as_secs_f64());
+
+    response
+}
+
+/// Metrics in the Prometheus text format, under `/metrics`.
+pub async fn metrics(State(state): State<PtolemyState>) -> Response {
+    let sink_registry = state.sink_registry.load();
+
+    // Reset so sinks removed by a reload drop out.
+    METRICS.writer_queue_depth.reset();
+    for (sink, depth) in sink_registry.queue_depths() {
+        METRICS
+            .writer_queue_depth
+            .with_label_values(&[sink])
+            .set(depth as i64);
+    }
+    METRICS.writer_spilled_bytes.reset();
+    for (sink, bytes) in sink_registry.spilled_bytes() {
+        METRICS
+            .writer_spilled_bytes
+            .with_label_values(&[sink])
+            .set(bytes as i64);
+    }
+
+    let encoder = TextEncoder::new();
+    let mut body = Vec::new();
+    if let Err(e) = encoder.encode(&METRICS.registry.gather(), &mut body) {
+        tracing::error!("Failed to encode metrics: {}", e);
+        return http::StatusCode::INTERNAL_SERVER_ERROR.into_response();
+    }
+
+    ([(CONTENT_TYPE, encoder.format_type().to_string())], body).into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generated::record_publisher::{InputRecord, Tier};
+
+    #[test]
+    fn test_record_metrics() {
+        let input = Record {
+            workspace_id: None,
+            record_data: Some(RecordData::Input(InputRecord {
+                tier: Tier::Component.into(),
+                ..Default::default()
+            })),
+        };
+        let received = || {
+            METRICS
+                .records_received
+                .with_label_values(&["input", "COMPONENT"])
+                .get()
+        };
+        let rejected = || {
+            METRICS
+                .records_rejected
+                .with_label_values(&["BadEnum"])
+                .get()
+        };
+        let (received_before, rejected_before) = (received(), rejected());
+
+        records_received(&[input.clone(), input]);
+        record_rejected(&ParseError::BadEnum("tier".to_string()));
+
+        assert_eq!(received() - received_before, 2);
+        assert_eq!(rejected() - rejected_before, 1);
+
+        let text = String::from_utf8({
+            let mut body = Vec::new();
+            TextEncoder::new()
+                .encode(&METRICS.registry.gather(), &mut body)
+                .unwrap();
+            body
+        })
+        .unwrap();
+        assert!(text
+            .contains("ptolemy_records_received_total{record_type=\"input\",tier=\"COMPONENT\"}"));
+    }
+
+    #[tokio::test]
+    async fn test_grpc_routes_are_bounded() {
+        use tower::ServiceExt;
+
+        // Mounted the way tonic mounts a service, by name alone.
+        let router = axum::Router::new().route(
+            "/record_publisher.RecordPublisher/*rest",
+            axum::routing::post(
+                |request: Request| async move { route(&request, true).to_string() },
+            ),
+        );
+        let route_of = |path: &str| {
+            let request = Request::post(path).body(axum::body::Body::empty()).unwrap();
+            let router = router.clone();
+            async move {
+                let body = router.oneshot(request).await.unwrap().into_body();
+                axum::body::to_bytes(body, usize::MAX).await.unwrap()
+            }
+        };
+
+        assert_eq!(
+            route_of("/record_publisher.RecordPublisher/Publish").await,
+            "/record_publisher.RecordPublisher/Publish"
+        );
+        assert_eq!(
+            route_of("/record_publisher.RecordPublisher/a8f3c1").await,
+            "unmatched"
+        );
+    }
+}
@@ -1,13 +1,23 @@
+pub mod audit;
+pub mod auth;
 pub mod config;
 pub mod crypto;
 pub mod error;
+pub mod iam;
+pub mod jwks;
+pub mod limits;
+pub mod metrics;
 pub mod routes;
+pub mod server;
 pub mod services;
 pub mod sink;
+pub mod spool;
 pub mod state;
+pub mod tls;
 pub mod tracing;
 
 pub mod consts {
     pub const SERVICE_API_KEY_PREFIX: &str = "pt-sk";
     pub const USER_API_KEY_PREFIX: &str = "pt-pa";
+    pub const REFRESH_TOKEN_PREFIX: &str = "pt-rt";
 }
@@ -0,0 +1,100 @@
+use crate::api::{
+    audit::{AuditAction, AuditContext},
+    error::ApiError,
+    iam::{
+        api_keys::{ApiKey, NewApiKey},
+        rbac::{WorkspaceAccess, WorkspaceRole},
+    },
+    state::PtolemyState,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{delete, get},
+    Json, Router,
+};
+use serde::Serialize;
+use uuid::Uuid;
+
+#[derive(Debug, Serialize)]
+struct CreatedApiKey {
+    #[serde(flatten)]
+    key: ApiKey,
+    /// Only ever returned here.
+    api_key: String,
+}
+
+async fn create_api_key(
+    State(state): State<PtolemyState>,
+    access: WorkspaceAccess,
+    context: AuditContext,
+    Json(new): Json<NewApiKey>,
+) -> Result<(StatusCode, Json<CreatedApiKey>), ApiError> {
+    access.require(WorkspaceRole::Manager)?;
+
+    if state.config.load().iam.require_api_key_expiry && new.expires_at.is_none() {
+        return Err(ApiError::BadQuery);
+    }
+
+    let result = state.api_keys.create(access.workspace_id, new).await;
+    let event = context.event(AuditAction::ApiKeyCreate);
+    match &result {
+        Ok((key, _)) => state.audit.record(event.target(key.id)),
+        Err(e) => state
+            .audit
+            .record(event.target(access.workspace_id).failure(e)),
+    }
+
+    let (key, api_key) = result?;
+    tracing::info!(
+        "Created API key {} ({}) in workspace {}",
+        key.id,
+        key.name,
+        key.workspace_id
+    );
+
+    Ok((StatusCode::CREATED, Json(CreatedApiKey { key, api_key })))
+}
+
+async fn list_api_keys(
+    State(state): State<PtolemyState>,
+    access: WorkspaceAccess,
+) -> Result<Json<Vec<ApiKey>>, ApiError> {
+    access.require(WorkspaceRole::User)?;
+    Ok(Json(state.api_keys.list(&access.workspace_id)))
+}
+
+async fn revoke_api_key(
+    State(state): State<PtolemyState>,
+    access: WorkspaceAccess,
+    context: AuditContext,
+    Path((_, id)): Path<(Uuid, Uuid)>,
+) -> Result<Json<ApiKey>, ApiError> {
+    access.require(WorkspaceRole::Manager)?;
+
+    let result = state.api_keys.revoke(&access.workspace_id, &id);
+    state
+        .audit
+        .record_result(context.event(AuditAction::ApiKeyRevoke).target(id), &result);
+
+    let key = result?;
+    tracing::info!(
+        "Revoked API key {} ({}) in workspace {}",
+        key.id,
+        key.name,
+        key.workspace_id
+    );
+
+    Ok(Json(key))
+}
+
+/// A workspace's service API keys, under
+/// `/v1/workspaces/:workspace_id/api-keys`. Keys are only managed over HTTP;
+/// there is no gRPC counterpart, since the only gRPC schema is the record
+/// publisher's.
+pub fn router() -> Router<PtolemyState> {
+    Router::new()
+        .route("/", get(list_api_keys).post(create_api_key))
+        .route("/:id", delete(revoke_api_key))
+}
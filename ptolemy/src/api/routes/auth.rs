@@ -0,0 +1,176 @@
+use crate::api::{
+    audit::{AuditAction, AuditContext},
+    auth::{self, Identity},
+    error::ApiError,
+    state::PtolemyState,
+};
+
+use axum::{
+    extract::State,
+    http::{header::CACHE_CONTROL, StatusCode},
+    response::IntoResponse,
+    routing::{post, put},
+    Extension, Json, Router,
+};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+struct Credentials {
+    username: String,
+    password: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct RefreshRequest {
+    refresh_token: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct PasswordChange {
+    current_password: String,
+    new_password: String,
+}
+
+#[derive(Debug, Serialize)]
+struct TokenResponse {
+    access_token: String,
+    token_type: &'static str,
+    expires_in: u64,
+    refresh_token: String,
+}
+
+async fn issue_tokens(state: &PtolemyState, user_id: Uuid) -> Result<TokenResponse, ApiError> {
+    let authenticator = state.authenticator.load();
+    let access_token = authenticator.issue_access_token(user_id)?;
+
+    let refresh_ttl = state
+        .config
+        .load()
+        .auth
+        .clone()
+        .unwrap_or_default()
+        .refresh_token_ttl();
+    let refresh_token = state.refresh_tokens.issue(user_id, refresh_ttl).await?;
+
+    Ok(TokenResponse {
+        access_token,
+        token_type: "Bearer",
+        expires_in: authenticator.access_token_ttl().as_secs(),
+        refresh_token,
+    })
+}
+
+async fn token(
+    State(state): State<PtolemyState>,
+    context: AuditContext,
+    Json(credentials): Json<Credentials>,
+) -> Result<Json<TokenResponse>, ApiError> {
+    let event = context
+        .event(AuditAction::Login)
+        .target(&credentials.username);
+
+    let Some(user) = state
+        .users
+        .verify_password(&credentials.username, &credentials.password)
+        .await
+    else {
+        let e = ApiError::AuthError("Invalid username or password".to_string());
+        state.audit.record(event.failure(&e));
+        return Err(e);
+    };
+
+    let event = match state.authenticator.load().user(&user.id) {
+        Some(identity) => event.actor(identity),
+        None => event,
+    };
+    let tokens = issue_tokens(&state, user.id).await;
+    state.audit.record_result(event, &tokens);
+
+    tracing::info!("User {} logged in", user.username);
+    Ok(Json(tokens?))
+}
+
+/// Trades a refresh token for new access and refresh tokens.
+async fn refresh(
+    State(state): State<PtolemyState>,
+    context: AuditContext,
+    Json(request): Json<RefreshRequest>,
+) -> Result<Json<TokenResponse>, ApiError> {
+    let mut event = context.event(AuditAction::TokenRefresh);
+
+    let result = match state.refresh_tokens.redeem(&request.refresh_token) {
+        Ok(user_id) => {
+            event = event.target(user_id);
+            match state.users.get(&user_id) {
+                Some(user) if user.is_active() => issue_tokens(&state, user.id).await,
+                _ => Err(ApiError::AuthError("Invalid refresh token".to_string())),
+            }
+        }
+        Err(e) => Err(e),
+    };
+    state.audit.record_result(event, &result);
+
+    Ok(Json(result?))
+}
+
+async fn logout(
+    State(state): State<PtolemyState>,
+    context: AuditContext,
+    Json(request): Json<RefreshRequest>,
+) -> Result<StatusCode, ApiError> {
+    let result = state.refresh_tokens.revoke(&request.refresh_token);
+    state
+        .audit
+        .record_result(context.event(AuditAction::Logout), &result);
+
+    result.map(|_| StatusCode::NO_CONTENT)
+}
+
+/// Changes the caller's password and logs them out everywhere else.
+async fn change_password(
+    State(state): State<PtolemyState>,
+    Extension(identity): Extension<Identity>,
+    context: AuditContext,
+    Json(change): Json<PasswordChange>,
+) -> Result<StatusCode, ApiError> {
+    let Identity::User { id, .. } = identity else {
+        return Err(ApiError::PermissionDenied);
+    };
+
+    let result = state
+        .users
+        .change_password(&id, &change.current_password, &change.new_password)
+        .await
+        .and_then(|_| state.refresh_tokens.revoke_user(&id));
+    state.audit.record_result(
+        context.event(AuditAction::PasswordChange).target(id),
+        &result,
+    );
+    result?;
+    tracing::info!("User {} changed their password", id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Public keys for verifying tokens, served at `/.well-known/jwks.json`.
+pub async fn jwks(State(state): State<PtolemyState>) -> impl IntoResponse {
+    let jwks = state.authenticator.load().jwks();
+    ([(CACHE_CONTROL, "public, max-age=300")], Json(jwks))
+}
+
+/// Login and token endpoints, under `/auth`.
+pub fn router(state: PtolemyState) -> Router<PtolemyState> {
+    let authenticated = Router::new()
+        .route("/password", put(change_password))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state,
+            auth::authenticate,
+        ));
+
+    Router::new()
+        .route("/token", post(token))
+        .route("/refresh", post(refresh))
+        .route("/logout", post(logout))
+        .merge(authenticated)
+}
@@ -0,0 +1,23 @@
+use crate::api::state::{PtolemyState, Readiness};
+
+use axum::{extract::State, Json};
+use http::StatusCode;
+use serde_json::{json, Value};
+
+/// Liveness: answers as long as the process is serving requests.
+pub async fn healthz() -> Json<Value> {
+    Json(json!({"status": "ok"}))
+}
+
+/// Readiness, with each sink's status. Responds 503 while not ready so load
+/// balancers and orchestrators hold traffic back.
+pub async fn readyz(State(state): State<PtolemyState>) -> (StatusCode, Json<Readiness>) {
+    let readiness = state.readiness();
+
+    let status = match readiness.ready {
+        true => StatusCode::OK,
+        false => StatusCode::SERVICE_UNAVAILABLE,
+    };
+
+    (status, Json(readiness))
+}
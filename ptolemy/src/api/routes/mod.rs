@@ -1,17 +1,43 @@
-use super::state::PtolemyState;
-use axum::Router;
+use super::{metrics, state::PtolemyState, tracing::REQUEST_ID};
+
+mod api_keys;
+mod auth;
+mod health;
+mod otlp;
+mod records;
+mod users;
+mod workspaces;
+
+use axum::{
+    extract::{DefaultBodyLimit, Request, State},
+    middleware::Next,
+    response::Response,
+    Router,
+};
 
 use http::{
     header::{AUTHORIZATION, CONTENT_TYPE},
     HeaderName, Method,
 };
-use tower_http::cors::{Any, CorsLayer};
+use opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::TraceServiceServer;
+use tonic_web::GrpcWebLayer;
+use tower::Layer;
+use tower_http::{
+    cors::{Any, CorsLayer},
+    request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer},
+};
 
 pub fn get_cors_layer() -> CorsLayer {
     let api_key = HeaderName::from_lowercase(b"x-api-key").unwrap();
     let grpc_web = HeaderName::from_lowercase(b"x-grpc-web").unwrap();
     let grpc_accept = HeaderName::from_lowercase(b"grpc-accept-encoding").unwrap();
     let grpc_encoding = HeaderName::from_lowercase(b"grpc-encoding").unwrap();
+    let grpc_timeout = HeaderName::from_lowercase(b"grpc-timeout").unwrap();
+    let user_agent = HeaderName::from_lowercase(b"x-user-agent").unwrap();
+    let grpc_status = HeaderName::from_lowercase(b"grpc-status").unwrap();
+    let grpc_message = HeaderName::from_lowercase(b"grpc-message").unwrap();
+    let grpc_details = HeaderName::from_lowercase(b"grpc-status-details-bin").unwrap();
+    let request_id = HeaderName::from_static(REQUEST_ID);
 
     CorsLayer::new()
         .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
@@ -23,23 +49,151 @@ pub fn get_cors_layer() -> CorsLayer {
             grpc_web,
             grpc_accept,
             grpc_encoding,
+            grpc_timeout,
+            user_agent,
+            request_id.clone(),
         ])
+        .expose_headers([grpc_status, grpc_message, grpc_details, request_id])
 }
 
-pub async fn get_router(state: PtolemyState) -> Router {
+/// gRPC services anyone can call, so health probes and tools like grpcurl
+/// work without credentials.
+const PUBLIC_GRPC_SERVICES: [&str; 3] = [
+    "grpc.health.v1.Health",
+    "grpc.reflection.v1.ServerReflection",
+    "grpc.reflection.v1alpha.ServerReflection",
+];
+
+async fn authenticate_grpc(state: State<PtolemyState>, request: Request, next: Next) -> Response {
+    let service = request.uri().path().split('/').nth(1);
+
+    match service.is_some_and(|s| PUBLIC_GRPC_SERVICES.contains(&s)) {
+        true => next.run(request).await,
+        false => super::auth::authenticate(state, request, next).await,
+    }
+}
+
+/// Describes every service the gRPC router serves.
+fn reflection() -> tonic_reflection::server::Builder<'static> {
+    tonic_reflection::server::Builder::configure()
+        .register_encoded_file_descriptor_set(crate::generated::RECORD_PUBLISHER_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(crate::generated::OTLP_TRACE_DESCRIPTOR_SET)
+        .register_encoded_file_descriptor_set(tonic_health::pb::FILE_DESCRIPTOR_SET)
+}
+
+/// The gRPC services, reachable over gRPC-web too so browsers can call them.
+pub async fn get_grpc_router(state: PtolemyState) -> Router {
+    let body_limit = state.config.load().server.request_body_limit_bytes;
+    let grpc_web = GrpcWebLayer::new();
+
     let publisher_service =
         crate::generated::record_publisher::record_publisher_server::RecordPublisherServer::new(
             super::services::RecordPublisherService::new(state.clone()),
-        );
+        )
+        .max_decoding_message_size(body_limit);
+
+    let trace_service =
+        TraceServiceServer::new(super::services::otlp::OtlpTraceService::new(state.clone()))
+            .max_decoding_message_size(body_limit);
 
-    let grpc_router = tonic::service::Routes::builder()
+    // Both versions, since older grpcurl releases only speak v1alpha.
+    let reflection_v1 = reflection().build_v1().unwrap();
+    let reflection_v1alpha = reflection().build_v1alpha().unwrap();
+
+    tonic::service::Routes::builder()
         .routes()
-        .add_service(publisher_service)
-        .into_axum_router();
+        .add_service(grpc_web.layer(publisher_service))
+        .add_service(grpc_web.layer(trace_service))
+        .add_service(grpc_web.layer(super::services::health::health_service(state.clone())))
+        .add_service(reflection_v1)
+        .add_service(reflection_v1alpha)
+        .into_axum_router()
+        .route_layer(axum::middleware::from_fn_with_state(
+            state,
+            authenticate_grpc,
+        ))
+        .layer(crate::trace_layer!(Grpc))
+        .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+            REQUEST_ID,
+        )))
+        .layer(SetRequestIdLayer::new(
+            HeaderName::from_static(REQUEST_ID),
+            MakeRequestUuid,
+        ))
+        .layer(axum::middleware::from_fn(metrics::track_requests))
+        .layer(get_cors_layer())
+}
+
+pub async fn get_http_router(state: PtolemyState) -> Router {
+    let body_limit = state.config.load().server.request_body_limit_bytes;
 
     Router::new()
         .route("/ping", axum::routing::get(|| async move { "Pong!" }))
+        .route("/healthz", axum::routing::get(health::healthz))
+        .route("/readyz", axum::routing::get(health::readyz))
+        .route("/.well-known/jwks.json", axum::routing::get(auth::jwks))
+        .route("/metrics", axum::routing::get(metrics::metrics))
+        .nest("/auth", auth::router(state.clone()))
+        .nest("/v1/records", records::router(state.clone()))
+        .nest("/v1/traces", otlp::router(state.clone()))
+        .nest("/v1/users", users::router(state.clone()))
+        .nest("/v1/workspaces", workspaces::router(state.clone()))
         .with_state(state)
-        .merge(grpc_router)
+        .layer(DefaultBodyLimit::max(body_limit))
+        .layer(crate::trace_layer!(Http))
+        .layer(PropagateRequestIdLayer::new(HeaderName::from_static(
+            REQUEST_ID,
+        )))
+        .layer(SetRequestIdLayer::new(
+            HeaderName::from_static(REQUEST_ID),
+            MakeRequestUuid,
+        ))
+        .layer(axum::middleware::from_fn(metrics::track_requests))
         .layer(get_cors_layer())
 }
+
+/// HTTP and gRPC routes together, for serving both on one port.
+pub async fn get_router(state: PtolemyState) -> Router {
+    get_http_router(state.clone())
+        .await
+        .merge(get_grpc_router(state).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use prost::Message;
+
+    #[test]
+    fn test_reflection_describes_served_services() {
+        use crate::generated::record_publisher::record_publisher_server;
+        use opentelemetry_proto::tonic::collector::trace::v1::trace_service_server;
+
+        let methods: Vec<String> = [
+            crate::generated::RECORD_PUBLISHER_DESCRIPTOR_SET,
+            crate::generated::OTLP_TRACE_DESCRIPTOR_SET,
+        ]
+        .into_iter()
+        .flat_map(|set| prost_types::FileDescriptorSet::decode(set).unwrap().file)
+        .flat_map(|file| {
+            file.service
+                .iter()
+                .flat_map(|service| {
+                    service.method.iter().map(|method| {
+                        format!("/{}.{}/{}", file.package(), service.name(), method.name())
+                    })
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect();
+
+        // The descriptors have to match the services actually compiled in.
+        for method in [
+            format!("/{}/Publish", record_publisher_server::SERVICE_NAME),
+            format!("/{}/Export", trace_service_server::SERVICE_NAME),
+        ] {
+            assert!(methods.contains(&method), "{} isn't described", method);
+        }
+        assert!(reflection().build_v1().is_ok());
+    }
+}
@@ -0,0 +1,62 @@
+use crate::api::{auth, error::ApiError, services::otlp::export_traces, state::PtolemyState};
+
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, State},
+    response::{IntoResponse, Response},
+    routing::post,
+    Extension, Router,
+};
+use http::{header::CONTENT_TYPE, HeaderMap};
+use opentelemetry_proto::tonic::collector::trace::v1::ExportTraceServiceRequest;
+use prost::Message;
+use std::net::SocketAddr;
+
+const PROTOBUF: &str = "application/x-protobuf";
+
+/// OTLP/HTTP trace export, in binary protobuf or JSON. The response is
+/// encoded the same way as the request.
+async fn export(
+    State(state): State<PtolemyState>,
+    Extension(identity): Extension<auth::Identity>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, ApiError> {
+    let is_protobuf = headers
+        .get(CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with(PROTOBUF));
+
+    let request = match is_protobuf {
+        true => ExportTraceServiceRequest::decode(body.as_ref())
+            .map_err(|e| ApiError::InvalidRecord(e.to_string()))?,
+        false => {
+            serde_json::from_slice(&body).map_err(|e| ApiError::InvalidRecord(e.to_string()))?
+        }
+    };
+
+    let response = export_traces(
+        &state,
+        &identity,
+        connect_info.map(|ConnectInfo(addr)| addr.ip()),
+        request,
+        body.len() as u64,
+    )
+    .await?;
+
+    Ok(match is_protobuf {
+        true => ([(CONTENT_TYPE, PROTOBUF)], response.encode_to_vec()).into_response(),
+        false => axum::Json(response).into_response(),
+    })
+}
+
+/// The OTLP/HTTP receiver, under `/v1/traces`.
+pub fn router(state: PtolemyState) -> Router<PtolemyState> {
+    Router::new()
+        .route("/", post(export))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state,
+            auth::authenticate,
+        ))
+}
@@ -0,0 +1,276 @@
+use crate::api::{auth, error::ApiError, metrics, services::publish_records, state::PtolemyState};
+use crate::error::ParseError;
+use crate::generated::record_publisher;
+use crate::models::{Record, TraceDocument};
+
+use axum::{
+    body::Bytes,
+    extract::{ConnectInfo, State},
+    routing::post,
+    Extension, Json, Router,
+};
+use http::{header::CONTENT_TYPE, HeaderMap};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+
+/// The byte items in a streamed body are separated by: a newline for NDJSON,
+/// or the record separator that starts each item of a JSON text sequence
+/// (RFC 7464). `None` for a plain JSON body.
+fn delimiter(headers: &HeaderMap) -> Option<u8> {
+    let content_type = headers.get(CONTENT_TYPE)?.to_str().ok()?;
+    if content_type.starts_with("application/x-ndjson")
+        || content_type.starts_with("application/jsonl")
+    {
+        Some(b'\n')
+    } else if content_type.starts_with("application/json-seq") {
+        Some(0x1E)
+    } else {
+        None
+    }
+}
+
+/// Counts a rejected item under `error` and describes it as item `n`.
+fn rejected(what: &str, n: usize, error: ParseError, reason: String) -> ApiError {
+    metrics::record_rejected(&error);
+    ApiError::InvalidRecord(format!("{} {}: {}", what, n, reason))
+}
+
+fn parse_record(n: usize, value: Value) -> Result<Vec<record_publisher::Record>, ApiError> {
+    let bad_json = |e: serde_json::Error| rejected("record", n, ParseError::BadJSON, e.to_string());
+    let invalid = |e: ParseError| {
+        let reason = format!("{:?}", e);
+        rejected("record", n, e, reason)
+    };
+
+    let record: Record = serde_json::from_value(value).map_err(bad_json)?;
+    Ok(vec![record.try_into().map_err(invalid)?])
+}
+
+fn parse_document(n: usize, value: Value) -> Result<Vec<record_publisher::Record>, ApiError> {
+    let bad_json =
+        |e: serde_json::Error| rejected("document", n, ParseError::BadJSON, e.to_string());
+    let invalid = |e: ParseError| {
+        let reason = format!("{:?}", e);
+        rejected("document", n, e, reason)
+    };
+
+    let document: TraceDocument = serde_json::from_value(value).map_err(bad_json)?;
+    document
+        .into_records()
+        .map_err(invalid)?
+        .into_iter()
+        .map(|record| record.try_into().map_err(invalid))
+        .collect()
+}
+
+/// Parses a JSON body (one item, or an array of them) or a streamed body (one
+/// item per line or sequence entry) with `parse`, which is given items
+/// numbered from 1.
+fn parse_body<F>(
+    headers: &HeaderMap,
+    body: &[u8],
+    what: &str,
+    parse: F,
+) -> Result<Vec<record_publisher::Record>, ApiError>
+where
+    F: Fn(usize, Value) -> Result<Vec<record_publisher::Record>, ApiError>,
+{
+    let values = match delimiter(headers) {
+        Some(delimiter) => body
+            .split(|b| *b == delimiter)
+            .map(|item| item.trim_ascii())
+            .filter(|item| !item.is_empty())
+            .enumerate()
+            .map(|(i, item)| {
+                serde_json::from_slice(item)
+                    .map_err(|e| rejected(what, i + 1, ParseError::BadJSON, e.to_string()))
+            })
+            .collect::<Result<Vec<Value>, _>>()?,
+        None => match serde_json::from_slice(body).map_err(|e| {
+            metrics::record_rejected(&ParseError::BadJSON);
+            ApiError::InvalidRecord(e.to_string())
+        })? {
+            Value::Array(values) => values,
+            value => vec![value],
+        },
+    };
+
+    let mut records = Vec::with_capacity(values.len());
+    for (i, value) in values.into_iter().enumerate() {
+        records.extend(parse(i + 1, value)?);
+    }
+
+    Ok(records)
+}
+
+/// Records in the JSON shape sinks write them in.
+fn parse_records(
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Vec<record_publisher::Record>, ApiError> {
+    parse_body(headers, body, "record", parse_record)
+}
+
+/// Records expanded from nested trace documents.
+fn parse_documents(
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<Vec<record_publisher::Record>, ApiError> {
+    parse_body(headers, body, "document", parse_document)
+}
+
+async fn publish_parsed(
+    state: &PtolemyState,
+    identity: &auth::Identity,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    records: Vec<record_publisher::Record>,
+    bytes: usize,
+) -> Result<Json<Value>, ApiError> {
+    let count = records.len();
+
+    publish_records(
+        state,
+        identity,
+        connect_info.map(|ConnectInfo(addr)| addr.ip()),
+        records,
+        bytes as u64,
+    )
+    .await?;
+
+    Ok(Json(json!({ "published": count })))
+}
+
+/// Publishes records in the JSON shape sinks write them in. Either all of
+/// them are published or, if any is invalid, none are.
+async fn publish(
+    State(state): State<PtolemyState>,
+    Extension(identity): Extension<auth::Identity>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, ApiError> {
+    let records = parse_records(&headers, &body)?;
+    publish_parsed(&state, &identity, connect_info, records, body.len()).await
+}
+
+/// Publishes the records nested trace documents expand into, all or none
+/// like `publish`.
+async fn publish_documents(
+    State(state): State<PtolemyState>,
+    Extension(identity): Extension<auth::Identity>,
+    connect_info: Option<ConnectInfo<SocketAddr>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Json<Value>, ApiError> {
+    let records = parse_documents(&headers, &body)?;
+    publish_parsed(&state, &identity, connect_info, records, body.len()).await
+}
+
+/// HTTP ingestion, under `/v1/records`.
+pub fn router(state: PtolemyState) -> Router<PtolemyState> {
+    Router::new()
+        .route("/", post(publish))
+        .route("/documents", post(publish_documents))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state,
+            auth::authenticate,
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_records() {
+        let event = json!({
+            "record_type": "event",
+            "tier": "SYSTEM",
+            "subject_id": "5e1c1b2a-6a2c-4b7a-9d8e-0f1a2b3c4d5e",
+            "parent_id": "5e1c1b2a-6a2c-4b7a-9d8e-0f1a2b3c4d5e",
+            "id": "7a9b8c7d-6e5f-4a3b-8c2d-1e0f9a8b7c6d",
+            "name": "rag",
+            "parameters": {"k": 5},
+            "version": null,
+            "environment": "dev",
+        });
+        let input = json!({
+            "record_type": "input",
+            "tier": "SYSTEM",
+            "subject_id": "5e1c1b2a-6a2c-4b7a-9d8e-0f1a2b3c4d5e",
+            "event_id": "7a9b8c7d-6e5f-4a3b-8c2d-1e0f9a8b7c6d",
+            "id": "0b1c2d3e-4f5a-4b6c-8d7e-9f0a1b2c3d4e",
+            "field_name": "query",
+            "field_value_type": "STRING",
+            "field_value_str": "hello",
+        });
+
+        let json_body = serde_json::to_vec(&json!([event, input])).unwrap();
+        assert_eq!(
+            parse_records(&HeaderMap::new(), &json_body).unwrap().len(),
+            2
+        );
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, "application/x-ndjson".parse().unwrap());
+        let ndjson_body = format!("{}\n\n{}\n", event, input);
+        let records = parse_records(&headers, ndjson_body.as_bytes()).unwrap();
+        let parsed: Vec<Record> = records.into_iter().map(|r| r.try_into().unwrap()).collect();
+        assert!(
+            matches!(&parsed[1], Record::Input(i) if i.field_value_str.as_deref() == Some("hello"))
+        );
+
+        // An input claiming a type it has no value for is rejected.
+        let mut bad = input.clone();
+        bad["field_value_type"] = json!("INT");
+        let ndjson_body = format!("{}\n{}\n", event, bad);
+        assert_eq!(
+            parse_records(&headers, ndjson_body.as_bytes()),
+            Err(ApiError::InvalidRecord(
+                "record 2: MissingField".to_string()
+            ))
+        );
+
+        // JSON text sequences start each record with a record separator.
+        let mut seq_headers = HeaderMap::new();
+        seq_headers.insert(CONTENT_TYPE, "application/json-seq".parse().unwrap());
+        let json_seq_body = format!("\x1e{}\n\x1e{}\n", event, input);
+        assert_eq!(
+            parse_records(&seq_headers, json_seq_body.as_bytes())
+                .unwrap()
+                .len(),
+            2
+        );
+        assert!(matches!(
+            parse_records(&seq_headers, format!("\x1e{}\n\x1e{{\n", event).as_bytes()),
+            Err(ApiError::InvalidRecord(e)) if e.starts_with("record 2: EOF")
+        ));
+
+        // Runtimes reach the sinks with their microseconds intact.
+        let runtime = json!({
+            "record_type": "runtime",
+            "tier": "SYSTEM",
+            "subject_id": "5e1c1b2a-6a2c-4b7a-9d8e-0f1a2b3c4d5e",
+            "event_id": "7a9b8c7d-6e5f-4a3b-8c2d-1e0f9a8b7c6d",
+            "id": "1c2d3e4f-5a6b-4c7d-8e9f-0a1b2c3d4e5f",
+            "start_time": 1_730_000_012_345_678_i64,
+            "end_time": 1_730_000_012_345_999_i64,
+        });
+        let records = parse_records(&HeaderMap::new(), runtime.to_string().as_bytes()).unwrap();
+        let published = crate::models::PublishedRecord::try_from(records[0].clone()).unwrap();
+        let published = serde_json::to_value(published).unwrap();
+        assert_eq!(published["start_time"], runtime["start_time"]);
+        assert_eq!(published["end_time"], runtime["end_time"]);
+
+        // Documents expand into their records, and are numbered as documents.
+        let document = include_str!("../../../../docs/sample/sample_log.json");
+        let records = parse_documents(&HeaderMap::new(), document.as_bytes()).unwrap();
+        assert!(records.len() > 2);
+        assert_eq!(
+            parse_documents(&headers, format!("{{}}\n{}", event).as_bytes()),
+            Err(ApiError::InvalidRecord(
+                "document 1: missing field `tier`".to_string()
+            ))
+        );
+    }
+}
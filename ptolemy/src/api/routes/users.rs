@@ -0,0 +1,142 @@
+use crate::api::{
+    audit::{AuditAction, AuditContext},
+    auth::{self, Identity},
+    error::ApiError,
+    iam::{
+        rbac::{require_system_role, SystemRole},
+        users::{NewUser, User},
+    },
+    state::PtolemyState,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::get,
+    Extension, Json, Router,
+};
+use uuid::Uuid;
+
+const USER_ADMINS: &[SystemRole] = &[SystemRole::Admin, SystemRole::Sysadmin];
+
+/// Only admins grant system roles. An admin can see every workspace, so
+/// letting the sysadmin make one would let it into workspace data.
+fn require_can_create(identity: &Identity, new: &NewUser) -> Result<(), ApiError> {
+    match new.system_role {
+        None => require_system_role(identity, USER_ADMINS),
+        Some(_) => require_system_role(identity, &[SystemRole::Admin]),
+    }
+}
+
+async fn create_user(
+    State(state): State<PtolemyState>,
+    Extension(identity): Extension<Identity>,
+    context: AuditContext,
+    Json(new): Json<NewUser>,
+) -> Result<(StatusCode, Json<User>), ApiError> {
+    require_can_create(&identity, &new)?;
+
+    let event = context.event(AuditAction::UserCreate);
+    let result = state.users.create(new).await;
+    match &result {
+        Ok(user) => state.audit.record(event.target(user.id)),
+        Err(e) => state.audit.record(event.failure(e)),
+    }
+
+    let user = result?;
+    tracing::info!("Created user {} ({})", user.id, user.username);
+
+    Ok((StatusCode::CREATED, Json(user)))
+}
+
+async fn list_users(
+    State(state): State<PtolemyState>,
+    Extension(identity): Extension<Identity>,
+) -> Result<Json<Vec<User>>, ApiError> {
+    require_system_role(&identity, USER_ADMINS)?;
+    Ok(Json(state.users.list()))
+}
+
+/// Users can look themselves up; anyone else needs to manage users.
+async fn get_user(
+    State(state): State<PtolemyState>,
+    Extension(identity): Extension<Identity>,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<User>, ApiError> {
+    if !matches!(identity, Identity::User { id, .. } if id == user_id) {
+        require_system_role(&identity, USER_ADMINS)?;
+    }
+
+    state
+        .users
+        .get(&user_id)
+        .filter(|user| user.is_active())
+        .map(Json)
+        .ok_or(ApiError::NotFoundError)
+}
+
+async fn delete_user(
+    State(state): State<PtolemyState>,
+    Extension(identity): Extension<Identity>,
+    context: AuditContext,
+    Path(user_id): Path<Uuid>,
+) -> Result<Json<User>, ApiError> {
+    require_system_role(&identity, USER_ADMINS)?;
+
+    // Memberships go first, so a deleted user can't stay a workspace admin.
+    let result = state
+        .workspaces
+        .remove_user(&user_id)
+        .and_then(|_| state.users.delete(&user_id));
+    state.audit.record_result(
+        context.event(AuditAction::UserDelete).target(user_id),
+        &result,
+    );
+
+    let user = result?;
+    state.refresh_tokens.revoke_user(&user.id)?;
+    tracing::info!("Deleted user {} ({})", user.id, user.username);
+
+    Ok(Json(user))
+}
+
+/// User management, under `/v1/users`.
+pub fn router(state: PtolemyState) -> Router<PtolemyState> {
+    Router::new()
+        .route("/", get(list_users).post(create_user))
+        .route("/:user_id", get(get_user).delete(delete_user))
+        .route_layer(axum::middleware::from_fn_with_state(
+            state,
+            auth::authenticate,
+        ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_admins_grant_system_roles() {
+        let user = |system_role| Identity::User {
+            id: Uuid::new_v4(),
+            system_role,
+        };
+        let new_user = |system_role| NewUser {
+            username: "ada".to_string(),
+            password: "hunter2".to_string(),
+            display_name: None,
+            system_role,
+        };
+
+        let sysadmin = user(Some(SystemRole::Sysadmin));
+        assert!(require_can_create(&sysadmin, &new_user(None)).is_ok());
+        assert_eq!(
+            require_can_create(&sysadmin, &new_user(Some(SystemRole::Admin))),
+            Err(ApiError::PermissionDenied)
+        );
+
+        let admin = user(Some(SystemRole::Admin));
+        assert!(require_can_create(&admin, &new_user(Some(SystemRole::Admin))).is_ok());
+        assert!(require_can_create(&user(None), &new_user(None)).is_err());
+    }
+}
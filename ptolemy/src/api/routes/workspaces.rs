@@ -0,0 +1,199 @@
+use super::api_keys;
+use crate::api::{
+    audit::{AuditAction, AuditContext},
+    auth::{self, Identity},
+    error::ApiError,
+    iam::{
+        rbac::{require_system_role, SystemRole, WorkspaceAccess, WorkspaceRole},
+        workspaces::{Member, Workspace},
+    },
+    state::PtolemyState,
+};
+
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    routing::{get, put},
+    Extension, Json, Router,
+};
+use serde::Deserialize;
+use uuid::Uuid;
+
+/// Only active users, other than the sysadmin, can join workspaces.
+fn require_user(state: &PtolemyState, user_id: &Uuid) -> Result<(), ApiError> {
+    match state.users.get(user_id) {
+        Some(user) if user.is_active() && user.system_role != Some(SystemRole::Sysadmin) => Ok(()),
+        _ => Err(ApiError::BadQuery),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct NewWorkspace {
+    name: String,
+    /// The workspace's first admin, which need not be the caller.
+    admin_user_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+struct MemberRole {
+    role: WorkspaceRole,
+}
+
+async fn create_workspace(
+    State(state): State<PtolemyState>,
+    Extension(identity): Extension<Identity>,
+    context: AuditContext,
+    Json(new): Json<NewWorkspace>,
+) -> Result<(StatusCode, Json<Workspace>), ApiError> {
+    require_system_role(&identity, &[SystemRole::Admin])?;
+    require_user(&state, &new.admin_user_id)?;
+
+    let event = context.event(AuditAction::WorkspaceCreate);
+    let result = state.workspaces.create(new.name, new.admin_user_id);
+    match &result {
+        Ok(workspace) => state.audit.record(event.target(workspace.id)),
+        Err(e) => state.audit.record(event.failure(e)),
+    }
+
+    let workspace = result?;
+    tracing::info!("Created workspace {} ({})", workspace.id, workspace.name);
+
+    Ok((StatusCode::CREATED, Json(workspace)))
+}
+
+/// Every workspace for system admins, and the caller's own for everyone
+/// else.
+async fn list_workspaces(
+    State(state): State<PtolemyState>,
+    Extension(identity): Extension<Identity>,
+) -> Result<Json<Vec<Workspace>>, ApiError> {
+    match identity {
+        Identity::User {
+            system_role: Some(SystemRole::Admin),
+            ..
+        } => Ok(Json(state.workspaces.list())),
+        Identity::User { id, .. } => Ok(Json(state.workspaces.list_for_user(&id))),
+        _ => Err(ApiError::PermissionDenied),
+    }
+}
+
+async fn get_workspace(
+    State(state): State<PtolemyState>,
+    access: WorkspaceAccess,
+) -> Result<Json<Workspace>, ApiError> {
+    access.require(WorkspaceRole::User)?;
+
+    state
+        .workspaces
+        .get(&access.workspace_id)
+        .map(Json)
+        .ok_or(ApiError::NotFoundError)
+}
+
+/// Only workspace admins can delete a workspace, not system admins. Its API
+/// keys are revoked along with it.
+async fn delete_workspace(
+    State(state): State<PtolemyState>,
+    access: WorkspaceAccess,
+    context: AuditContext,
+) -> Result<Json<Workspace>, ApiError> {
+    access.require(WorkspaceRole::Admin)?;
+
+    let result = state
+        .api_keys
+        .revoke_workspace(&access.workspace_id)
+        .and_then(|_| state.workspaces.delete(&access.workspace_id));
+    state.audit.record_result(
+        context
+            .event(AuditAction::WorkspaceDelete)
+            .target(access.workspace_id),
+        &result,
+    );
+
+    let workspace = result?;
+    tracing::info!("Deleted workspace {} ({})", workspace.id, workspace.name);
+
+    Ok(Json(workspace))
+}
+
+async fn list_members(
+    State(state): State<PtolemyState>,
+    access: WorkspaceAccess,
+) -> Result<Json<Vec<Member>>, ApiError> {
+    access.require(WorkspaceRole::User)?;
+    Ok(Json(state.workspaces.members(&access.workspace_id)?))
+}
+
+async fn set_member(
+    State(state): State<PtolemyState>,
+    access: WorkspaceAccess,
+    context: AuditContext,
+    Path((_, user_id)): Path<(Uuid, Uuid)>,
+    Json(MemberRole { role }): Json<MemberRole>,
+) -> Result<Json<Member>, ApiError> {
+    access.require(WorkspaceRole::Admin)?;
+    require_user(&state, &user_id)?;
+
+    let result = state
+        .workspaces
+        .set_member(&access.workspace_id, Member { user_id, role });
+    state.audit.record_result(
+        context
+            .event(AuditAction::MemberSet)
+            .target(format!("{}/{}", access.workspace_id, user_id)),
+        &result,
+    );
+
+    let member = result?;
+    tracing::info!(
+        "Set role of {} in workspace {} to {:?}",
+        user_id,
+        access.workspace_id,
+        role
+    );
+
+    Ok(Json(member))
+}
+
+async fn remove_member(
+    State(state): State<PtolemyState>,
+    access: WorkspaceAccess,
+    context: AuditContext,
+    Path((_, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<StatusCode, ApiError> {
+    access.require(WorkspaceRole::Admin)?;
+
+    let result = state
+        .workspaces
+        .remove_member(&access.workspace_id, &user_id);
+    state.audit.record_result(
+        context
+            .event(AuditAction::MemberRemove)
+            .target(format!("{}/{}", access.workspace_id, user_id)),
+        &result,
+    );
+    result?;
+    tracing::info!("Removed {} from workspace {}", user_id, access.workspace_id);
+
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Workspaces, their members and their API keys, under `/v1/workspaces`.
+pub fn router(state: PtolemyState) -> Router<PtolemyState> {
+    Router::new()
+        .route("/", get(list_workspaces).post(create_workspace))
+        .route(
+            "/:workspace_id",
+            get(get_workspace).delete(delete_workspace),
+        )
+        .route("/:workspace_id/members", get(list_members))
+        .route(
+            "/:workspace_id/members/:user_id",
+            put(set_member).delete(remove_member),
+        )
+        .nest("/:workspace_id/api-keys", api_keys::router())
+        .route_layer(axum::middleware::from_fn_with_state(
+            state,
+            auth::authenticate,
+        ))
+}
@@ -0,0 +1,142 @@
+use super::config::server::ServerConfig;
+use super::tls::TlsAcceptor;
+
+use axum::{extract::ConnectInfo, Router};
+use hyper::body::Incoming;
+use hyper_util::{
+    rt::{TokioExecutor, TokioIo, TokioTimer},
+    server::{conn::auto, graceful::GracefulShutdown},
+};
+use std::future::Future;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{mpsc, OwnedSemaphorePermit, Semaphore};
+use tower::ServiceExt;
+
+async fn accept(listener: &TcpListener) -> (TcpStream, SocketAddr) {
+    loop {
+        match listener.accept().await {
+            Ok(conn) => return conn,
+            Err(e) => {
+                // Usually running out of file descriptors; back off instead of
+                // spinning.
+                tracing::error!("Failed to accept connection: {}", e);
+                tokio::time::sleep(Duration::from_millis(100)).await;
+            }
+        }
+    }
+}
+
+/// Waits for a free connection slot, if connections are limited, then for
+/// the next connection.
+async fn accept_with_permit(
+    listener: &TcpListener,
+    connections: &Option<Arc<Semaphore>>,
+) -> (TcpStream, SocketAddr, Option<OwnedSemaphorePermit>) {
+    let permit = match connections {
+        None => None,
+        Some(connections) => connections.clone().acquire_owned().await.ok(),
+    };
+
+    let (stream, remote_addr) = accept(listener).await;
+    (stream, remote_addr, permit)
+}
+
+fn configure_stream(stream: &TcpStream, config: &ServerConfig) {
+    if let Err(e) = stream.set_nodelay(true) {
+        tracing::debug!("Failed to set TCP_NODELAY: {}", e);
+    }
+
+    if let Some(interval) = config.keep_alive() {
+        let keepalive = socket2::TcpKeepalive::new().with_time(interval);
+        if let Err(e) = socket2::SockRef::from(stream).set_tcp_keepalive(&keepalive) {
+            tracing::debug!("Failed to set TCP keep-alive: {}", e);
+        }
+    }
+}
+
+fn serve_connection<IO>(
+    builder: &auto::Builder<TokioExecutor>,
+    graceful: &GracefulShutdown,
+    router: &Router,
+    (io, remote_addr, permit): (IO, SocketAddr, Option<OwnedSemaphorePermit>),
+) where
+    IO: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let router = router.clone();
+    let service = hyper::service::service_fn(move |mut request: http::Request<Incoming>| {
+        request.extensions_mut().insert(ConnectInfo(remote_addr));
+        router.clone().oneshot(request)
+    });
+
+    let conn = builder
+        .serve_connection_with_upgrades(TokioIo::new(io), service)
+        .into_owned();
+    let conn = graceful.watch(conn);
+
+    tokio::spawn(async move {
+        if let Err(e) = conn.await {
+            tracing::debug!("Connection from {} closed with error: {}", remote_addr, e);
+        }
+        drop(permit);
+    });
+}
+
+/// Serves `router` on `listener` until `shutdown` resolves, then waits for
+/// open connections to finish their in-flight requests. With `tls`, every
+/// connection is expected to start with a TLS handshake.
+pub async fn serve<F>(
+    listener: TcpListener,
+    router: Router,
+    config: ServerConfig,
+    tls: Option<Arc<TlsAcceptor>>,
+    shutdown: F,
+) where
+    F: Future<Output = ()>,
+{
+    let connections = config.max_connections.map(|n| Arc::new(Semaphore::new(n)));
+    let graceful = GracefulShutdown::new();
+
+    let mut builder = auto::Builder::new(TokioExecutor::new());
+    builder
+        .http2()
+        .timer(TokioTimer::new())
+        .keep_alive_interval(config.keep_alive());
+
+    // Handshakes run in their own tasks so a slow client can't hold up the
+    // accept loop; finished ones come back here to be served.
+    let (handshake_tx, mut handshakes) = mpsc::unbounded_channel();
+
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            (stream, remote_addr, permit) = accept_with_permit(&listener, &connections) => {
+                configure_stream(&stream, &config);
+
+                let Some(tls) = &tls else {
+                    serve_connection(&builder, &graceful, &router, (stream, remote_addr, permit));
+                    continue;
+                };
+
+                let (tls, handshake_tx) = (tls.clone(), handshake_tx.clone());
+                tokio::spawn(async move {
+                    match tls.accept(stream).await {
+                        Ok(stream) => {
+                            let _ = handshake_tx.send((stream, remote_addr, permit));
+                        }
+                        Err(e) => tracing::debug!("TLS handshake with {} failed: {}", remote_addr, e),
+                    }
+                });
+            }
+            Some(conn) = handshakes.recv() => serve_connection(&builder, &graceful, &router, conn),
+            _ = &mut shutdown => break,
+        }
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+}
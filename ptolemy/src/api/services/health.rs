@@ -0,0 +1,58 @@
+use crate::api::state::PtolemyState;
+use crate::generated::record_publisher::record_publisher_server::RecordPublisherServer;
+
+use opentelemetry_proto::tonic::collector::trace::v1::trace_service_server::TraceServiceServer;
+use std::time::Duration;
+use tonic::server::NamedService;
+use tonic_health::{
+    pb::health_server::{Health, HealthServer},
+    server::HealthReporter,
+    ServingStatus,
+};
+
+/// How often sink health is copied into the health service. Sink health
+/// itself only changes every `health_check_interval_secs`.
+const REPORT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Services whose health follows the sinks, along with the server as a
+/// whole (`""`).
+const SERVICES: [&str; 3] = [
+    "",
+    <RecordPublisherServer<super::RecordPublisherService> as NamedService>::NAME,
+    <TraceServiceServer<super::otlp::OtlpTraceService> as NamedService>::NAME,
+];
+
+/// Serving while the server is ready, as reported by `/readyz`.
+fn serving_status(state: &PtolemyState) -> ServingStatus {
+    match state.readiness().ready {
+        true => ServingStatus::Serving,
+        false => ServingStatus::NotServing,
+    }
+}
+
+async fn report_health(state: PtolemyState, mut reporter: HealthReporter) {
+    let mut reported = None;
+
+    loop {
+        let status = serving_status(&state);
+
+        // Reporting notifies watchers, so only report changes.
+        if reported != Some(status) {
+            tracing::info!("gRPC health status is now {}", status);
+            for service in SERVICES {
+                reporter.set_service_status(service, status).await;
+            }
+            reported = Some(status);
+        }
+
+        tokio::time::sleep(REPORT_INTERVAL).await;
+    }
+}
+
+/// The standard `grpc.health.v1.Health` service, reporting the server and
+/// its publishing services as not serving while the server isn't ready.
+pub fn health_service(state: PtolemyState) -> HealthServer<impl Health> {
+    let (reporter, service) = tonic_health::server::health_reporter();
+    tokio::spawn(report_health(state, reporter));
+    service
+}
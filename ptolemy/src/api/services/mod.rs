@@ -1,7 +1,65 @@
-use super::state::PtolemyState;
-use crate::generated::record_publisher;
+use super::{auth::Identity, error::ApiError, metrics, state::PtolemyState};
+use crate::generated::record_publisher::{self, Record};
+use crate::models::PublishedRecord;
+use axum::extract::ConnectInfo;
+use prost::Message;
+use std::net::{IpAddr, SocketAddr};
 use tonic::{Request, Response, Status};
 
+pub mod health;
+pub mod otlp;
+
+/// Checks the caller may publish `records` and is within its limits, then
+/// hands them to the sinks. Shared by every ingestion endpoint; `bytes` is
+/// the size of the request they came in.
+pub async fn publish_records(
+    state: &PtolemyState,
+    identity: &Identity,
+    client_ip: Option<IpAddr>,
+    mut records: Vec<Record>,
+    bytes: u64,
+) -> Result<(), ApiError> {
+    if !identity.can_write() {
+        return Err(ApiError::PermissionDenied);
+    }
+
+    state.rate_limiter.check(
+        &state.config.load().limits,
+        identity,
+        client_ip,
+        records.len() as u64,
+        bytes,
+    )?;
+
+    // Sinks drop records they can't convert, so they're dropped and counted
+    // here instead, once rather than once per sink.
+    records.retain(|record| match PublishedRecord::try_from(record.clone()) {
+        Ok(_) => true,
+        Err(e) => {
+            tracing::warn!("Dropping invalid record: {:?}", e);
+            metrics::record_rejected(&e);
+            false
+        }
+    });
+
+    metrics::records_received(&records);
+    tracing::Span::current().record("records", records.len());
+
+    // Records are attributed to the workspace of the key they were
+    // published with, whatever the client set.
+    if let Some(workspace_id) = identity.workspace_id() {
+        for record in &mut records {
+            record.workspace_id = Some(workspace_id.to_string());
+        }
+    }
+
+    for result in state.sink_registry.load_full().fanout(records).await {
+        result?;
+    }
+
+    Ok(())
+}
+
 #[derive(Debug)]
 pub struct RecordPublisherService {
     state: PtolemyState,
@@ -19,9 +77,25 @@ impl record_publisher::record_publisher_server::RecordPublisher for RecordPublis
         &self,
         request: Request<record_publisher::PublishRequest>,
     ) -> Result<Response<record_publisher::PublishResponse>, Status> {
-        let records = request.into_inner().records;
+        let identity = request
+            .extensions()
+            .get::<Identity>()
+            .cloned()
+            .ok_or(ApiError::PermissionDenied)?;
+        let client_ip = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let bytes = request.get_ref().encoded_len() as u64;
 
-        self.state.sink_registry.fanout(records).await;
+        publish_records(
+            &self.state,
+            &identity,
+            client_ip,
+            request.into_inner().records,
+            bytes,
+        )
+        .await?;
 
         let reply = record_publisher::PublishResponse {
             successful: true,
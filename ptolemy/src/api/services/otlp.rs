@@ -0,0 +1,436 @@
+use super::publish_records;
+use crate::api::{
+    auth::Identity, config::otlp::OtlpConfig, error::ApiError, metrics, state::PtolemyState,
+};
+use crate::error::ParseError;
+use crate::generated::record_publisher::{self, record::RecordData, Record};
+use crate::models::{Tier, JSON};
+
+use axum::extract::ConnectInfo;
+use opentelemetry_proto::tonic::{
+    collector::trace::v1::{
+        trace_service_server::TraceService, ExportTracePartialSuccess, ExportTraceServiceRequest,
+        ExportTraceServiceResponse,
+    },
+    common::v1::{any_value, AnyValue, KeyValue},
+    trace::v1::{status::StatusCode, Span},
+};
+use prost::Message;
+use serde_json::Value;
+use std::net::{IpAddr, SocketAddr};
+use tonic::{Request, Response, Status};
+use uuid::Uuid;
+
+/// Namespace of the ids of events made from spans. Ids are derived from the
+/// span, so a span exported twice becomes the same records.
+const SPAN_NAMESPACE: Uuid = Uuid::from_u128(0x6f1c_5d0e_8a3b_4c2f_9e7d_1b4a_2c6e_8f30);
+
+fn any_value_to_json(value: &AnyValue) -> Value {
+    match &value.value {
+        None => Value::Null,
+        Some(any_value::Value::StringValue(s)) => Value::from(s.clone()),
+        Some(any_value::Value::BoolValue(b)) => Value::from(*b),
+        Some(any_value::Value::IntValue(i)) => Value::from(*i),
+        Some(any_value::Value::DoubleValue(d)) => Value::from(*d),
+        Some(any_value::Value::ArrayValue(a)) => {
+            Value::Array(a.values.iter().map(any_value_to_json).collect())
+        }
+        Some(any_value::Value::KvlistValue(kv)) => Value::Object(
+            kv.values
+                .iter()
+                .map(|kv| (kv.key.clone(), attribute_value(kv)))
+                .collect(),
+        ),
+        Some(any_value::Value::BytesValue(b)) => Value::from(hex::encode(b)),
+    }
+}
+
+fn attribute_value(kv: &KeyValue) -> Value {
+    kv.value.as_ref().map_or(Value::Null, any_value_to_json)
+}
+
+fn attribute<'a>(attributes: &'a [KeyValue], key: &str) -> Option<&'a AnyValue> {
+    attributes
+        .iter()
+        .find(|kv| kv.key == key)
+        .and_then(|kv| kv.value.as_ref())
+}
+
+fn string_attribute(attributes: &[KeyValue], key: &str) -> Option<String> {
+    match attribute(attributes, key)?.value.as_ref()? {
+        any_value::Value::StringValue(s) => Some(s.clone()),
+        _ => None,
+    }
+}
+
+/// Whether `key` is one of `names`, or nested under one of them.
+fn matches_any(key: &str, names: &[String]) -> bool {
+    names.iter().any(|name| {
+        key.strip_prefix(name.as_str())
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+    })
+}
+
+fn span_event_id(trace_id: &[u8], span_id: &[u8]) -> Uuid {
+    Uuid::new_v5(&SPAN_NAMESPACE, &[trace_id, span_id].concat())
+}
+
+fn unix_seconds(nanos: u64) -> f32 {
+    (nanos as f64 / 1e9) as f32
+}
+
+fn span_tier(config: &OtlpConfig, span: &Span) -> Tier {
+    if let Some(tier) = string_attribute(&span.attributes, &config.tier_attribute)
+        .and_then(|t| Tier::try_from(t.to_uppercase()).ok())
+    {
+        return tier;
+    }
+
+    let kind_tier = config
+        .span_kind_attributes
+        .iter()
+        .filter_map(|key| string_attribute(&span.attributes, key))
+        .find_map(|kind| config.tiers.get(&kind));
+
+    match kind_tier {
+        Some(tier) => tier.clone(),
+        None if span.parent_span_id.is_empty() => config.root_tier.clone(),
+        None => config.default_tier.clone(),
+    }
+}
+
+/// The error type and content of a failed span, from its exception event if
+/// it has one.
+fn span_error(span: &Span) -> (Option<String>, Option<String>) {
+    let status = span.status.as_ref();
+    if status.is_none_or(|s| s.code() != StatusCode::Error) {
+        return (None, None);
+    }
+
+    let exception = span.events.iter().find(|e| e.name == "exception");
+    let exception_attribute =
+        |key: &str| exception.and_then(|e| string_attribute(&e.attributes, key));
+
+    let error_type = exception_attribute("exception.type").unwrap_or_else(|| "Error".to_string());
+    let error_content = exception_attribute("exception.stacktrace")
+        .or_else(|| exception_attribute("exception.message"))
+        .or_else(|| status.map(|s| s.message.clone()).filter(|m| !m.is_empty()));
+
+    (Some(error_type), error_content)
+}
+
+/// Maps a span to an event and runtime record, plus an input, output or
+/// metadata record for each of its attributes. Events take the trace as
+/// their subject, and the parent span's event as their parent.
+pub fn span_to_records(
+    config: &OtlpConfig,
+    resource: &[KeyValue],
+    span: &Span,
+) -> Result<Vec<Record>, ParseError> {
+    let subject_id = Uuid::from_slice(&span.trace_id).map_err(|_| ParseError::InvalidUuid)?;
+    if span.span_id.len() != 8 {
+        return Err(ParseError::InvalidUuid);
+    }
+    if span.start_time_unix_nano == 0 || span.end_time_unix_nano < span.start_time_unix_nano {
+        return Err(ParseError::BadTimestamp);
+    }
+
+    let event_id = span_event_id(&span.trace_id, &span.span_id);
+    let parent_id = match span.parent_span_id.is_empty() {
+        true => subject_id,
+        false => span_event_id(&span.trace_id, &span.parent_span_id),
+    };
+    let tier: record_publisher::Tier = span_tier(config, span).into();
+    let record_id = |kind: &str, name: &str| {
+        Uuid::new_v5(&event_id, format!("{}:{}", kind, name).as_bytes()).to_string()
+    };
+
+    let mut parameters = serde_json::Map::new();
+    let mut records = Vec::new();
+
+    for kv in &span.attributes {
+        let value = attribute_value(kv);
+        if value.is_null() {
+            continue;
+        }
+
+        let (subject_id, event_id, id) = (
+            subject_id.to_string(),
+            event_id.to_string(),
+            record_id("attribute", &kv.key),
+        );
+        let field_name = kv.key.clone();
+
+        let record_data = if matches_any(&kv.key, &config.input_attributes) {
+            RecordData::Input(record_publisher::InputRecord {
+                tier: tier.into(),
+                subject_id,
+                event_id,
+                id,
+                field_name,
+                field_value: Some(JSON(value).into()),
+            })
+        } else if matches_any(&kv.key, &config.output_attributes) {
+            RecordData::Output(record_publisher::OutputRecord {
+                tier: tier.into(),
+                subject_id,
+                event_id,
+                id,
+                field_name,
+                field_value: Some(JSON(value).into()),
+            })
+        } else if matches_any(&kv.key, &config.parameter_attributes) {
+            parameters.insert(field_name, value);
+            continue;
+        } else {
+            RecordData::Metadata(record_publisher::MetadataRecord {
+                tier: tier.into(),
+                subject_id,
+                event_id,
+                id,
+                field_name,
+                field_value: match value {
+                    Value::String(s) => s,
+                    value => value.to_string(),
+                },
+            })
+        };
+
+        records.push(record_data);
+    }
+
+    let (error_type, error_content) = span_error(span);
+
+    let event = RecordData::Event(record_publisher::EventRecord {
+        tier: tier.into(),
+        subject_id: subject_id.to_string(),
+        parent_id: parent_id.to_string(),
+        id: event_id.to_string(),
+        name: span.name.clone(),
+        parameters: (!parameters.is_empty()).then(|| JSON(Value::Object(parameters)).into()),
+        version: string_attribute(resource, "service.version"),
+        environment: string_attribute(resource, "deployment.environment.name")
+            .or_else(|| string_attribute(resource, "deployment.environment")),
+    });
+    let runtime = RecordData::Runtime(record_publisher::RuntimeRecord {
+        tier: tier.into(),
+        subject_id: subject_id.to_string(),
+        event_id: event_id.to_string(),
+        id: record_id("runtime", ""),
+        start_time: unix_seconds(span.start_time_unix_nano),
+        end_time: unix_seconds(span.end_time_unix_nano),
+        error_type,
+        error_content,
+        start_time_us: Some((span.start_time_unix_nano / 1_000) as i64),
+        end_time_us: Some((span.end_time_unix_nano / 1_000) as i64),
+    });
+
+    Ok([event, runtime]
+        .into_iter()
+        .chain(records)
+        .map(|record_data| Record {
+            workspace_id: None,
+            record_data: Some(record_data),
+        })
+        .collect())
+}
+
+/// Publishes the records the spans in an export map to. Spans that can't be
+/// mapped are reported back as rejected rather than failing the export.
+pub async fn export_traces(
+    state: &PtolemyState,
+    identity: &Identity,
+    client_ip: Option<IpAddr>,
+    request: ExportTraceServiceRequest,
+    bytes: u64,
+) -> Result<ExportTraceServiceResponse, ApiError> {
+    let config = state.config.load();
+    if !config.otlp.enabled {
+        return Err(ApiError::NotFoundError);
+    }
+
+    let mut records = Vec::new();
+    let mut rejected = (0, None);
+
+    for resource_spans in &request.resource_spans {
+        let resource = resource_spans
+            .resource
+            .as_ref()
+            .map_or(&[][..], |r| &r.attributes);
+
+        for span in resource_spans
+            .scope_spans
+            .iter()
+            .flat_map(|scope_spans| &scope_spans.spans)
+        {
+            match span_to_records(&config.otlp, resource, span) {
+                Ok(span_records) => records.extend(span_records),
+                Err(e) => {
+                    metrics::record_rejected(&e);
+                    rejected.0 += 1;
+                    rejected.1.get_or_insert_with(|| {
+                        format!("span {}: {:?}", hex::encode(&span.span_id), e)
+                    });
+                }
+            }
+        }
+    }
+
+    if !records.is_empty() {
+        publish_records(state, identity, client_ip, records, bytes).await?;
+    }
+
+    Ok(ExportTraceServiceResponse {
+        partial_success: rejected.1.map(|error_message| ExportTracePartialSuccess {
+            rejected_spans: rejected.0,
+            error_message,
+        }),
+    })
+}
+
+/// The OTLP trace service, so OpenTelemetry exporters can publish to the
+/// server directly.
+#[derive(Debug)]
+pub struct OtlpTraceService {
+    state: PtolemyState,
+}
+
+impl OtlpTraceService {
+    pub fn new(state: PtolemyState) -> Self {
+        Self { state }
+    }
+}
+
+#[tonic::async_trait]
+impl TraceService for OtlpTraceService {
+    async fn export(
+        &self,
+        request: Request<ExportTraceServiceRequest>,
+    ) -> Result<Response<ExportTraceServiceResponse>, Status> {
+        let identity = request
+            .extensions()
+            .get::<Identity>()
+            .cloned()
+            .ok_or(ApiError::PermissionDenied)?;
+        let client_ip = request
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+        let bytes = request.get_ref().encoded_len() as u64;
+
+        let response = export_traces(
+            &self.state,
+            &identity,
+            client_ip,
+            request.into_inner(),
+            bytes,
+        )
+        .await?;
+
+        Ok(Response::new(response))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Record as PtolemyRecord;
+    use opentelemetry_proto::tonic::trace::v1::{span, Status as SpanStatus};
+
+    fn kv(key: &str, value: any_value::Value) -> KeyValue {
+        KeyValue {
+            key: key.to_string(),
+            value: Some(AnyValue { value: Some(value) }),
+        }
+    }
+
+    fn string(s: &str) -> any_value::Value {
+        any_value::Value::StringValue(s.to_string())
+    }
+
+    #[test]
+    fn test_span_to_records() {
+        let config = OtlpConfig::default();
+        let trace_id = vec![7; 16];
+        let span = Span {
+            trace_id: trace_id.clone(),
+            span_id: vec![2; 8],
+            parent_span_id: vec![1; 8],
+            name: "completion".to_string(),
+            start_time_unix_nano: 1_733_933_557_000_000_000,
+            end_time_unix_nano: 1_733_933_558_500_000_000,
+            attributes: vec![
+                kv("gen_ai.operation.name", string("chat")),
+                kv("gen_ai.prompt.0.content", string("Hi")),
+                kv("gen_ai.completion.0.content", string("Hello!")),
+                kv(
+                    "gen_ai.request.temperature",
+                    any_value::Value::DoubleValue(0.5),
+                ),
+                kv("gen_ai.usage.input_tokens", any_value::Value::IntValue(3)),
+            ],
+            events: vec![span::Event {
+                name: "exception".to_string(),
+                attributes: vec![kv("exception.type", string("Timeout"))],
+                ..Default::default()
+            }],
+            status: Some(SpanStatus {
+                code: StatusCode::Error.into(),
+                message: "timed out".to_string(),
+            }),
+            ..Default::default()
+        };
+        let resource = [kv("service.version", string("1.2.0"))];
+
+        let records: Vec<PtolemyRecord> = span_to_records(&config, &resource, &span)
+            .unwrap()
+            .into_iter()
+            .map(|r| r.try_into().unwrap())
+            .collect();
+        assert_eq!(records.len(), 6);
+
+        let PtolemyRecord::Event(event) = &records[0] else {
+            panic!("expected an event, got {:?}", records[0]);
+        };
+        assert_eq!(event.tier, Tier::Component);
+        assert_eq!(
+            event.subject_id.as_uuid(),
+            Uuid::from_slice(&trace_id).unwrap()
+        );
+        assert_eq!(event.parent_id.as_uuid(), span_event_id(&trace_id, &[1; 8]));
+        assert_eq!(event.version.as_deref(), Some("1.2.0"));
+        assert!(event.parameters.as_ref().unwrap().0["gen_ai.request.temperature"] == 0.5);
+
+        assert!(matches!(
+            &records[1],
+            PtolemyRecord::Runtime(r) if r.error_type.as_deref() == Some("Timeout")
+                && r.error_content.as_deref() == Some("timed out")
+        ));
+        assert!(matches!(
+            &records[3],
+            PtolemyRecord::Input(i) if i.field_value_str.as_deref() == Some("Hi")
+        ));
+        assert!(matches!(
+            &records[4],
+            PtolemyRecord::Output(o) if o.field_name == "gen_ai.completion.0.content"
+        ));
+        assert!(matches!(
+            &records[5],
+            PtolemyRecord::Metadata(m) if m.field_value == "3"
+        ));
+
+        // Root spans are the system, and re-exporting gives the same ids.
+        let root = Span {
+            parent_span_id: Vec::new(),
+            attributes: Vec::new(),
+            ..span.clone()
+        };
+        let records = span_to_records(&config, &[], &root).unwrap();
+        assert!(matches!(
+            &records[0].record_data,
+            Some(RecordData::Event(e)) if e.tier() == record_publisher::Tier::System
+                && e.parent_id == e.subject_id
+        ));
+        assert_eq!(records, span_to_records(&config, &[], &root).unwrap());
+    }
+}
@@ -0,0 +1,143 @@
+use super::super::{
+    config::{sinks::SinkConfig, PtolemyConfig},
+    error::ApiError,
+};
+use super::{sink::Sink, KafkaSink, StdoutSink};
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Builds a sink from its entry in the `sinks` list.
+pub type SinkFactory = fn(&SinkConfig, &PtolemyConfig) -> Result<Arc<dyn Sink>, ApiError>;
+
+type DynSinkFactory =
+    Arc<dyn Fn(&SinkConfig, &PtolemyConfig) -> Result<Arc<dyn Sink>, ApiError> + Send + Sync>;
+
+/// A sink type submitted from any linked crate with
+/// `inventory::submit! { SinkPlugin::new("my_sink", my_factory) }`.
+pub struct SinkPlugin {
+    pub sink_type: &'static str,
+    pub factory: SinkFactory,
+}
+
+impl SinkPlugin {
+    pub const fn new(sink_type: &'static str, factory: SinkFactory) -> Self {
+        Self { sink_type, factory }
+    }
+}
+
+inventory::collect!(SinkPlugin);
+
+/// Sink factories keyed by the `type` they are configured with.
+#[derive(Clone)]
+pub struct SinkFactories {
+    factories: HashMap<String, DynSinkFactory>,
+}
+
+impl std::fmt::Debug for SinkFactories {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set().entries(self.factories.keys()).finish()
+    }
+}
+
+impl Default for SinkFactories {
+    /// The built-in sinks plus every submitted `SinkPlugin`.
+    fn default() -> Self {
+        let mut factories = Self::builtin();
+
+        for plugin in inventory::iter::<SinkPlugin> {
+            factories.register(plugin.sink_type, plugin.factory);
+        }
+
+        factories
+    }
+}
+
+impl SinkFactories {
+    pub fn builtin() -> Self {
+        let mut factories = Self {
+            factories: HashMap::new(),
+        };
+
+        factories
+            .register("stdout", |_, _| Ok(Arc::new(StdoutSink)))
+            .register("kafka", |sink, config| {
+                Ok(Arc::new(KafkaSink::new(
+                    &sink.settings()?,
+                    config.sink_timeout(),
+                )?))
+            });
+
+        factories
+    }
+
+    /// Adds a sink type, replacing any factory already registered for it.
+    pub fn register<F>(&mut self, sink_type: impl Into<String>, factory: F) -> &mut Self
+    where
+        F: Fn(&SinkConfig, &PtolemyConfig) -> Result<Arc<dyn Sink>, ApiError>
+            + Send
+            + Sync
+            + 'static,
+    {
+        let sink_type = sink_type.into();
+        if self
+            .factories
+            .insert(sink_type.clone(), Arc::new(factory))
+            .is_some()
+        {
+            tracing::warn!("Replacing factory for sink type {}", sink_type);
+        }
+        self
+    }
+
+    pub fn build(
+        &self,
+        sink: &SinkConfig,
+        config: &PtolemyConfig,
+    ) -> Result<Arc<dyn Sink>, ApiError> {
+        let factory = self.factories.get(&sink.sink_type).ok_or_else(|| {
+            tracing::error!("Unknown sink type: {}", sink.sink_type);
+            ApiError::ConfigError
+        })?;
+
+        factory(sink, config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sink_config(value: serde_json::Value) -> SinkConfig {
+        serde_json::from_value(value).unwrap()
+    }
+
+    #[test]
+    fn test_builds_registered_types() {
+        let factories = SinkFactories::default();
+        let config = PtolemyConfig::default();
+
+        assert!(factories
+            .build(&sink_config(json!({"type": "stdout"})), &config)
+            .is_ok());
+        assert!(factories
+            .build(&sink_config(json!({"type": "nonexistent"})), &config)
+            .is_err());
+    }
+
+    #[test]
+    fn test_settings_exclude_type_and_routing() {
+        let sink = sink_config(json!({
+            "type": "custom",
+            "name": "webhook",
+            "endpoint": "http://localhost",
+            "routing": {"include": {"record_types": ["feedback"]}},
+        }));
+
+        assert_eq!(sink.name(), "webhook");
+        assert_eq!(sink.settings.len(), 1);
+        assert_eq!(sink.settings["endpoint"], "http://localhost");
+        assert!(!sink.routing.is_empty());
+    }
+}
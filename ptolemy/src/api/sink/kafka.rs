@@ -1,37 +1,28 @@
 use crate::generated::record_publisher::Record;
 use rdkafka::{
-    producer::{FutureProducer, FutureRecord},
+    producer::{FutureProducer, FutureRecord, Producer},
     ClientConfig,
 };
 
 use super::{
-    super::{config::PtolemyConfig, error::ApiError},
+    super::{config::kafka::KafkaConfig, error::ApiError},
     sink::Sink,
 };
 
 pub struct KafkaSink {
     producer: FutureProducer,
+    flush_timeout: std::time::Duration,
 }
 
-#[async_trait::async_trait]
-impl Sink for KafkaSink {
-    fn name(&self) -> &'static str {
-        "kafka"
-    }
-
-    fn from_config(config: &PtolemyConfig) -> Result<Self, ApiError>
-    where
-        Self: Sized,
-    {
-        let conf = config.kafka.as_ref().ok_or(ApiError::ConfigError)?;
-
+impl KafkaSink {
+    pub fn new(conf: &KafkaConfig, flush_timeout: std::time::Duration) -> Result<Self, ApiError> {
         let mut client = ClientConfig::new();
         client.set("bootstrap.servers", &conf.bootstrap_servers);
 
         // ---- Optional settings ----
 
         if let Some(ref qm) = conf.queue_buffering_max_ms {
-            client.set("queue.buffering.max.ms", &qm.to_string());
+            client.set("queue.buffering.max.ms", qm.to_string());
         }
 
         if let Some(ref proto) = conf.security_protocol {
@@ -44,16 +35,16 @@ impl Sink for KafkaSink {
             client.set("acks", acks);
         }
         if let Some(idem) = conf.enable_idempotence {
-            client.set("enable.idempotence", &idem.to_string());
+            client.set("enable.idempotence", idem.to_string());
         }
         if let Some(timeout) = conf.message_timeout_ms {
-            client.set("message.timeout.ms", &timeout.to_string());
+            client.set("message.timeout.ms", timeout.to_string());
         }
         if let Some(retries) = conf.retries {
-            client.set("retries", &retries.to_string());
+            client.set("retries", retries.to_string());
         }
         if let Some(backoff) = conf.retry_backoff_ms {
-            client.set("retry.backoff.ms", &backoff.to_string());
+            client.set("retry.backoff.ms", backoff.to_string());
         }
         if let Some(ref comp) = conf.compression_type {
             client.set("compression.type", comp);
@@ -62,61 +53,124 @@ impl Sink for KafkaSink {
         // ---- Create producer ----
         client
             .create::<FutureProducer>()
-            .map(|producer| KafkaSink { producer })
+            .map(|producer| KafkaSink {
+                producer,
+                flush_timeout,
+            })
             .map_err(|err| {
                 tracing::error!("Kafka producer creation failed: {}", err);
                 ApiError::ConnectionError
             })
     }
+}
 
+#[async_trait::async_trait]
+impl Sink for KafkaSink {
     async fn send_batch(&self, records: Vec<Record>) -> Result<(), ApiError> {
-        let recs: Vec<crate::models::Record> = records
+        let failed = self
+            .send_each(records)
+            .await
+            .iter()
+            .filter(|r| r.is_err())
+            .count();
+
+        if failed > 0 {
+            tracing::error!("Failed to produce {} records to Kafka.", failed);
+            return Err(ApiError::ConnectionError);
+        }
+
+        Ok(())
+    }
+
+    async fn send_each(&self, records: Vec<Record>) -> Vec<Result<(), ApiError>> {
+        // Records that can't be converted or serialized are dropped rather
+        // than failed, since redelivering them would never succeed.
+        let messages: Vec<Option<(String, String, Option<String>)>> = records
             .into_iter()
-            .filter_map(|r| match r.try_into() {
-                Ok(r) => Some(r),
-                Err(e) => {
-                    tracing::error!({"Invalid record: {:?}", e});
-                    None
+            .map(|r| {
+                let rec: crate::models::PublishedRecord = match r.try_into() {
+                    Ok(r) => r,
+                    Err(e) => {
+                        tracing::error!({"Invalid record: {:?}", e});
+                        return None;
+                    }
+                };
+                let topic = format!("ptolemy.{}", String::from(rec.record.record_type()));
+                match serde_json::to_string(&rec) {
+                    Ok(payload) => Some((topic, payload, rec.workspace_id)),
+                    Err(e) => {
+                        tracing::error!("Error serializing message: {:?}", e);
+                        None
+                    }
                 }
             })
             .collect();
 
-        for rec in recs {
-            let record_type = match &rec {
-                crate::models::Record::Event(_) => "event",
-                crate::models::Record::Runtime(_) => "runtime",
-                crate::models::Record::Input(_) => "input",
-                crate::models::Record::Output(_) => "output",
-                crate::models::Record::Feedback(_) => "feedback",
-                crate::models::Record::Metadata(_) => "metadata",
+        // Sent concurrently so the producer can batch them, and reported one
+        // by one so a single failure doesn't redeliver the rest.
+        let sends = messages.iter().map(|message| async move {
+            let Some((topic, payload, key)) = message else {
+                return Ok(());
             };
 
-            let topic = format!("ptolemy.{}", record_type);
-
-            let serialized_record = match serde_json::to_string(&rec) {
-                Ok(s) => s,
-                Err(e) => {
-                    tracing::error!("Error serializing message: {:?}", e);
-                    continue;
-                }
-            };
+            // Keyed by workspace so each workspace's records stay on one
+            // partition.
+            let mut message = FutureRecord::<str, str>::to(topic).payload(payload);
+            if let Some(workspace_id) = key {
+                message = message.key(workspace_id.as_str());
+            }
 
             match self
                 .producer
-                .send(
-                    FutureRecord::to(&topic)
-                        .key(&())
-                        .payload(&serialized_record),
-                    std::time::Duration::from_secs(0),
-                )
+                .send(message, std::time::Duration::from_secs(0))
                 .await
             {
-                Ok(_) => tracing::debug!("Successfully produced message to Kafka."),
-                Err(e) => tracing::error!("Error producing message to Kafka: {:?}", e.0),
+                Ok(_) => {
+                    tracing::debug!("Successfully produced message to Kafka.");
+                    Ok(())
+                }
+                Err(e) => {
+                    tracing::error!("Error producing message to Kafka: {:?}", e.0);
+                    Err(ApiError::ConnectionError)
+                }
             }
-        }
+        });
 
-        Ok(())
+        futures::future::join_all(sends).await
+    }
+
+    async fn flush(&self) -> Result<(), ApiError> {
+        let producer = self.producer.clone();
+        let timeout = self.flush_timeout;
+
+        // `flush` blocks until the producer queue is empty or the timeout hits.
+        tokio::task::spawn_blocking(move || producer.flush(timeout))
+            .await
+            .map_err(|e| {
+                tracing::error!("Kafka flush task failed: {}", e);
+                ApiError::InternalError
+            })?
+            .map_err(|e| {
+                tracing::error!("Failed to flush Kafka producer: {}", e);
+                ApiError::ConnectionError
+            })
+    }
+
+    async fn health_check(&self) -> Result<(), ApiError> {
+        let producer = self.producer.clone();
+        let timeout = self.flush_timeout;
+
+        tokio::task::spawn_blocking(move || producer.client().fetch_metadata(None, timeout))
+            .await
+            .map_err(|e| {
+                tracing::error!("Kafka health check task failed: {}", e);
+                ApiError::InternalError
+            })?
+            .map(|_| ())
+            .map_err(|e| {
+                tracing::debug!("Kafka metadata request failed: {}", e);
+                ApiError::ConnectionError
+            })
     }
 }
 
@@ -1,25 +1,41 @@
+pub mod factory;
 pub mod kafka;
+pub mod routing;
 pub mod sink;
 pub mod stdout;
 
+pub use factory::{SinkFactories, SinkFactory, SinkPlugin};
 pub use kafka::KafkaSink;
 pub use sink::Sink;
 pub use stdout::StdoutSink;
 
 use super::config::PtolemyConfig;
 use super::error::ApiError;
+use super::spool::Spool;
 
 pub fn configure_sink_registry(config: &PtolemyConfig) -> Result<sink::SinkRegistry, ApiError> {
-    let mut registry = sink::SinkRegistry::new();
+    configure_sink_registry_with(config, &SinkFactories::default())
+}
+
+/// Builds a registry holding every sink in `config`, using `factories` to
+/// create them by type.
+pub fn configure_sink_registry_with(
+    config: &PtolemyConfig,
+    factories: &SinkFactories,
+) -> Result<sink::SinkRegistry, ApiError> {
+    let spool = config.spool.clone().map(Spool::open).transpose()?;
+    let mut registry =
+        sink::SinkRegistry::new(config.writer_config(), config.sink_timeout(), spool);
 
-    if let Some(_) = &config.stdout {
-        registry.register(StdoutSink::from_config(config)?);
-        tracing::debug!("Registered StdoutSink.");
+    if let Some(overflow) = &config.overflow {
+        registry = registry.with_overflow(overflow.clone());
     }
 
-    if let Some(_) = &config.kafka {
-        registry.register(KafkaSink::from_config(config)?);
-        tracing::debug!("Registered KafkaSink.");
+    for sink_config in config.sink_configs()? {
+        let sink = factories.build(&sink_config, config)?;
+        let name = sink_config.name().to_string();
+        registry.register_configured(sink, sink_config)?;
+        tracing::debug!("Registered {} sink.", name);
     }
 
     tracing::debug!("Successfullly configured all sinks.");
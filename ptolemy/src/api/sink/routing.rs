@@ -0,0 +1,348 @@
+use crate::generated::record_publisher::{record::RecordData, Record};
+use crate::models::{RecordType, Tier};
+
+use super::super::config::routing::{RecordFilter, RoutingConfig};
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+#[derive(Debug, Clone)]
+struct EventContext {
+    name: String,
+    environment: Option<String>,
+}
+
+/// Remembers the name and environment of recently seen events so records
+/// published after their event can still be routed by them.
+#[derive(Debug)]
+struct EventCache {
+    capacity: usize,
+    events: HashMap<String, EventContext>,
+    order: VecDeque<String>,
+}
+
+impl EventCache {
+    fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    fn insert(&mut self, id: &str, context: EventContext) {
+        if self.events.insert(id.to_string(), context).is_none() {
+            self.order.push_back(id.to_string());
+        }
+
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.events.remove(&oldest);
+            }
+        }
+    }
+
+    fn get(&self, id: &str) -> Option<&EventContext> {
+        self.events.get(id)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RecordAttributes {
+    pub record_type: RecordType,
+    pub tier: Option<Tier>,
+    pub event_name: Option<String>,
+    pub environment: Option<String>,
+    pub field_name: Option<String>,
+    pub workspace_id: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct RecordRouter {
+    events: Mutex<EventCache>,
+}
+
+impl Default for RecordRouter {
+    fn default() -> Self {
+        Self::new(100_000)
+    }
+}
+
+impl RecordRouter {
+    pub fn new(event_cache_capacity: usize) -> Self {
+        Self {
+            events: Mutex::new(EventCache::new(event_cache_capacity)),
+        }
+    }
+
+    /// Extracts the routable attributes of each record. Events in the batch
+    /// are recorded first so their children in the same batch can use them.
+    pub fn attributes(&self, records: &[Record]) -> Vec<Option<RecordAttributes>> {
+        let mut events = self.events.lock().unwrap();
+
+        for record in records {
+            if let Some(RecordData::Event(e)) = &record.record_data {
+                events.insert(
+                    &e.id,
+                    EventContext {
+                        name: e.name.clone(),
+                        environment: e.environment.clone(),
+                    },
+                );
+            }
+        }
+
+        records
+            .iter()
+            .map(|record| {
+                let (record_type, tier, event_id, field_name) = match record.record_data.as_ref()? {
+                    RecordData::Event(e) => (RecordType::Event, e.tier(), &e.id, None),
+                    RecordData::Runtime(r) => (RecordType::Runtime, r.tier(), &r.event_id, None),
+                    RecordData::Input(i) => (
+                        RecordType::Input,
+                        i.tier(),
+                        &i.event_id,
+                        Some(&i.field_name),
+                    ),
+                    RecordData::Output(o) => (
+                        RecordType::Output,
+                        o.tier(),
+                        &o.event_id,
+                        Some(&o.field_name),
+                    ),
+                    RecordData::Feedback(f) => (
+                        RecordType::Feedback,
+                        f.tier(),
+                        &f.event_id,
+                        Some(&f.field_name),
+                    ),
+                    RecordData::Metadata(m) => (
+                        RecordType::Metadata,
+                        m.tier(),
+                        &m.event_id,
+                        Some(&m.field_name),
+                    ),
+                };
+
+                let event = events.get(event_id);
+
+                Some(RecordAttributes {
+                    record_type,
+                    tier: tier.try_into().ok(),
+                    event_name: event.map(|e| e.name.clone()),
+                    environment: event.and_then(|e| e.environment.clone()),
+                    field_name: field_name.cloned(),
+                    workspace_id: record.workspace_id.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl RoutingConfig {
+    pub fn matches(&self, attributes: &RecordAttributes) -> bool {
+        let included = self
+            .include
+            .as_ref()
+            .is_none_or(|filter| filter.matches(attributes, true));
+
+        let excluded = self
+            .exclude
+            .as_ref()
+            .is_some_and(|filter| filter.matches(attributes, false));
+
+        included && !excluded
+    }
+}
+
+impl RecordFilter {
+    /// `unknown_event` is whether `environments` and `event_names` match
+    /// records whose event isn't in the cache.
+    pub fn matches(&self, attributes: &RecordAttributes, unknown_event: bool) -> bool {
+        fn any_of<T>(list: &[T], f: impl Fn(&T) -> bool) -> bool {
+            list.is_empty() || list.iter().any(f)
+        }
+
+        // Events are always cached before their own attributes are taken.
+        let known = attributes.event_name.is_some();
+
+        any_of(&self.record_types, |t| *t == attributes.record_type)
+            && any_of(&self.tiers, |t| attributes.tier.as_ref() == Some(t))
+            && any_of(&self.environments, |env| match known {
+                true => attributes.environment.as_deref() == Some(env.as_str()),
+                false => unknown_event,
+            })
+            && any_of(&self.event_names, |pattern| match &attributes.event_name {
+                Some(name) => glob_match(pattern, name),
+                None => unknown_event,
+            })
+            && any_of(&self.field_names, |pattern| {
+                attributes
+                    .field_name
+                    .as_deref()
+                    .is_some_and(|name| glob_match(pattern, name))
+            })
+            && any_of(&self.workspaces, |id| {
+                attributes.workspace_id.as_deref() == Some(id.as_str())
+            })
+    }
+}
+
+/// Matches `text` against `pattern`, where `*` matches any run of characters.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+
+    let (mut p, mut t) = (0, 0);
+    let mut backtrack: Option<(usize, usize)> = None;
+
+    while t < text.len() {
+        if p < pattern.len() && pattern[p] == '*' {
+            backtrack = Some((p, t));
+            p += 1;
+        } else if p < pattern.len() && pattern[p] == text[t] {
+            p += 1;
+            t += 1;
+        } else if let Some((star_p, star_t)) = backtrack {
+            p = star_p + 1;
+            t = star_t + 1;
+            backtrack = Some((star_p, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generated::record_publisher::{self, EventRecord, FeedbackRecord};
+
+    fn event(id: &str, name: &str, environment: &str) -> Record {
+        Record {
+            workspace_id: None,
+            record_data: Some(RecordData::Event(EventRecord {
+                tier: record_publisher::Tier::System.into(),
+                id: id.to_string(),
+                name: name.to_string(),
+                environment: Some(environment.to_string()),
+                ..Default::default()
+            })),
+        }
+    }
+
+    fn feedback(event_id: &str, field_name: &str) -> Record {
+        Record {
+            workspace_id: None,
+            record_data: Some(RecordData::Feedback(FeedbackRecord {
+                tier: record_publisher::Tier::System.into(),
+                event_id: event_id.to_string(),
+                field_name: field_name.to_string(),
+                ..Default::default()
+            })),
+        }
+    }
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match("*", "anything"));
+        assert!(glob_match("llm.*", "llm.completion"));
+        assert!(glob_match("*.completion", "llm.completion"));
+        assert!(glob_match("l*m*n", "llm.completion"));
+        assert!(!glob_match("llm.*", "retriever.query"));
+        assert!(!glob_match("llm", "llm.completion"));
+    }
+
+    #[test]
+    fn test_children_inherit_event_environment() {
+        let router = RecordRouter::default();
+        let routing = RoutingConfig {
+            include: Some(RecordFilter {
+                environments: vec!["PROD".to_string()],
+                ..Default::default()
+            }),
+            exclude: None,
+        };
+
+        router.attributes(&[event("prod-event", "MyComponent", "PROD")]);
+        let attributes = router.attributes(&[
+            feedback("prod-event", "rating"),
+            event("dev-event", "MyComponent", "DEV"),
+            feedback("dev-event", "rating"),
+            feedback("unknown-event", "rating"),
+        ]);
+
+        let routed: Vec<bool> = attributes
+            .iter()
+            .map(|a| routing.matches(a.as_ref().unwrap()))
+            .collect();
+
+        // Children of events that aren't cached are let through rather than
+        // dropped.
+        assert_eq!(routed, vec![true, false, false, true]);
+
+        let routing = RoutingConfig {
+            include: None,
+            exclude: routing.include,
+        };
+        assert!(routing.matches(attributes[3].as_ref().unwrap()));
+    }
+
+    #[test]
+    fn test_include_and_exclude() {
+        let router = RecordRouter::default();
+        let routing = RoutingConfig {
+            include: Some(RecordFilter {
+                record_types: vec![RecordType::Feedback],
+                ..Default::default()
+            }),
+            exclude: Some(RecordFilter {
+                field_names: vec!["internal_*".to_string()],
+                ..Default::default()
+            }),
+        };
+
+        let attributes = router.attributes(&[
+            event("event", "MyComponent", "PROD"),
+            feedback("event", "rating"),
+            feedback("event", "internal_score"),
+        ]);
+
+        let routed: Vec<bool> = attributes
+            .iter()
+            .map(|a| routing.matches(a.as_ref().unwrap()))
+            .collect();
+
+        assert_eq!(routed, vec![false, true, false]);
+    }
+
+    #[test]
+    fn test_workspaces() {
+        let router = RecordRouter::default();
+        let routing = RoutingConfig {
+            include: Some(RecordFilter {
+                workspaces: vec!["team-a".to_string()],
+                ..Default::default()
+            }),
+            exclude: None,
+        };
+
+        let mut records = vec![
+            feedback("event", "rating"),
+            feedback("event", "rating"),
+            feedback("event", "rating"),
+        ];
+        records[0].workspace_id = Some("team-a".to_string());
+        records[1].workspace_id = Some("team-b".to_string());
+
+        let routed: Vec<bool> = router
+            .attributes(&records)
+            .iter()
+            .map(|a| routing.matches(a.as_ref().unwrap()))
+            .collect();
+
+        assert_eq!(routed, vec![true, false, false]);
+    }
+}
@@ -1,53 +1,1174 @@
 use crate::generated::record_publisher::Record;
+use crate::writer::{OverflowConfig, Spill, Writer, WriterConfig, WriterError};
 
-use super::super::{config::PtolemyConfig, error::ApiError};
+use super::super::metrics;
+use super::super::spool::Spool;
+use super::super::{
+    config::{routing::RoutingConfig, sinks::SinkConfig, PtolemyConfig},
+    error::ApiError,
+};
+use super::factory::SinkFactories;
+use super::routing::RecordRouter;
 
+use prost::Message;
+use serde::Serialize;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::{sync::RwLock, task::JoinHandle};
 
 #[async_trait::async_trait]
 pub trait Sink: std::fmt::Debug + Send + Sync {
-    fn from_config(config: &PtolemyConfig) -> Result<Self, ApiError>
-    where
-        Self: Sized;
+    async fn send_batch(&self, messages: Vec<Record>) -> Result<(), ApiError>;
 
-    fn name(&self) -> &'static str
-    where
-        Self: Sized;
+    /// Sends `messages`, returning the outcome of each in order. Sinks that
+    /// can tell which records failed override this so only those are
+    /// redelivered.
+    async fn send_each(&self, messages: Vec<Record>) -> Vec<Result<(), ApiError>> {
+        let count = messages.len();
+        let result = self.send_batch(messages).await;
+        vec![result; count]
+    }
 
-    async fn send_batch(&self, messages: Vec<Record>) -> Result<(), ApiError>;
+    /// Called once before the sink receives any records.
+    async fn start(&self) -> Result<(), ApiError> {
+        Ok(())
+    }
+
+    /// Pushes out anything the sink is holding on to internally.
+    async fn flush(&self) -> Result<(), ApiError> {
+        Ok(())
+    }
+
+    /// Checks whether the sink can currently deliver records.
+    async fn health_check(&self) -> Result<(), ApiError> {
+        Ok(())
+    }
+
+    /// Called once after the sink's writer has drained and the sink has been
+    /// flushed. No records are sent after this.
+    async fn shutdown(&self) -> Result<(), ApiError> {
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "status", content = "error", rename_all = "lowercase")]
+pub enum SinkHealth {
+    Unknown,
+    Healthy,
+    Unhealthy(String),
+}
+
+impl From<Result<(), ApiError>> for SinkHealth {
+    fn from(value: Result<(), ApiError>) -> Self {
+        match value {
+            Ok(_) => SinkHealth::Healthy,
+            Err(e) => SinkHealth::Unhealthy(e.to_string()),
+        }
+    }
 }
 
+type HealthMap = Arc<std::sync::RwLock<HashMap<String, SinkHealth>>>;
+
+/// The sink a writer delivers to. Shared between registries across a reload
+/// so a sink can be replaced without replacing its writer.
+type SinkSlot = Arc<std::sync::RwLock<Arc<dyn Sink>>>;
+
+/// A record queued for a sink, with its spool sequence number when spooling
+/// is enabled.
+#[derive(Debug)]
+pub struct Envelope {
+    seq: Option<u64>,
+    record: Record,
+}
+
+impl Spill for Envelope {
+    fn spill(&self) -> Vec<u8> {
+        // Spool sequence numbers start at 1, so 0 stands in for `None`.
+        let mut buf = self.seq.unwrap_or(0).to_le_bytes().to_vec();
+        self.record.encode(&mut buf).unwrap();
+        buf
+    }
+
+    fn unspill(bytes: &[u8]) -> Option<Self> {
+        let (seq, record) = bytes.split_at_checked(8)?;
+        let seq = u64::from_le_bytes(seq.try_into().ok()?);
+
+        Some(Self {
+            seq: (seq != 0).then_some(seq),
+            record: Record::decode(record).ok()?,
+        })
+    }
+}
+
+/// Sinks keyed by name, each behind its own writer.
 #[derive(Debug)]
 pub struct SinkRegistry {
-    sinks: HashMap<&'static str, Arc<dyn Sink>>,
+    sinks: HashMap<String, SinkSlot>,
+    writers: HashMap<String, Arc<Writer<Envelope>>>,
+    configs: HashMap<String, SinkConfig>,
+    routes: HashMap<String, RoutingConfig>,
+    router: Arc<RecordRouter>,
+    spool: Option<Arc<Spool>>,
+    writer_config: WriterConfig,
+    overflow: Option<OverflowConfig>,
+    sink_timeout: Duration,
+    health: HealthMap,
+    health_task: std::sync::Mutex<Option<JoinHandle<()>>>,
+    // Held for reading by every fanout and for writing by shutdown, so
+    // shutdown waits for in-flight fanouts and later ones see `false`.
+    accepting: RwLock<bool>,
+    // Sinks swapped out by `reload`, closed by `retire`.
+    replaced: std::sync::Mutex<Vec<(String, Arc<dyn Sink>)>>,
+}
+
+impl Default for SinkRegistry {
+    fn default() -> Self {
+        Self::new(WriterConfig::default(), Duration::from_secs(10), None)
+    }
 }
 
 impl SinkRegistry {
-    pub fn new() -> Self {
+    pub fn new(writer_config: WriterConfig, sink_timeout: Duration, spool: Option<Spool>) -> Self {
         Self {
             sinks: HashMap::new(),
+            writers: HashMap::new(),
+            configs: HashMap::new(),
+            routes: HashMap::new(),
+            router: Arc::new(RecordRouter::default()),
+            spool: spool.map(Arc::new),
+            writer_config,
+            overflow: None,
+            sink_timeout,
+            health: Arc::new(std::sync::RwLock::new(HashMap::new())),
+            health_task: std::sync::Mutex::new(None),
+            accepting: RwLock::new(true),
+            replaced: std::sync::Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Lets writers of sinks registered from now on spill to disk when their
+    /// buffer is full, each in its own subdirectory of `overflow.path`.
+    pub fn with_overflow(mut self, overflow: OverflowConfig) -> Self {
+        self.overflow = Some(overflow);
+        self
+    }
+
+    pub fn register<S: Sink + 'static>(
+        &mut self,
+        name: impl Into<String>,
+        sink: S,
+    ) -> Result<(), ApiError> {
+        self.register_with_routing(name, sink, RoutingConfig::default())
+    }
+
+    /// Registers a sink that only receives the records `routing` lets through.
+    pub fn register_with_routing<S: Sink + 'static>(
+        &mut self,
+        name: impl Into<String>,
+        sink: S,
+        routing: RoutingConfig,
+    ) -> Result<(), ApiError> {
+        self.register_dyn(name, Arc::new(sink), routing)
+    }
+
+    /// Registers an already-built sink, such as one made by a `SinkFactory`.
+    /// Names also name the sink's spool cursor and overflow directory, so
+    /// they're limited to letters, digits, `-`, `_` and `.`.
+    pub fn register_dyn(
+        &mut self,
+        name: impl Into<String>,
+        sink: Arc<dyn Sink>,
+        routing: RoutingConfig,
+    ) -> Result<(), ApiError> {
+        let name = name.into();
+
+        if !is_valid_name(&name) {
+            tracing::error!("Invalid sink name: {:?}", name);
+            return Err(ApiError::ConfigError);
+        }
+
+        if self.sinks.contains_key(&name) {
+            tracing::error!("Sink {} is registered more than once", name);
+            return Err(ApiError::ConfigError);
         }
+
+        if let Some(spool) = &self.spool {
+            spool.add_sink(&name)?;
+        }
+
+        let sink: SinkSlot = Arc::new(std::sync::RwLock::new(sink));
+
+        let writer = {
+            let sink = sink.clone();
+            let spool = self.spool.clone();
+            let timeout = self.sink_timeout;
+            let redeliver_limit = self.writer_config.batch_size;
+            let sink_name = name.clone();
+
+            let handler = move |batch: Vec<Envelope>| {
+                deliver(
+                    sink_name.clone(),
+                    sink.clone(),
+                    spool.clone(),
+                    timeout,
+                    redeliver_limit,
+                    batch,
+                )
+            };
+
+            match &self.overflow {
+                None => Writer::new(handler, self.writer_config.clone()),
+                Some(overflow) => Writer::with_overflow(
+                    handler,
+                    self.writer_config.clone(),
+                    &overflow.subdir(&name),
+                )
+                .map_err(|e| {
+                    tracing::error!("Failed to open overflow for sink {}: {}", name, e);
+                    ApiError::InternalError
+                })?,
+            }
+        };
+
+        self.sinks.insert(name.clone(), sink);
+        self.writers.insert(name.clone(), Arc::new(writer));
+
+        if routing.is_empty() {
+            self.routes.remove(&name);
+        } else {
+            self.routes.insert(name.clone(), routing);
+        }
+
+        self.health
+            .write()
+            .unwrap()
+            .insert(name, SinkHealth::Unknown);
+
+        Ok(())
+    }
+
+    /// Registers a sink built from `config` under the config's name,
+    /// remembering the config so a reload can tell whether the sink changed.
+    pub fn register_configured(
+        &mut self,
+        sink: Arc<dyn Sink>,
+        config: SinkConfig,
+    ) -> Result<(), ApiError> {
+        let name = config.name().to_string();
+        self.register_dyn(name.clone(), sink, config.routing.clone())?;
+        self.configs.insert(name, config);
+        Ok(())
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn Sink>> {
+        self.sinks
+            .get(name)
+            .map(|slot| slot.read().unwrap().clone())
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.sinks.keys().map(String::as_str).collect()
+    }
+
+    pub fn health(&self) -> HashMap<String, SinkHealth> {
+        self.health.read().unwrap().clone()
+    }
+
+    pub fn spool(&self) -> Option<&Arc<Spool>> {
+        self.spool.as_ref()
+    }
+
+    /// Whether readiness depends on the sink `name`. Sinks registered without
+    /// a config always count.
+    pub fn is_required(&self, name: &str) -> bool {
+        self.configs.get(name).is_none_or(|c| c.required)
     }
 
-    pub fn register<S: Sink + 'static>(&mut self, sink: S) {
-        self.sinks.insert(sink.name(), Arc::new(sink));
+    /// False once shutdown or retirement has begun.
+    pub fn is_accepting(&self) -> bool {
+        self.accepting.try_read().is_ok_and(|accepting| *accepting)
     }
 
-    pub fn get(&self, name: &str) -> Option<&Arc<dyn Sink>> {
-        self.sinks.get(name)
+    /// Records each sink's writer currently has buffered.
+    pub fn queue_depths(&self) -> HashMap<&str, usize> {
+        self.writers
+            .iter()
+            .map(|(name, writer)| (name.as_str(), writer.queue_depth()))
+            .collect()
     }
 
-    pub fn all(&self) -> impl Iterator<Item = &Arc<dyn Sink>> {
-        self.sinks.values()
+    /// Bytes each sink's writer currently has spilled to disk.
+    pub fn spilled_bytes(&self) -> HashMap<&str, u64> {
+        self.writers
+            .iter()
+            .map(|(name, writer)| (name.as_str(), writer.spilled_bytes()))
+            .collect()
     }
 
-    // For your fanout use case
+    /// Starts every sink, replays anything in the spool they haven't
+    /// received yet, then runs a health check on all of them every
+    /// `health_check_interval` until shutdown.
+    pub async fn start(&self, health_check_interval: Option<Duration>) -> Result<(), ApiError> {
+        for (name, sink) in self.sinks.iter() {
+            let sink = sink.read().unwrap().clone();
+            sink.start().await.map_err(|e| {
+                tracing::error!("Failed to start sink {}: {:?}", name, e);
+                e
+            })?;
+            tracing::debug!("Started sink {}.", name);
+        }
+
+        if let Some(spool) = &self.spool {
+            for (name, writer) in self.writers.iter() {
+                self.replay(spool, name, writer).await?;
+            }
+        }
+
+        self.start_health_checks(health_check_interval).await;
+
+        Ok(())
+    }
+
+    /// Runs a health check on every sink, then again each `interval` until
+    /// shutdown.
+    pub async fn start_health_checks(&self, interval: Option<Duration>) {
+        check_health(&self.sinks, &self.health, self.sink_timeout).await;
+
+        let Some(interval) = interval else {
+            return;
+        };
+
+        let sinks = self.sinks.clone();
+        let health = self.health.clone();
+        let timeout = self.sink_timeout;
+
+        let task = tokio::spawn(async move {
+            let mut ticker =
+                tokio::time::interval_at(tokio::time::Instant::now() + interval, interval);
+            loop {
+                ticker.tick().await;
+                check_health(&sinks, &health, timeout).await;
+            }
+        });
+
+        *self.health_task.lock().unwrap() = Some(task);
+    }
+
+    /// Builds a registry for `config` to take over from this one. Writers of
+    /// sinks that are still configured carry over with whatever they have
+    /// queued; a sink whose settings changed is swapped out underneath its
+    /// writer. New sinks are built and started before anything is touched,
+    /// so a config that fails leaves this registry as it was.
+    ///
+    /// Buffering, spool and overflow settings carry over unchanged. Once the
+    /// new registry is in use, `retire` this one.
+    pub async fn reload(
+        &self,
+        config: &PtolemyConfig,
+        factories: &SinkFactories,
+    ) -> Result<SinkRegistry, ApiError> {
+        let mut sinks = Vec::new();
+        let mut failure = None;
+
+        for sink_config in config.sink_configs()? {
+            let name = sink_config.name().to_string();
+
+            if !is_valid_name(&name) || sinks.iter().any(|(n, _, _)| *n == name) {
+                tracing::error!("Sink {:?} is invalid or configured more than once", name);
+                failure = Some(ApiError::ConfigError);
+                break;
+            }
+
+            let sink = match self.configs.get(&name) == Some(&sink_config) {
+                true => None,
+                false => {
+                    let started = async {
+                        let sink = factories.build(&sink_config, config)?;
+                        sink.start().await?;
+                        Ok::<_, ApiError>(sink)
+                    };
+
+                    match started.await {
+                        Ok(sink) => Some(sink),
+                        Err(e) => {
+                            failure = Some(e);
+                            break;
+                        }
+                    }
+                }
+            };
+
+            sinks.push((name, sink_config, sink));
+        }
+
+        if let Some(e) = failure {
+            close_sinks(started(sinks)).await;
+            return Err(e);
+        }
+
+        let mut next = SinkRegistry::new(self.writer_config.clone(), self.sink_timeout, None);
+        next.spool = self.spool.clone();
+        next.overflow = self.overflow.clone();
+        next.router = self.router.clone();
+        next.health = self.health.clone();
+
+        // New sinks are registered before any live slot is swapped, so a
+        // failure here still leaves this registry as it was.
+        for (name, sink_config, sink) in sinks.iter() {
+            if self.sinks.contains_key(name) {
+                continue;
+            }
+
+            let sink = sink.clone().expect("new sinks are always built");
+            if let Err(e) = next.register_configured(sink, sink_config.clone()) {
+                next.discard().await;
+                close_sinks(started(sinks)).await;
+                return Err(e);
+            }
+        }
+
+        for (name, sink_config, sink) in sinks {
+            let Some(slot) = self.sinks.get(&name) else {
+                continue;
+            };
+
+            if let Some(sink) = sink {
+                let previous = std::mem::replace(&mut *slot.write().unwrap(), sink);
+                tracing::info!("Replacing sink {}.", name);
+                self.replaced.lock().unwrap().push((name.clone(), previous));
+            }
+
+            next.sinks.insert(name.clone(), slot.clone());
+            next.writers
+                .insert(name.clone(), self.writers[&name].clone());
+            if !sink_config.routing.is_empty() {
+                next.routes
+                    .insert(name.clone(), sink_config.routing.clone());
+            }
+            next.configs.insert(name, sink_config);
+        }
+
+        Ok(next)
+    }
+
+    /// Undoes registering the sinks of a registry that never took over.
+    async fn discard(&self) {
+        futures::future::join_all(self.writers.values().map(|w| w.shutdown())).await;
+
+        for name in self.writers.keys() {
+            self.health.write().unwrap().remove(name);
+
+            if let Some(spool) = &self.spool {
+                let sink = name.clone();
+                if let Err(e) = run_blocking(spool, move |s| s.remove_sink(&sink)).await {
+                    tracing::error!("Failed to remove sink {} from the spool: {:?}", name, e);
+                }
+            }
+        }
+    }
+
+    /// Stops this registry after `successor` has taken over from it. Waits
+    /// for in-flight fanouts, drains the writers of sinks that were removed
+    /// and drops them from the spool, then closes removed and replaced sinks.
+    pub async fn retire(&self, successor: &SinkRegistry) {
+        if let Some(task) = self.health_task.lock().unwrap().take() {
+            task.abort();
+        }
+
+        *self.accepting.write().await = false;
+
+        let removed: Vec<String> = self
+            .writers
+            .keys()
+            .filter(|name| !successor.writers.contains_key(*name))
+            .cloned()
+            .collect();
+
+        futures::future::join_all(removed.iter().map(|name| self.writers[name].shutdown())).await;
+
+        if let Some(spool) = &self.spool {
+            for name in removed.iter() {
+                let sink = name.clone();
+                if let Err(e) = run_blocking(spool, move |s| s.remove_sink(&sink)).await {
+                    tracing::error!("Failed to remove sink {} from the spool: {:?}", name, e);
+                }
+            }
+        }
+
+        let mut closing = std::mem::take(&mut *self.replaced.lock().unwrap());
+        for name in removed {
+            tracing::info!("Removing sink {}.", name);
+            let sink = self.sinks[&name].read().unwrap().clone();
+            self.health.write().unwrap().remove(&name);
+            closing.push((name, sink));
+        }
+
+        close_sinks(closing).await;
+    }
+
+    async fn replay(
+        &self,
+        spool: &Arc<Spool>,
+        name: &str,
+        writer: &Writer<Envelope>,
+    ) -> Result<(), ApiError> {
+        let Some(mut after) = spool.start_replay(name) else {
+            return Ok(());
+        };
+
+        let mut replayed = 0;
+        loop {
+            let page = run_blocking(spool, move |s| s.read_after(after)).await?;
+            let Some(last) = page.last().map(|(seq, _)| *seq) else {
+                break;
+            };
+
+            let (seqs, records): (Vec<u64>, Vec<Record>) = page.into_iter().unzip();
+            let indices = self
+                .route(&records)
+                .into_iter()
+                .find(|(sink, _)| *sink == name)
+                .map(|(_, indices)| indices)
+                .unwrap_or_default();
+
+            let routed: Vec<u64> = indices.iter().map(|i| seqs[*i]).collect();
+            spool.mark_replayed(name, &routed, last);
+
+            writer
+                .write_many(indices.into_iter().map(|i| Envelope {
+                    seq: Some(seqs[i]),
+                    record: records[i].clone(),
+                }))
+                .await
+                .map_err(|_| ApiError::Unavailable)?;
+
+            replayed += routed.len();
+            after = last;
+        }
+
+        if replayed > 0 {
+            tracing::info!("Replaying {} spooled records to sink {}.", replayed, name);
+        }
+
+        let name = name.to_string();
+        run_blocking(spool, move |s| s.finish_replay(&name)).await
+    }
+
+    /// Indices of the records in `messages` that each sink should receive.
+    fn route(&self, messages: &[Record]) -> Vec<(&str, Vec<usize>)> {
+        let attributes = match self.routes.is_empty() {
+            true => Vec::new(),
+            false => self.router.attributes(messages),
+        };
+
+        self.writers
+            .keys()
+            .map(|name| {
+                let indices = match self.routes.get(name) {
+                    None => (0..messages.len()).collect(),
+                    Some(routing) => attributes
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, attrs)| attrs.as_ref().is_some_and(|a| routing.matches(a)))
+                        .map(|(i, _)| i)
+                        .collect(),
+                };
+                (name.as_str(), indices)
+            })
+            .collect()
+    }
+
+    /// Persists a batch of records to the spool, if enabled, then queues them
+    /// on the writer of every sink whose routing rules accept them.
     pub async fn fanout(&self, messages: Vec<Record>) -> Vec<Result<(), ApiError>> {
-        let futures = self
-            .sinks
-            .values()
-            .map(|sink| sink.send_batch(messages.clone()));
+        let accepting = self.accepting.read().await;
+
+        if !*accepting {
+            return self
+                .writers
+                .keys()
+                .map(|_| Err(ApiError::Unavailable))
+                .collect();
+        }
+
+        let routes = self.route(&messages);
+
+        let seqs: Vec<Option<u64>> = match &self.spool {
+            None => vec![None; messages.len()],
+            Some(spool) => {
+                let records = messages.clone();
+                let spool_routes: Vec<(String, Vec<usize>)> = routes
+                    .iter()
+                    .map(|(name, indices)| (name.to_string(), indices.clone()))
+                    .collect();
+
+                match run_blocking(spool, move |s| s.append(&records, &spool_routes)).await {
+                    Ok(seqs) => seqs.into_iter().map(Some).collect(),
+                    Err(e) => return self.writers.keys().map(|_| Err(e.clone())).collect(),
+                }
+            }
+        };
+
+        let futures = routes.into_iter().map(|(name, indices)| {
+            let writer = &self.writers[name];
+            let envelopes: Vec<Envelope> = indices
+                .into_iter()
+                .map(|i| Envelope {
+                    seq: seqs[i],
+                    record: messages[i].clone(),
+                })
+                .collect();
+
+            async move {
+                if envelopes.is_empty() {
+                    return Ok(());
+                }
+
+                writer.write_many(envelopes).await.map_err(|e| {
+                    tracing::error!("Failed to queue records for sink {}: {}", name, e);
+                    match e {
+                        WriterError::OverflowFull => ApiError::ResourceExhausted,
+                        _ => ApiError::Unavailable,
+                    }
+                })
+            }
+        });
+
         futures::future::join_all(futures).await
     }
+
+    /// Stops accepting new records, waits for in-flight fanouts, drains every
+    /// writer, then flushes and shuts down every sink.
+    pub async fn shutdown(&self) {
+        if let Some(task) = self.health_task.lock().unwrap().take() {
+            task.abort();
+        }
+
+        *self.accepting.write().await = false;
+        tracing::debug!("Sink registry no longer accepting records.");
+
+        futures::future::join_all(self.writers.values().map(|w| w.shutdown())).await;
+        tracing::debug!("All sink writers drained.");
+
+        close_sinks(
+            self.sinks
+                .iter()
+                .map(|(name, sink)| (name.clone(), sink.read().unwrap().clone())),
+        )
+        .await;
+
+        tracing::info!("Sink registry shut down.");
+    }
+}
+
+/// A sink `reload` keeps, with the sink it started if the config changed.
+type ReloadedSink = (String, SinkConfig, Option<Arc<dyn Sink>>);
+
+/// The sinks `reload` built and started, to close if it fails.
+fn started(sinks: Vec<ReloadedSink>) -> impl Iterator<Item = (String, Arc<dyn Sink>)> {
+    sinks
+        .into_iter()
+        .filter_map(|(name, _, sink)| Some((name, sink?)))
+}
+
+/// Flushes, then shuts down, each sink.
+async fn close_sinks(sinks: impl IntoIterator<Item = (String, Arc<dyn Sink>)>) {
+    let sinks: Vec<_> = sinks.into_iter().collect();
+
+    let flushes = sinks.iter().map(|(name, sink)| async move {
+        if let Err(e) = sink.flush().await {
+            tracing::error!("Failed to flush sink {}: {:?}", name, e);
+        }
+    });
+    futures::future::join_all(flushes).await;
+
+    let shutdowns = sinks.iter().map(|(name, sink)| async move {
+        if let Err(e) = sink.shutdown().await {
+            tracing::error!("Failed to shut down sink {}: {:?}", name, e);
+        }
+    });
+    futures::future::join_all(shutdowns).await;
+}
+
+async fn run_blocking<T, F>(spool: &Arc<Spool>, f: F) -> Result<T, ApiError>
+where
+    T: Send + 'static,
+    F: FnOnce(&Spool) -> Result<T, ApiError> + Send + 'static,
+{
+    let spool = spool.clone();
+    tokio::task::spawn_blocking(move || f(&spool))
+        .await
+        .map_err(|e| {
+            tracing::error!("Spool task failed: {}", e);
+            ApiError::InternalError
+        })?
+}
+
+fn is_valid_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with('.')
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+async fn send_with_timeout(
+    name: &str,
+    sink: &Arc<dyn Sink>,
+    records: Vec<Record>,
+    timeout: Duration,
+) -> Vec<Result<(), ApiError>> {
+    let count = records.len();
+    let start = std::time::Instant::now();
+    let results = match tokio::time::timeout(timeout, sink.send_each(records)).await {
+        Ok(results) => results,
+        Err(_) => {
+            tracing::error!("Sink {} timed out after {:?}", name, timeout);
+            vec![Err(ApiError::TimeoutError); count]
+        }
+    };
+
+    metrics::sink_sent(name, start.elapsed(), results.iter().all(Result::is_ok));
+    results
+}
+
+/// Splits `seqs` by whether the record sent alongside each was delivered.
+fn split_delivered(
+    seqs: Vec<Option<u64>>,
+    results: &[Result<(), ApiError>],
+) -> (Vec<u64>, Vec<u64>) {
+    let mut delivered = Vec::new();
+    let mut failed = Vec::new();
+    for (seq, result) in seqs.into_iter().zip(results) {
+        match (seq, result) {
+            (Some(seq), Ok(_)) => delivered.push(seq),
+            (Some(seq), Err(_)) => failed.push(seq),
+            (None, _) => {}
+        }
+    }
+    (delivered, failed)
+}
+
+/// Records the outcome of each sent record in the spool.
+async fn ack_each(
+    spool: &Arc<Spool>,
+    name: &str,
+    delivered: Vec<u64>,
+    failed: Vec<u64>,
+) -> Result<(), ApiError> {
+    let sink_name = name.to_string();
+    run_blocking(spool, move |s| {
+        s.ack(&sink_name, &delivered, true)?;
+        s.ack(&sink_name, &failed, false)
+    })
+    .await
+}
+
+/// Sends a batch to a sink and records the outcome of each record in the
+/// spool. Once the sink accepts a whole batch, records it previously failed
+/// to take are retried.
+async fn deliver(
+    name: String,
+    sink: SinkSlot,
+    spool: Option<Arc<Spool>>,
+    timeout: Duration,
+    redeliver_limit: usize,
+    batch: Vec<Envelope>,
+) -> Result<(), ApiError> {
+    let sink = sink.read().unwrap().clone();
+    let (seqs, records): (Vec<Option<u64>>, Vec<Record>) =
+        batch.into_iter().map(|e| (e.seq, e.record)).unzip();
+
+    let results = send_with_timeout(&name, &sink, records, timeout).await;
+    let result = results
+        .iter()
+        .find(|r| r.is_err())
+        .cloned()
+        .unwrap_or(Ok(()));
+
+    let Some(spool) = spool else {
+        return result;
+    };
+
+    let (delivered, failed) = split_delivered(seqs, &results);
+    ack_each(&spool, &name, delivered, failed).await?;
+    result.clone()?;
+
+    loop {
+        let sink_name = name.clone();
+        let failed =
+            run_blocking(&spool, move |s| s.take_failed(&sink_name, redeliver_limit)).await?;
+        if failed.is_empty() {
+            break;
+        }
+
+        tracing::info!("Redelivering {} records to sink {}.", failed.len(), name);
+
+        let (seqs, records): (Vec<u64>, Vec<Record>) = failed.into_iter().unzip();
+        let results = send_with_timeout(&name, &sink, records, timeout).await;
+        let (delivered, failed) = split_delivered(seqs.into_iter().map(Some).collect(), &results);
+        let done = failed.is_empty();
+        ack_each(&spool, &name, delivered, failed).await?;
+
+        if !done {
+            break;
+        }
+    }
+
+    result
+}
+
+async fn check_health(sinks: &HashMap<String, SinkSlot>, health: &HealthMap, timeout: Duration) {
+    let checks = sinks.iter().map(|(name, sink)| async move {
+        let sink = sink.read().unwrap().clone();
+        let status: SinkHealth = match tokio::time::timeout(timeout, sink.health_check()).await {
+            Ok(result) => result.into(),
+            Err(_) => SinkHealth::Unhealthy(ApiError::TimeoutError.to_string()),
+        };
+        (name.clone(), status)
+    });
+
+    for (name, status) in futures::future::join_all(checks).await {
+        let previous = health.write().unwrap().insert(name.clone(), status.clone());
+
+        if previous.as_ref() != Some(&status) {
+            match &status {
+                SinkHealth::Unhealthy(e) => tracing::warn!("Sink {} is unhealthy: {}", name, e),
+                _ => tracing::info!("Sink {} is {:?}", name, status),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    #[derive(Debug, Default)]
+    struct TestSink {
+        shut_down: Arc<AtomicBool>,
+    }
+
+    #[async_trait::async_trait]
+    impl Sink for TestSink {
+        async fn send_batch(&self, _messages: Vec<Record>) -> Result<(), ApiError> {
+            Ok(())
+        }
+
+        async fn shutdown(&self) -> Result<(), ApiError> {
+            self.shut_down.store(true, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct FlakySink {
+        failing: Arc<AtomicBool>,
+        attempts: Arc<std::sync::atomic::AtomicUsize>,
+        received: Arc<std::sync::Mutex<Vec<Record>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Sink for FlakySink {
+        async fn send_batch(&self, messages: Vec<Record>) -> Result<(), ApiError> {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            if self.failing.load(Ordering::SeqCst) {
+                return Err(ApiError::ConnectionError);
+            }
+            self.received.lock().unwrap().extend(messages);
+            Ok(())
+        }
+    }
+
+    /// Rejects records for one workspace while `failing` is set.
+    #[derive(Debug, Default)]
+    struct PickySink {
+        failing: Arc<AtomicBool>,
+        received: Arc<std::sync::Mutex<Vec<Record>>>,
+    }
+
+    #[async_trait::async_trait]
+    impl Sink for PickySink {
+        async fn send_batch(&self, _messages: Vec<Record>) -> Result<(), ApiError> {
+            unreachable!("records are sent one by one")
+        }
+
+        async fn send_each(&self, messages: Vec<Record>) -> Vec<Result<(), ApiError>> {
+            let failing = self.failing.load(Ordering::SeqCst);
+            let mut received = self.received.lock().unwrap();
+            messages
+                .into_iter()
+                .map(
+                    |message| match failing && message.workspace_id.as_deref() == Some("bad") {
+                        true => Err(ApiError::ConnectionError),
+                        false => {
+                            received.push(message);
+                            Ok(())
+                        }
+                    },
+                )
+                .collect()
+        }
+    }
+
+    async fn wait_for(condition: impl Fn() -> bool) {
+        for _ in 0..500 {
+            if condition() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("timed out waiting for the sink");
+    }
+
+    fn config(sinks: serde_json::Value) -> PtolemyConfig {
+        PtolemyConfig {
+            sinks: serde_json::from_value(sinks).unwrap(),
+            ..Default::default()
+        }
+    }
+
+    /// Shut-down flags of the `tracked` sinks, in the order they were built.
+    type Tracked = Arc<std::sync::Mutex<Vec<Arc<AtomicBool>>>>;
+
+    fn factories() -> (SinkFactories, Tracked) {
+        let tracked = Tracked::default();
+        let built = tracked.clone();
+
+        let mut factories = SinkFactories::builtin();
+        factories
+            .register("test", |_, _| Ok(Arc::new(TestSink::default())))
+            .register("tracked", move |_, _| {
+                let sink = TestSink::default();
+                built.lock().unwrap().push(sink.shut_down.clone());
+                Ok(Arc::new(sink))
+            });
+        (factories, tracked)
+    }
+
+    fn shut_down(tracked: &Tracked) -> Vec<bool> {
+        let tracked = tracked.lock().unwrap();
+        tracked.iter().map(|f| f.load(Ordering::SeqCst)).collect()
+    }
+
+    #[tokio::test]
+    async fn test_reload_keeps_writers_and_replaces_changed_sinks() {
+        let (factories, tracked) = factories();
+        let before =
+            config(serde_json::json!([{"type": "tracked", "version": 1}, {"type": "stdout"}]));
+        let after = config(serde_json::json!([{"type": "tracked", "version": 2}]));
+
+        let registry = super::super::configure_sink_registry_with(&before, &factories).unwrap();
+        let old_sink = registry.get("tracked").unwrap();
+
+        let next = registry.reload(&after, &factories).await.unwrap();
+        assert_eq!(shut_down(&tracked), vec![false, false]);
+        registry.retire(&next).await;
+
+        assert!(Arc::ptr_eq(
+            &registry.writers["tracked"],
+            &next.writers["tracked"]
+        ));
+        assert!(!Arc::ptr_eq(&old_sink, &next.get("tracked").unwrap()));
+        assert!(next.get("stdout").is_none());
+        assert!(registry.writers["stdout"]
+            .write(Envelope {
+                seq: None,
+                record: Record::default(),
+            })
+            .await
+            .is_err());
+
+        // Only the replaced sink has been shut down so far.
+        assert_eq!(shut_down(&tracked), vec![true, false]);
+
+        next.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_failed_reload_leaves_registry_unchanged() {
+        let (factories, _) = factories();
+        let before = config(serde_json::json!([{"type": "test"}]));
+        let after =
+            config(serde_json::json!([{"type": "test", "version": 2}, {"type": "missing"}]));
+
+        let registry = super::super::configure_sink_registry_with(&before, &factories).unwrap();
+        let old_sink = registry.get("test").unwrap();
+
+        assert!(registry.reload(&after, &factories).await.is_err());
+        assert!(Arc::ptr_eq(&old_sink, &registry.get("test").unwrap()));
+
+        registry.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_failed_registration_leaves_live_sinks() {
+        let (factories, tracked) = factories();
+        let dir = crate::test_util::TempDir::new("overflow");
+        // A file where the new sink's overflow directory would go.
+        std::fs::create_dir_all(dir.path()).unwrap();
+        std::fs::write(dir.path().join("blocked"), b"").unwrap();
+
+        let overflow = OverflowConfig {
+            path: dir.path_string(),
+            max_bytes: 1 << 20,
+        };
+        let before = PtolemyConfig {
+            overflow: Some(overflow.clone()),
+            ..config(serde_json::json!([{"type": "tracked", "version": 1}]))
+        };
+        let after = PtolemyConfig {
+            overflow: Some(overflow),
+            ..config(serde_json::json!([
+                {"type": "tracked", "version": 2},
+                {"type": "test", "name": "blocked"}
+            ]))
+        };
+
+        let registry = super::super::configure_sink_registry_with(&before, &factories).unwrap();
+        let old_sink = registry.get("tracked").unwrap();
+
+        assert!(registry.reload(&after, &factories).await.is_err());
+        assert!(Arc::ptr_eq(&old_sink, &registry.get("tracked").unwrap()));
+        assert_eq!(shut_down(&tracked), vec![false, true]);
+        assert!(!registry.health().contains_key("blocked"));
+
+        registry.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_sinks_are_registered_by_name() {
+        let (factories, _) = factories();
+        let named = config(serde_json::json!([
+            {"type": "test", "name": "primary"},
+            {"type": "test", "name": "backup"},
+            {"type": "stdout"}
+        ]));
+
+        let registry = super::super::configure_sink_registry_with(&named, &factories).unwrap();
+        let mut names = registry.names();
+        names.sort();
+        assert_eq!(names, vec!["backup", "primary", "stdout"]);
+
+        let duplicate = config(serde_json::json!([{"type": "test"}, {"type": "test"}]));
+        assert!(super::super::configure_sink_registry_with(&duplicate, &factories).is_err());
+        assert!(registry.reload(&duplicate, &factories).await.is_err());
+
+        let unsafe_name = config(serde_json::json!([{"type": "test", "name": "../test"}]));
+        assert!(super::super::configure_sink_registry_with(&unsafe_name, &factories).is_err());
+
+        registry.shutdown().await;
+    }
+
+    #[tokio::test]
+    async fn test_failed_batches_are_redelivered() {
+        let dir = crate::test_util::TempDir::new("spool");
+        let spool = Spool::open(crate::api::config::spool::SpoolConfig {
+            path: dir.path_string(),
+            fsync: false,
+            ..Default::default()
+        })
+        .unwrap();
+        let writer_config = WriterConfig {
+            batch_size: 1,
+            ..Default::default()
+        };
+        let mut registry = SinkRegistry::new(writer_config, Duration::from_secs(1), Some(spool));
+
+        let sink = FlakySink::default();
+        let (failing, attempts, received) = (
+            sink.failing.clone(),
+            sink.attempts.clone(),
+            sink.received.clone(),
+        );
+        registry.register("flaky", sink).unwrap();
+        let spool = registry.spool().unwrap().clone();
+
+        let record = |workspace: &str| Record {
+            workspace_id: Some(workspace.to_string()),
+            record_data: None,
+        };
+
+        failing.store(true, Ordering::SeqCst);
+        registry.fanout(vec![record("a")]).await;
+        wait_for(|| attempts.load(Ordering::SeqCst) == 1).await;
+        assert_eq!(spool.acked("flaky"), Some(0));
+
+        failing.store(false, Ordering::SeqCst);
+        registry.fanout(vec![record("b")]).await;
+        wait_for(|| received.lock().unwrap().len() == 2).await;
+        registry.shutdown().await;
+
+        assert_eq!(*received.lock().unwrap(), vec![record("b"), record("a")]);
+        assert_eq!(spool.acked("flaky"), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_only_failed_records_are_redelivered() {
+        let dir = crate::test_util::TempDir::new("spool");
+        let spool = Spool::open(crate::api::config::spool::SpoolConfig {
+            path: dir.path_string(),
+            fsync: false,
+            ..Default::default()
+        })
+        .unwrap();
+        let writer_config = WriterConfig {
+            batch_size: 3,
+            ..Default::default()
+        };
+        let mut registry = SinkRegistry::new(writer_config, Duration::from_secs(1), Some(spool));
+
+        let sink = PickySink::default();
+        let (failing, received) = (sink.failing.clone(), sink.received.clone());
+        registry.register("picky", sink).unwrap();
+        let spool = registry.spool().unwrap().clone();
+
+        let record = |workspace: &str| Record {
+            workspace_id: Some(workspace.to_string()),
+            record_data: None,
+        };
+
+        failing.store(true, Ordering::SeqCst);
+        registry
+            .fanout(vec![record("a"), record("bad"), record("c")])
+            .await;
+        wait_for(|| received.lock().unwrap().len() == 2).await;
+        wait_for(|| spool.acked("picky") == Some(1)).await;
+
+        failing.store(false, Ordering::SeqCst);
+        registry.fanout(vec![record("d")]).await;
+        wait_for(|| received.lock().unwrap().len() == 4).await;
+        registry.shutdown().await;
+
+        assert_eq!(
+            *received.lock().unwrap(),
+            vec![record("a"), record("c"), record("d"), record("bad")]
+        );
+        assert_eq!(spool.acked("picky"), Some(4));
+    }
+
+    #[tokio::test]
+    async fn test_required_sinks() {
+        let config = config(serde_json::json!([
+            {"type": "test", "required": false},
+            {"type": "stdout"}
+        ]));
+
+        let registry = super::super::configure_sink_registry_with(&config, &factories().0).unwrap();
+        assert!(!registry.is_required("test"));
+        assert!(registry.is_required("stdout"));
+        assert!(!registry.configs["test"].settings.contains_key("required"));
+        assert!(registry.is_accepting());
+
+        // Sinks are checked once on start, even without periodic checks.
+        assert_eq!(registry.health()["stdout"], SinkHealth::Unknown);
+        registry.start(None).await.unwrap();
+        assert_eq!(registry.health()["stdout"], SinkHealth::Healthy);
+
+        registry.shutdown().await;
+        assert!(!registry.is_accepting());
+    }
 }
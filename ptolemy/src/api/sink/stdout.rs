@@ -1,9 +1,6 @@
 use crate::{generated::record_publisher::Record, models};
 
-use super::{
-    super::{config::PtolemyConfig, error::ApiError},
-    sink::Sink,
-};
+use super::{super::error::ApiError, sink::Sink};
 
 #[derive(Debug)]
 pub struct StdoutSink;
@@ -18,18 +15,10 @@ impl Sink for StdoutSink {
         }
         Ok(())
     }
-
-    fn name(&self) -> &'static str {
-        "stdout"
-    }
-
-    fn from_config(_config: &PtolemyConfig) -> Result<Self, ApiError> {
-        Ok(Self)
-    }
 }
 
 fn serialize_to_json(record: Record) -> Option<String> {
-    let rec = match models::Record::try_from(record) {
+    let rec = match models::PublishedRecord::try_from(record) {
         Ok(r) => r,
         Err(e) => {
             tracing::error!("⚠️ Error parsing record: {:?}", e);
@@ -40,7 +29,7 @@ fn serialize_to_json(record: Record) -> Option<String> {
     match serde_json::to_string(&rec) {
         Ok(json_str) => return Some(json_str),
         Err(e) => {
-            tracing::error!("⚠️ Error serializing record {}: {:?}", rec.id(), e);
+            tracing::error!("⚠️ Error serializing record {}: {:?}", rec.record.id(), e);
             return None;
         }
     };
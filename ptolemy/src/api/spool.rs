@@ -0,0 +1,730 @@
+//! Append-only, on-disk log of accepted records.
+//!
+//! Records are appended to numbered segment files before `Publish` returns.
+//! Each entry is framed as
+//!
+//! ```text
+//! [len: u32][crc32: u32][seq: u64][payload: len bytes]
+//! ```
+//!
+//! where the payload is the protobuf-encoded record and the checksum covers
+//! the sequence number and payload. Every sink has a cursor: the highest
+//! sequence number up to which every record routed to it has been delivered.
+//! Cursors are persisted as they advance, records past a sink's cursor are
+//! replayed on restart, and segments behind every cursor are deleted.
+
+use crate::generated::record_publisher::Record;
+use prost::Message;
+
+use super::{config::spool::SpoolConfig, error::ApiError};
+
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+const HEADER_LEN: usize = 16;
+const SEGMENT_EXTENSION: &str = "seg";
+const CURSOR_DIR: &str = "cursors";
+
+#[derive(Debug)]
+struct Segment {
+    first_seq: u64,
+    last_seq: Option<u64>,
+    path: PathBuf,
+    size: u64,
+}
+
+#[derive(Debug, Default)]
+struct SinkCursor {
+    acked: u64,
+    pending: BTreeSet<u64>,
+    failed: BTreeSet<u64>,
+    // Lowest sequence number not yet handed to the sink during a replay.
+    replay_floor: Option<u64>,
+}
+
+impl SinkCursor {
+    fn position(&self, last_seq: u64) -> u64 {
+        [
+            self.pending.first(),
+            self.failed.first(),
+            self.replay_floor.as_ref(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+        .map_or(last_seq, |seq| seq - 1)
+    }
+}
+
+#[derive(Debug)]
+struct SpoolInner {
+    active: File,
+    segments: Vec<Segment>,
+    next_seq: u64,
+    size: u64,
+    cursors: HashMap<String, SinkCursor>,
+    /// Set when a failed append couldn't be rolled back, leaving garbage at
+    /// the end of the active segment.
+    broken: bool,
+}
+
+#[derive(Debug)]
+pub struct Spool {
+    dir: PathBuf,
+    config: SpoolConfig,
+    inner: Mutex<SpoolInner>,
+}
+
+fn io_error(context: &str, e: std::io::Error) -> ApiError {
+    tracing::error!("Spool {}: {}", context, e);
+    ApiError::InternalError
+}
+
+fn segment_path(dir: &Path, first_seq: u64) -> PathBuf {
+    dir.join(format!("{:020}.{}", first_seq, SEGMENT_EXTENSION))
+}
+
+fn cursor_path(dir: &Path, sink: &str) -> PathBuf {
+    dir.join(CURSOR_DIR).join(format!("{}.cursor", sink))
+}
+
+fn encode_entry(seq: u64, record: &Record, buf: &mut Vec<u8>) {
+    let payload = record.encode_to_vec();
+
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(&seq.to_le_bytes());
+    hasher.update(&payload);
+
+    buf.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    buf.extend_from_slice(&hasher.finalize().to_le_bytes());
+    buf.extend_from_slice(&seq.to_le_bytes());
+    buf.extend_from_slice(&payload);
+}
+
+/// Decodes every intact entry in `data`, stopping at the first torn or
+/// corrupt one. Returns the entries and the number of bytes they span.
+fn decode_entries(data: &[u8]) -> (Vec<(u64, Record)>, usize) {
+    let mut entries = Vec::new();
+    let mut offset = 0;
+
+    while data.len() - offset >= HEADER_LEN {
+        let header = &data[offset..offset + HEADER_LEN];
+        let len = u32::from_le_bytes(header[0..4].try_into().unwrap()) as usize;
+        let crc = u32::from_le_bytes(header[4..8].try_into().unwrap());
+        let seq = u64::from_le_bytes(header[8..16].try_into().unwrap());
+
+        let Some(payload) = data.get(offset + HEADER_LEN..offset + HEADER_LEN + len) else {
+            break;
+        };
+
+        let mut hasher = crc32fast::Hasher::new();
+        hasher.update(&seq.to_le_bytes());
+        hasher.update(payload);
+        if hasher.finalize() != crc {
+            break;
+        }
+
+        match Record::decode(payload) {
+            Ok(record) => entries.push((seq, record)),
+            Err(_) => break,
+        }
+
+        offset += HEADER_LEN + len;
+    }
+
+    (entries, offset)
+}
+
+fn read_segment(segment: &Segment) -> Result<Vec<(u64, Record)>, ApiError> {
+    let data = fs::read(&segment.path).map_err(|e| io_error("read failed", e))?;
+    Ok(decode_entries(&data).0)
+}
+
+fn write_cursor(dir: &Path, sink: &str, acked: u64) -> Result<(), ApiError> {
+    let path = cursor_path(dir, sink);
+    let tmp = path.with_extension("tmp");
+
+    fs::write(&tmp, acked.to_string()).map_err(|e| io_error("cursor write failed", e))?;
+    fs::rename(&tmp, &path).map_err(|e| io_error("cursor rename failed", e))
+}
+
+impl Spool {
+    /// Opens the spool at `config.path`, validating every segment and
+    /// truncating a torn write at the end of the last one.
+    pub fn open(config: SpoolConfig) -> Result<Self, ApiError> {
+        let dir = PathBuf::from(&config.path);
+        fs::create_dir_all(dir.join(CURSOR_DIR)).map_err(|e| io_error("create failed", e))?;
+
+        let mut segments = Vec::new();
+        for entry in fs::read_dir(&dir).map_err(|e| io_error("list failed", e))? {
+            let path = entry.map_err(|e| io_error("list failed", e))?.path();
+            if path.extension().and_then(|e| e.to_str()) != Some(SEGMENT_EXTENSION) {
+                continue;
+            }
+
+            let Some(first_seq) = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.parse::<u64>().ok())
+            else {
+                tracing::warn!("Ignoring unrecognized spool file {:?}", path);
+                continue;
+            };
+
+            segments.push(Segment {
+                first_seq,
+                last_seq: None,
+                path,
+                size: 0,
+            });
+        }
+        segments.sort_by_key(|s| s.first_seq);
+
+        let segment_count = segments.len();
+        for (i, segment) in segments.iter_mut().enumerate() {
+            let data = fs::read(&segment.path).map_err(|e| io_error("read failed", e))?;
+            let (entries, valid_len) = decode_entries(&data);
+
+            if valid_len < data.len() {
+                if i + 1 == segment_count {
+                    tracing::warn!(
+                        "Truncating {} bytes of torn writes from {:?}",
+                        data.len() - valid_len,
+                        segment.path
+                    );
+                    OpenOptions::new()
+                        .write(true)
+                        .open(&segment.path)
+                        .and_then(|f| f.set_len(valid_len as u64))
+                        .map_err(|e| io_error("truncate failed", e))?;
+                } else {
+                    tracing::error!(
+                        "Spool segment {:?} is corrupt after {} entries; the rest is lost",
+                        segment.path,
+                        entries.len()
+                    );
+                }
+            }
+
+            segment.last_seq = entries.last().map(|(seq, _)| *seq);
+            segment.size = valid_len as u64;
+        }
+
+        let last_written = segments.iter().filter_map(|s| s.last_seq).max();
+
+        // Cursors can point past the last segment once it's been compacted,
+        // so sequence numbers keep counting up from whichever is higher.
+        let mut last_acked = 0;
+        for entry in fs::read_dir(dir.join(CURSOR_DIR)).map_err(|e| io_error("list failed", e))? {
+            let path = entry.map_err(|e| io_error("list failed", e))?.path();
+            if let Ok(acked) = fs::read_to_string(&path).map(|s| s.trim().parse::<u64>()) {
+                last_acked = last_acked.max(acked.unwrap_or(0));
+            }
+        }
+
+        let next_seq = last_written.unwrap_or(0).max(last_acked) + 1;
+
+        if segments.is_empty() {
+            segments.push(Segment {
+                first_seq: next_seq,
+                last_seq: None,
+                path: segment_path(&dir, next_seq),
+                size: 0,
+            });
+        }
+
+        let active = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&segments.last().unwrap().path)
+            .map_err(|e| io_error("open failed", e))?;
+
+        let size = segments.iter().map(|s| s.size).sum();
+
+        tracing::info!(
+            "Opened spool at {:?} ({} segments, {} bytes, next sequence {})",
+            dir,
+            segments.len(),
+            size,
+            next_seq
+        );
+
+        Ok(Self {
+            dir,
+            config,
+            inner: Mutex::new(SpoolInner {
+                active,
+                segments,
+                next_seq,
+                size,
+                cursors: HashMap::new(),
+                broken: false,
+            }),
+        })
+    }
+
+    /// Loads the cursor for `sink`. A sink without a saved cursor starts at
+    /// the end of the spool rather than receiving everything already in it.
+    pub fn add_sink(&self, sink: &str) -> Result<(), ApiError> {
+        let mut inner = self.inner.lock().unwrap();
+        let last_seq = inner.next_seq - 1;
+
+        let acked = match fs::read_to_string(cursor_path(&self.dir, sink)) {
+            Ok(s) => s.trim().parse::<u64>().map_err(|e| {
+                tracing::error!("Invalid spool cursor for sink {}: {}", sink, e);
+                ApiError::ConfigError
+            })?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                write_cursor(&self.dir, sink, last_seq)?;
+                last_seq
+            }
+            Err(e) => return Err(io_error("cursor read failed", e)),
+        };
+
+        inner.cursors.insert(
+            sink.to_string(),
+            SinkCursor {
+                acked,
+                ..Default::default()
+            },
+        );
+
+        Ok(())
+    }
+
+    /// Forgets a sink that is no longer configured, so the spool doesn't
+    /// hold on to records for it.
+    pub fn remove_sink(&self, sink: &str) -> Result<(), ApiError> {
+        let mut inner = self.inner.lock().unwrap();
+        inner.cursors.remove(sink);
+
+        match fs::remove_file(cursor_path(&self.dir, sink)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                return Err(io_error("cursor remove failed", e))
+            }
+            _ => {}
+        }
+
+        self.compact(&mut inner)
+    }
+
+    pub fn size(&self) -> u64 {
+        self.inner.lock().unwrap().size
+    }
+
+    pub fn max_size_bytes(&self) -> Option<u64> {
+        self.config.max_size_bytes
+    }
+
+    /// The last sequence number `sink` is known to have received.
+    pub fn acked(&self, sink: &str) -> Option<u64> {
+        self.inner
+            .lock()
+            .unwrap()
+            .cursors
+            .get(sink)
+            .map(|c| c.acked)
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.config
+            .max_size_bytes
+            .is_some_and(|max| self.size() >= max)
+    }
+
+    /// Durably appends `records` and marks each as pending delivery to the
+    /// sinks `routes` sends it to, by index into `records`. Returns the
+    /// sequence number assigned to each record.
+    pub fn append(
+        &self,
+        records: &[Record],
+        routes: &[(String, Vec<usize>)],
+    ) -> Result<Vec<u64>, ApiError> {
+        let mut inner = self.inner.lock().unwrap();
+        if inner.broken {
+            return Err(ApiError::InternalError);
+        }
+
+        let mut buf = Vec::new();
+        let seqs: Vec<u64> = (inner.next_seq..inner.next_seq + records.len() as u64).collect();
+        for (seq, record) in seqs.iter().zip(records) {
+            encode_entry(*seq, record, &mut buf);
+        }
+
+        if let Some(max) = self.config.max_size_bytes {
+            if inner.size + buf.len() as u64 > max {
+                tracing::warn!("Spool is full ({} of {} bytes)", inner.size, max);
+                return Err(ApiError::ResourceExhausted);
+            }
+        }
+
+        if let Err(e) = self.write_active(&mut inner, &buf) {
+            // Drop whatever made it to disk so the next append doesn't land
+            // behind a partial entry and get truncated with it on reopen.
+            let good = inner.segments.last().unwrap().size;
+            if let Err(e) = inner.active.set_len(good) {
+                tracing::error!("Spool truncate failed, refusing further appends: {}", e);
+                inner.broken = true;
+            }
+            return Err(e);
+        }
+
+        inner.next_seq += records.len() as u64;
+        inner.size += buf.len() as u64;
+
+        let active = inner.segments.last_mut().unwrap();
+        active.size += buf.len() as u64;
+        if let Some(last) = seqs.last() {
+            active.last_seq = Some(*last);
+        }
+
+        for (sink, indices) in routes {
+            if let Some(cursor) = inner.cursors.get_mut(sink) {
+                cursor.pending.extend(indices.iter().map(|i| seqs[*i]));
+            }
+        }
+
+        if inner.segments.last().unwrap().size >= self.config.segment_size_bytes {
+            self.rotate(&mut inner)?;
+        }
+
+        Ok(seqs)
+    }
+
+    fn write_active(&self, inner: &mut SpoolInner, buf: &[u8]) -> Result<(), ApiError> {
+        inner
+            .active
+            .write_all(buf)
+            .map_err(|e| io_error("append failed", e))?;
+
+        if self.config.fsync {
+            inner
+                .active
+                .sync_data()
+                .map_err(|e| io_error("sync failed", e))?;
+        }
+
+        Ok(())
+    }
+
+    fn rotate(&self, inner: &mut SpoolInner) -> Result<(), ApiError> {
+        let first_seq = inner.next_seq;
+        let path = segment_path(&self.dir, first_seq);
+
+        inner.active = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&path)
+            .map_err(|e| io_error("open failed", e))?;
+
+        inner.segments.push(Segment {
+            first_seq,
+            last_seq: None,
+            path,
+            size: 0,
+        });
+
+        Ok(())
+    }
+
+    /// Records the outcome of delivering `seqs` to `sink`, advancing and
+    /// persisting its cursor if possible and deleting segments every sink is
+    /// done with.
+    pub fn ack(&self, sink: &str, seqs: &[u64], delivered: bool) -> Result<(), ApiError> {
+        let mut inner = self.inner.lock().unwrap();
+        let last_seq = inner.next_seq - 1;
+
+        let Some(cursor) = inner.cursors.get_mut(sink) else {
+            return Ok(());
+        };
+
+        for seq in seqs {
+            cursor.pending.remove(seq);
+            match delivered {
+                true => cursor.failed.remove(seq),
+                false => cursor.failed.insert(*seq),
+            };
+        }
+
+        let position = cursor.position(last_seq);
+        if position > cursor.acked {
+            cursor.acked = position;
+            write_cursor(&self.dir, sink, position)?;
+            self.compact(&mut inner)?;
+        }
+
+        Ok(())
+    }
+
+    fn compact(&self, inner: &mut SpoolInner) -> Result<(), ApiError> {
+        let Some(acked) = inner.cursors.values().map(|c| c.acked).min() else {
+            return Ok(());
+        };
+
+        // Never remove the active segment.
+        while inner.segments.len() > 1 {
+            let segment = &inner.segments[0];
+            if segment.last_seq.is_some_and(|last| last > acked) {
+                break;
+            }
+
+            fs::remove_file(&segment.path).map_err(|e| io_error("remove failed", e))?;
+            tracing::debug!("Removed spool segment {:?}", segment.path);
+
+            inner.size -= segment.size;
+            inner.segments.remove(0);
+        }
+
+        Ok(())
+    }
+
+    /// Takes up to `limit` records that previously failed to reach `sink`
+    /// and marks them as pending again so they can be redelivered.
+    pub fn take_failed(&self, sink: &str, limit: usize) -> Result<Vec<(u64, Record)>, ApiError> {
+        let mut inner = self.inner.lock().unwrap();
+
+        let Some(cursor) = inner.cursors.get_mut(sink) else {
+            return Ok(Vec::new());
+        };
+
+        let seqs: BTreeSet<u64> = cursor.failed.iter().take(limit).copied().collect();
+        for seq in seqs.iter() {
+            cursor.failed.remove(seq);
+            cursor.pending.insert(*seq);
+        }
+
+        let (Some(first), Some(last)) = (seqs.first(), seqs.last()) else {
+            return Ok(Vec::new());
+        };
+
+        let mut records = Vec::with_capacity(seqs.len());
+        for segment in inner.segments.iter() {
+            if segment.first_seq > *last || segment.last_seq.is_none_or(|l| l < *first) {
+                continue;
+            }
+
+            records.extend(
+                read_segment(segment)?
+                    .into_iter()
+                    .filter(|(seq, _)| seqs.contains(seq)),
+            );
+        }
+
+        Ok(records)
+    }
+
+    /// Starts replaying `sink` from its cursor, returning the cursor. The
+    /// cursor won't advance past records that haven't been replayed yet
+    /// until [`Spool::finish_replay`] is called.
+    pub fn start_replay(&self, sink: &str) -> Option<u64> {
+        let mut inner = self.inner.lock().unwrap();
+        let cursor = inner.cursors.get_mut(sink)?;
+        cursor.replay_floor = Some(cursor.acked + 1);
+        Some(cursor.acked)
+    }
+
+    /// Reads the entries after `after` in the first segment that has any.
+    /// Returns an empty page once the end of the spool is reached.
+    pub fn read_after(&self, after: u64) -> Result<Vec<(u64, Record)>, ApiError> {
+        let inner = self.inner.lock().unwrap();
+
+        for segment in inner.segments.iter() {
+            if segment.last_seq.is_none_or(|last| last <= after) {
+                continue;
+            }
+
+            return Ok(read_segment(segment)?
+                .into_iter()
+                .filter(|(seq, _)| *seq > after)
+                .collect());
+        }
+
+        Ok(Vec::new())
+    }
+
+    /// Marks `seqs` as pending for `sink` and moves its replay floor past
+    /// `replayed_through`.
+    pub fn mark_replayed(&self, sink: &str, seqs: &[u64], replayed_through: u64) {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(cursor) = inner.cursors.get_mut(sink) {
+            cursor.pending.extend(seqs);
+            cursor.replay_floor = Some(replayed_through + 1);
+        }
+    }
+
+    pub fn finish_replay(&self, sink: &str) -> Result<(), ApiError> {
+        let mut inner = self.inner.lock().unwrap();
+        if let Some(cursor) = inner.cursors.get_mut(sink) {
+            cursor.replay_floor = None;
+        }
+        drop(inner);
+
+        // Nothing may have been replayed, so the cursor may be able to move.
+        self.ack(sink, &[], true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generated::record_publisher::{record::RecordData, MetadataRecord};
+    use crate::test_util::TempDir;
+
+    fn spool_config(dir: &TempDir) -> SpoolConfig {
+        SpoolConfig {
+            path: dir.path_string(),
+            segment_size_bytes: 256,
+            max_size_bytes: None,
+            fsync: false,
+        }
+    }
+
+    fn record(field_value: &str) -> Record {
+        Record {
+            workspace_id: None,
+            record_data: Some(RecordData::Metadata(MetadataRecord {
+                field_name: "key".to_string(),
+                field_value: field_value.to_string(),
+                ..Default::default()
+            })),
+        }
+    }
+
+    fn routes(sink: &str, n: usize) -> Vec<(String, Vec<usize>)> {
+        vec![(sink.to_string(), (0..n).collect())]
+    }
+
+    #[test]
+    fn test_unacked_records_survive_reopen() {
+        let dir = TempDir::new("spool");
+        let config = spool_config(&dir);
+
+        let spool = Spool::open(config.clone()).unwrap();
+        spool.add_sink("kafka").unwrap();
+        let seqs = spool
+            .append(
+                &[record("a"), record("b"), record("c")],
+                &routes("kafka", 3),
+            )
+            .unwrap();
+        assert_eq!(seqs, vec![1, 2, 3]);
+
+        // Only the first record reaches the sink before the "crash".
+        spool.ack("kafka", &[1], true).unwrap();
+        spool.ack("kafka", &[3], false).unwrap();
+        drop(spool);
+
+        let spool = Spool::open(config.clone()).unwrap();
+        spool.add_sink("kafka").unwrap();
+        let after = spool.start_replay("kafka").unwrap();
+        assert_eq!(after, 1);
+
+        let replayed: Vec<u64> = spool
+            .read_after(after)
+            .unwrap()
+            .into_iter()
+            .map(|(seq, _)| seq)
+            .collect();
+        assert_eq!(replayed, vec![2, 3]);
+    }
+
+    #[test]
+    fn test_torn_write_is_truncated() {
+        let dir = TempDir::new("spool");
+        let config = spool_config(&dir);
+
+        let spool = Spool::open(config.clone()).unwrap();
+        spool.add_sink("stdout").unwrap();
+        spool.append(&[record("a")], &routes("stdout", 1)).unwrap();
+        drop(spool);
+
+        let segment = segment_path(Path::new(&config.path), 1);
+        let mut file = OpenOptions::new().append(true).open(&segment).unwrap();
+        file.write_all(&[42, 0, 0, 0, 1, 2]).unwrap();
+
+        let spool = Spool::open(config.clone()).unwrap();
+        spool.add_sink("stdout").unwrap();
+        assert_eq!(spool.read_after(0).unwrap().len(), 1);
+        assert_eq!(
+            spool.append(&[record("b")], &routes("stdout", 1)).unwrap(),
+            vec![2]
+        );
+        assert_eq!(spool.read_after(0).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_failed_append_is_not_kept() {
+        let dir = TempDir::new("spool");
+        let config = spool_config(&dir);
+
+        let spool = Spool::open(config.clone()).unwrap();
+        spool.add_sink("stdout").unwrap();
+        spool.append(&[record("a")], &routes("stdout", 1)).unwrap();
+
+        // A read-only handle fails both the write and the rollback, so the
+        // spool stops taking appends rather than writing after garbage.
+        let segment = segment_path(Path::new(&config.path), 1);
+        spool.inner.lock().unwrap().active = File::open(&segment).unwrap();
+        assert_eq!(
+            spool.append(&[record("b")], &routes("stdout", 1)),
+            Err(ApiError::InternalError)
+        );
+        spool.inner.lock().unwrap().active =
+            OpenOptions::new().append(true).open(&segment).unwrap();
+        assert_eq!(
+            spool.append(&[record("c")], &routes("stdout", 1)),
+            Err(ApiError::InternalError)
+        );
+        drop(spool);
+
+        let spool = Spool::open(config).unwrap();
+        assert_eq!(spool.read_after(0).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_delivered_segments_are_removed() {
+        let dir = TempDir::new("spool");
+        let config = spool_config(&dir);
+
+        let spool = Spool::open(config.clone()).unwrap();
+        spool.add_sink("stdout").unwrap();
+
+        let mut seqs = Vec::new();
+        for i in 0..20 {
+            seqs.extend(
+                spool
+                    .append(&[record(&i.to_string())], &routes("stdout", 1))
+                    .unwrap(),
+            );
+        }
+        assert!(spool.inner.lock().unwrap().segments.len() > 1);
+
+        spool.ack("stdout", &seqs, true).unwrap();
+        assert_eq!(spool.inner.lock().unwrap().segments.len(), 1);
+
+        // A removed sink no longer holds segments back.
+        spool.add_sink("kafka").unwrap();
+        let seqs: Vec<u64> = (0..20)
+            .flat_map(|i| {
+                let routes = [routes("stdout", 1), routes("kafka", 1)].concat();
+                spool.append(&[record(&i.to_string())], &routes).unwrap()
+            })
+            .collect();
+        spool.ack("stdout", &seqs, true).unwrap();
+        assert!(spool.inner.lock().unwrap().segments.len() > 1);
+
+        spool.remove_sink("kafka").unwrap();
+        assert_eq!(spool.inner.lock().unwrap().segments.len(), 1);
+        assert!(!cursor_path(&spool.dir, "kafka").exists());
+
+        // Failed records are handed back for redelivery.
+        let seqs = spool.append(&[record("x")], &routes("stdout", 1)).unwrap();
+        spool.ack("stdout", &seqs, false).unwrap();
+        let failed = spool.take_failed("stdout", 10).unwrap();
+        assert_eq!(failed.len(), 1);
+        assert_eq!(failed[0].0, seqs[0]);
+    }
+}
@@ -1,28 +1,285 @@
 use super::{
+    audit::{AuditAction, AuditEvent, AuditLog},
+    auth::Authenticator,
     config::PtolemyConfig,
     crypto::PasswordHandler,
     error::ApiError,
-    sink::{configure_sink_registry, sink::SinkRegistry},
+    iam::{
+        api_keys::ApiKeyStore, tokens::RefreshTokenStore, users::UserStore,
+        workspaces::WorkspaceStore,
+    },
+    limits::RateLimiter,
+    sink::{
+        configure_sink_registry_with,
+        sink::{SinkHealth, SinkRegistry},
+        SinkFactories,
+    },
 };
+use arc_swap::ArcSwap;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
 
 pub type PtolemyState = std::sync::Arc<AppState>;
 
+#[derive(Debug, Clone, Serialize)]
+pub struct SinkReadiness {
+    #[serde(flatten)]
+    pub health: SinkHealth,
+    pub required: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SpoolReadiness {
+    pub size_bytes: u64,
+    pub max_size_bytes: Option<u64>,
+    pub full: bool,
+}
+
+/// How the last config reload went.
+#[derive(Debug, Clone, Serialize)]
+pub struct ReloadStatus {
+    pub at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Whether the server can take records, and why not.
+#[derive(Debug, Clone, Serialize)]
+pub struct Readiness {
+    pub ready: bool,
+    /// `None` until the config is first reloaded. A failed reload keeps the
+    /// previous config, so it doesn't make the server unready.
+    pub last_reload: Option<ReloadStatus>,
+    pub accepting: bool,
+    pub spool: Option<SpoolReadiness>,
+    pub sinks: BTreeMap<String, SinkReadiness>,
+}
+
 #[derive(Debug)]
 pub struct AppState {
-    pub config: PtolemyConfig,
+    pub config: ArcSwap<PtolemyConfig>,
     pub password_handler: PasswordHandler,
-    pub sink_registry: SinkRegistry,
+    pub authenticator: ArcSwap<Authenticator>,
+    pub api_keys: Arc<ApiKeyStore>,
+    pub workspaces: WorkspaceStore,
+    pub users: Arc<UserStore>,
+    pub refresh_tokens: RefreshTokenStore,
+    pub audit: AuditLog,
+    pub rate_limiter: RateLimiter,
+    pub sink_registry: ArcSwap<SinkRegistry>,
+    sink_factories: SinkFactories,
+    reload_lock: tokio::sync::Mutex<()>,
+    last_reload: std::sync::Mutex<Option<ReloadStatus>>,
 }
 
 impl AppState {
     pub async fn new(config: PtolemyConfig) -> Result<Self, ApiError> {
+        Self::with_sink_factories(config, SinkFactories::default()).await
+    }
+
+    /// Like `new`, building sinks with `sink_factories` both now and on
+    /// every reload.
+    pub async fn with_sink_factories(
+        config: PtolemyConfig,
+        sink_factories: SinkFactories,
+    ) -> Result<Self, ApiError> {
         let password_handler = super::crypto::PasswordHandler::new();
-        let sink_registry = configure_sink_registry(&config)?;
+
+        let api_keys = Arc::new(ApiKeyStore::open(&config.iam)?);
+        let workspaces = WorkspaceStore::open(&config.iam)?;
+        let users = Arc::new(UserStore::open(&config.iam, password_handler.clone())?);
+        let refresh_tokens = RefreshTokenStore::open(&config.iam)?;
+        let audit = AuditLog::open(&config.audit)?;
+        bootstrap_sysadmin(&config, &users)?;
+
+        let authenticator = Authenticator::from_config(&config, api_keys.clone(), users.clone())?;
+        if !authenticator.is_enabled() {
+            tracing::warn!("Authentication is disabled; anyone can publish records");
+        }
+
+        let sink_registry = configure_sink_registry_with(&config, &sink_factories)?;
+        sink_registry.start(config.health_check_interval()).await?;
 
         Ok(Self {
-            config,
+            config: ArcSwap::from_pointee(config),
             password_handler,
-            sink_registry,
+            authenticator: ArcSwap::from_pointee(authenticator),
+            api_keys,
+            workspaces,
+            users,
+            refresh_tokens,
+            audit,
+            rate_limiter: RateLimiter::default(),
+            sink_registry: ArcSwap::from_pointee(sink_registry),
+            sink_factories,
+            reload_lock: tokio::sync::Mutex::new(()),
+            last_reload: std::sync::Mutex::new(None),
         })
     }
+
+    /// Re-reads the config file and swaps in a sink registry built from it.
+    /// If the new config is invalid, or any new sink fails to start, the
+    /// current config and registry stay in place.
+    pub async fn reload(&self) -> Result<(), ApiError> {
+        let _guard = self.reload_lock.lock().await;
+
+        let result = self.reload_config().await;
+        self.audit
+            .record_result(AuditEvent::new(AuditAction::ConfigReload), &result);
+        *self.last_reload.lock().unwrap() = Some(ReloadStatus {
+            at: Utc::now(),
+            error: result.as_ref().err().map(|e| e.to_string()),
+        });
+
+        result
+    }
+
+    async fn reload_config(&self) -> Result<(), ApiError> {
+        let config = PtolemyConfig::from_file()?;
+        let authenticator =
+            Authenticator::from_config(&config, self.api_keys.clone(), self.users.clone())?;
+        bootstrap_sysadmin(&config, &self.users)?;
+        let current = self.sink_registry.load_full();
+
+        let restart_required = config.restart_required(&self.config.load());
+        if !restart_required.is_empty() {
+            tracing::warn!(
+                "Changes to {} take effect on restart",
+                restart_required.join(", ")
+            );
+        }
+
+        let next = Arc::new(current.reload(&config, &self.sink_factories).await?);
+        next.start_health_checks(config.health_check_interval())
+            .await;
+
+        self.sink_registry.store(next.clone());
+        self.authenticator.store(Arc::new(authenticator));
+        self.config.store(Arc::new(config));
+
+        current.retire(&next).await;
+        tracing::info!("Configuration reloaded.");
+
+        Ok(())
+    }
+
+    /// Ready while accepting records, with the spool below its limit and
+    /// every required sink passing its last health check.
+    pub fn readiness(&self) -> Readiness {
+        let sink_registry = self.sink_registry.load();
+        let health = sink_registry.health();
+
+        let sinks: BTreeMap<_, _> = sink_registry
+            .names()
+            .into_iter()
+            .map(|name| {
+                let readiness = SinkReadiness {
+                    health: health.get(name).cloned().unwrap_or(SinkHealth::Unknown),
+                    required: sink_registry.is_required(name),
+                };
+                (name.to_string(), readiness)
+            })
+            .collect();
+
+        let spool = sink_registry.spool().map(|spool| SpoolReadiness {
+            size_bytes: spool.size(),
+            max_size_bytes: spool.max_size_bytes(),
+            full: spool.is_full(),
+        });
+
+        let accepting = sink_registry.is_accepting();
+
+        let ready = accepting
+            && !spool.as_ref().is_some_and(|s| s.full)
+            && sinks
+                .values()
+                .all(|s| !s.required || s.health == SinkHealth::Healthy);
+
+        Readiness {
+            ready,
+            last_reload: self.last_reload.lock().unwrap().clone(),
+            accepting,
+            spool,
+            sinks,
+        }
+    }
+
+    pub async fn shutdown(&self) {
+        let _guard = self.reload_lock.lock().await;
+        self.sink_registry.load_full().shutdown().await;
+        self.audit.close();
+    }
+}
+
+fn bootstrap_sysadmin(config: &PtolemyConfig, users: &UserStore) -> Result<(), ApiError> {
+    match &config.iam.sysadmin {
+        Some(sysadmin) => {
+            users.bootstrap_sysadmin(&sysadmin.username, &sysadmin.password)?;
+        }
+        None => tracing::warn!("PTOLEMY_USER and PTOLEMY_PASS are not set; no sysadmin"),
+    }
+
+    Ok(())
+}
+
+fn config_modified() -> Option<std::time::SystemTime> {
+    std::fs::metadata(PtolemyConfig::path())
+        .and_then(|m| m.modified())
+        .ok()
+}
+
+/// Reloads the config whenever the config file changes (checked every
+/// `config_poll_interval_secs`) or the process receives SIGHUP.
+pub async fn watch_config(state: PtolemyState) {
+    #[cfg(unix)]
+    let mut sighup = match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup()) {
+        Ok(signal) => Some(signal),
+        Err(e) => {
+            tracing::error!("Failed to install SIGHUP handler: {}", e);
+            None
+        }
+    };
+
+    let mut last_modified = config_modified();
+
+    loop {
+        let poll_interval = state.config.load().config_poll_interval();
+
+        let poll = async {
+            match poll_interval {
+                Some(interval) => tokio::time::sleep(interval).await,
+                None => std::future::pending().await,
+            }
+        };
+
+        #[cfg(unix)]
+        let hangup = async {
+            match sighup.as_mut() {
+                Some(signal) => signal.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        #[cfg(not(unix))]
+        let hangup = std::future::pending::<()>();
+
+        tokio::select! {
+            _ = hangup => tracing::info!("Received SIGHUP, reloading configuration"),
+            _ = poll => {
+                let modified = config_modified();
+                if modified == last_modified {
+                    continue;
+                }
+                tracing::info!("Configuration file changed, reloading");
+            }
+        }
+
+        last_modified = config_modified();
+
+        if let Err(e) = state.reload().await {
+            tracing::error!("Failed to reload configuration: {:?}", e);
+        }
+    }
 }
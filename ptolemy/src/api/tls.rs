@@ -0,0 +1,161 @@
+use super::config::server::TlsConfig;
+use super::error::ApiError;
+
+use std::time::{Duration, SystemTime};
+use tokio::net::TcpStream;
+
+#[cfg(feature = "openssl")]
+use openssl::ssl::{self, SslAcceptor, SslFiletype, SslMethod, SslVerifyMode};
+
+#[cfg(feature = "openssl")]
+pub type TlsStream = tokio_openssl::SslStream<TcpStream>;
+
+#[cfg(not(feature = "openssl"))]
+pub type TlsStream = TcpStream;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Terminates TLS for the server's listeners. The certificate, key and
+/// client CA bundle are re-read whenever one of them changes on disk.
+pub struct TlsAcceptor {
+    config: TlsConfig,
+    #[cfg(feature = "openssl")]
+    acceptor: arc_swap::ArcSwap<SslAcceptor>,
+    #[cfg(not(feature = "openssl"))]
+    never: std::convert::Infallible,
+}
+
+impl std::fmt::Debug for TlsAcceptor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("TlsAcceptor")
+            .field("config", &self.config)
+            .finish_non_exhaustive()
+    }
+}
+
+fn modified(config: &TlsConfig) -> Vec<Option<SystemTime>> {
+    [
+        Some(&config.cert_path),
+        Some(&config.key_path),
+        config.client_ca_path.as_ref(),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|path| std::fs::metadata(path).and_then(|m| m.modified()).ok())
+    .collect()
+}
+
+#[cfg(feature = "openssl")]
+fn build_acceptor(config: &TlsConfig) -> Result<SslAcceptor, ApiError> {
+    let build = || -> Result<SslAcceptor, openssl::error::ErrorStack> {
+        let mut builder = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server())?;
+        builder.set_certificate_chain_file(&config.cert_path)?;
+        builder.set_private_key_file(&config.key_path, SslFiletype::PEM)?;
+        builder.check_private_key()?;
+
+        builder.set_alpn_select_callback(|_, client| {
+            ssl::select_next_proto(b"\x02h2\x08http/1.1", client).ok_or(ssl::AlpnError::NOACK)
+        });
+
+        if let Some(ca_path) = &config.client_ca_path {
+            builder.set_ca_file(ca_path)?;
+            builder.set_client_ca_list(openssl::x509::X509Name::load_client_ca_file(ca_path)?);
+
+            let mode = match config.require_client_cert {
+                true => SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT,
+                false => SslVerifyMode::PEER,
+            };
+            builder.set_verify(mode);
+        }
+
+        Ok(builder.build())
+    };
+
+    build().map_err(|e| {
+        tracing::error!("Failed to load TLS certificates: {}", e);
+        ApiError::ConfigError
+    })
+}
+
+impl TlsAcceptor {
+    #[cfg(feature = "openssl")]
+    pub fn new(config: TlsConfig) -> Result<Self, ApiError> {
+        let acceptor = build_acceptor(&config)?;
+
+        Ok(Self {
+            config,
+            acceptor: arc_swap::ArcSwap::from_pointee(acceptor),
+        })
+    }
+
+    #[cfg(not(feature = "openssl"))]
+    pub fn new(_config: TlsConfig) -> Result<Self, ApiError> {
+        tracing::error!("TLS is configured but Ptolemy was built without the openssl feature");
+        Err(ApiError::ConfigError)
+    }
+
+    /// Rebuilds the acceptor from the files on disk. Connections already
+    /// established keep their certificates; if loading fails, the current
+    /// ones stay in use.
+    pub fn reload(&self) -> Result<(), ApiError> {
+        #[cfg(feature = "openssl")]
+        {
+            self.acceptor.store(build_acceptor(&self.config)?.into());
+            tracing::info!("Reloaded TLS certificates.");
+            Ok(())
+        }
+
+        #[cfg(not(feature = "openssl"))]
+        match self.never {}
+    }
+
+    /// Polls the certificate files every `reload_interval_secs` and reloads
+    /// them when any of them changes.
+    pub async fn watch(self: std::sync::Arc<Self>) {
+        let Some(interval) = self.config.reload_interval() else {
+            return;
+        };
+
+        let mut last_modified = modified(&self.config);
+        loop {
+            tokio::time::sleep(interval).await;
+
+            let current = modified(&self.config);
+            if current == last_modified {
+                continue;
+            }
+            last_modified = current;
+
+            if let Err(e) = self.reload() {
+                tracing::error!("Failed to reload TLS certificates: {:?}", e);
+            }
+        }
+    }
+
+    pub async fn accept(&self, stream: TcpStream) -> std::io::Result<TlsStream> {
+        #[cfg(feature = "openssl")]
+        {
+            use std::pin::Pin;
+
+            let acceptor = self.acceptor.load_full();
+            let ssl = ssl::Ssl::new(acceptor.context()).map_err(std::io::Error::other)?;
+            let mut stream =
+                tokio_openssl::SslStream::new(ssl, stream).map_err(std::io::Error::other)?;
+
+            tokio::time::timeout(HANDSHAKE_TIMEOUT, Pin::new(&mut stream).accept())
+                .await
+                .map_err(|_| {
+                    std::io::Error::new(std::io::ErrorKind::TimedOut, "TLS handshake timed out")
+                })?
+                .map_err(std::io::Error::other)?;
+
+            Ok(stream)
+        }
+
+        #[cfg(not(feature = "openssl"))]
+        {
+            let _ = (stream, HANDSHAKE_TIMEOUT);
+            match self.never {}
+        }
+    }
+}
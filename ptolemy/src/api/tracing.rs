@@ -1,9 +1,40 @@
+use super::config::{
+    logging::{LogFileConfig, LogFormat, LogRotation, LoggingConfig},
+    telemetry::TelemetryConfig,
+};
+use super::error::ApiError;
+
+use axum::extract::MatchedPath;
+use http::{HeaderMap, Request, StatusCode};
+use opentelemetry::{
+    propagation::Extractor,
+    trace::{TraceContextExt, TraceId, TracerProvider as _},
+    KeyValue,
+};
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{Sampler, TracerProvider},
+    Resource,
+};
+use tracing::{field::Empty, Span};
+use tracing_appender::{
+    non_blocking::WorkerGuard,
+    rolling::{RollingFileAppender, Rotation},
+};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::{
+    fmt::MakeWriter, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer, Registry,
+};
+
 #[macro_export]
 macro_rules! trace_layer {
-    (Http) => { $crate::trace_layer!(new_for_http) };
-    // (Grpc) => { crate::trace_layer!(new_for_grpc) };
-    ($type:ident) => {
+    (Http) => { $crate::trace_layer!(new_for_http, $crate::api::tracing::http_span) };
+    (Grpc) => { $crate::trace_layer!(new_for_grpc, $crate::api::tracing::grpc_span) };
+    ($type:ident, $make_span:path) => {
         tower_http::trace::TraceLayer::$type()
+            .make_span_with($make_span)
             .on_request(|request: &axum::http::Request<_>, _: &_| {
                 tracing::info!(
                     method = %request.method(),
@@ -12,16 +43,320 @@ macro_rules! trace_layer {
                 );
             })
             .on_response(
-                tower_http::trace::DefaultOnResponse::new()
-                    .level(tracing::Level::INFO)
-                    .latency_unit(tower_http::LatencyUnit::Micros),
+                |response: &axum::http::Response<_>, latency, span: &tracing::Span| {
+                    $crate::api::tracing::record_response(
+                        response.status(),
+                        response.headers(),
+                        span,
+                    );
+                    tower_http::trace::OnResponse::on_response(
+                        tower_http::trace::DefaultOnResponse::new()
+                            .level(tracing::Level::INFO)
+                            .latency_unit(tower_http::LatencyUnit::Micros),
+                        response,
+                        latency,
+                        span,
+                    )
+                },
             )
             .on_body_chunk(tower_http::trace::DefaultOnBodyChunk::new())
             .on_eos(
-                tower_http::trace::DefaultOnEos::new()
-                    .level(tracing::Level::INFO)
-                    .latency_unit(tower_http::LatencyUnit::Micros),
+                |trailers: Option<&axum::http::HeaderMap>, duration, span: &tracing::Span| {
+                    $crate::api::tracing::record_grpc_status(trailers, span);
+                    tower_http::trace::OnEos::on_eos(
+                        tower_http::trace::DefaultOnEos::new()
+                            .level(tracing::Level::INFO)
+                            .latency_unit(tower_http::LatencyUnit::Micros),
+                        trailers,
+                        duration,
+                        span,
+                    )
+                },
             )
             .on_failure(tower_http::trace::DefaultOnFailure::new().level(tracing::Level::ERROR))
     };
 }
+
+pub const REQUEST_ID: &str = "x-request-id";
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+/// Set by `SetRequestIdLayer` when the client didn't send one.
+fn request_id<B>(request: &Request<B>) -> &str {
+    request
+        .headers()
+        .get(REQUEST_ID)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default()
+}
+
+/// Continues the trace named by the request's `traceparent` header, if any,
+/// and records its id so log lines can be matched up with exported spans.
+fn continue_trace(span: &Span, headers: &HeaderMap) {
+    let parent = opentelemetry::global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(headers))
+    });
+    span.set_parent(parent);
+
+    let trace_id = span.context().span().span_context().trace_id();
+    if trace_id != TraceId::INVALID {
+        span.record("trace_id", trace_id.to_string());
+    }
+}
+
+pub fn http_span<B>(request: &Request<B>) -> Span {
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or(request.uri().path(), |path| path.as_str());
+
+    let span = tracing::info_span!(
+        "request",
+        otel.name = format!("{} {}", request.method(), route),
+        otel.kind = "server",
+        otel.status_code = Empty,
+        http.request.method = %request.method(),
+        http.route = route,
+        url.path = request.uri().path(),
+        http.response.status_code = Empty,
+        records = Empty,
+        request_id = request_id(request),
+        trace_id = Empty,
+    );
+    continue_trace(&span, request.headers());
+    span
+}
+
+pub fn grpc_span<B>(request: &Request<B>) -> Span {
+    let path = request.uri().path().trim_start_matches('/');
+    let (service, method) = path.split_once('/').unwrap_or((path, ""));
+
+    let span = tracing::info_span!(
+        "grpc",
+        otel.name = path,
+        otel.kind = "server",
+        otel.status_code = Empty,
+        rpc.system = "grpc",
+        rpc.service = service,
+        rpc.method = method,
+        rpc.grpc.status_code = Empty,
+        records = Empty,
+        request_id = request_id(request),
+        trace_id = Empty,
+    );
+    continue_trace(&span, request.headers());
+    span
+}
+
+/// Records the HTTP status, and the gRPC status when a call fails before
+/// sending a body, which puts it in the headers instead of the trailers.
+pub fn record_response(status: StatusCode, headers: &HeaderMap, span: &Span) {
+    span.record("http.response.status_code", status.as_u16());
+    if status.is_server_error() {
+        span.record("otel.status_code", "ERROR");
+    }
+
+    record_grpc_status(Some(headers), span);
+}
+
+pub fn record_grpc_status(headers: Option<&HeaderMap>, span: &Span) {
+    let Some(code) = headers
+        .and_then(|h| h.get("grpc-status"))
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<i32>().ok())
+    else {
+        return;
+    };
+
+    span.record("rpc.grpc.status_code", code);
+    if code != 0 {
+        span.record("otel.status_code", "ERROR");
+    }
+}
+
+fn log_file(config: &LogFileConfig) -> Result<RollingFileAppender, Box<dyn std::error::Error>> {
+    // Pruning old files complains if the directory doesn't exist yet.
+    std::fs::create_dir_all(&config.directory)?;
+
+    let rotation = match config.rotation {
+        LogRotation::Minutely => Rotation::MINUTELY,
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+
+    let mut builder = RollingFileAppender::builder()
+        .rotation(rotation)
+        .filename_prefix(&config.prefix);
+    if let Some(max_files) = config.max_files {
+        builder = builder.max_log_files(max_files);
+    }
+
+    Ok(builder.build(&config.directory)?)
+}
+
+fn log_layer<W>(format: LogFormat, writer: W, ansi: bool) -> Box<dyn Layer<Registry> + Send + Sync>
+where
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+
+    match format {
+        LogFormat::Text => layer.boxed(),
+        LogFormat::Pretty => layer.pretty().boxed(),
+        LogFormat::Json => layer.json().boxed(),
+    }
+}
+
+/// The server's own logs and spans. Spans go to OpenTelemetry as well as the
+/// log, which is what lets requests join the trace in their `traceparent`.
+pub struct Telemetry {
+    provider: TracerProvider,
+    log_guard: Option<WorkerGuard>,
+}
+
+impl Telemetry {
+    /// Installs the global subscriber: log lines as set in `logging`, and
+    /// spans filtered by `telemetry.filter`, exported when `otlp_endpoint`
+    /// is set. If either is invalid, logs why to stdout and fails.
+    pub fn init(logging: &LoggingConfig, telemetry: &TelemetryConfig) -> Result<Self, ApiError> {
+        let mut errors = Vec::new();
+
+        let log_filter = match std::env::var("RUST_LOG") {
+            Ok(directives) => EnvFilter::try_new(directives),
+            Err(_) => EnvFilter::try_new(&logging.filter),
+        }
+        .map_err(|e| errors.push(format!("Invalid logging.filter: {}", e)));
+        let log_file = logging
+            .file
+            .as_ref()
+            .map(log_file)
+            .transpose()
+            .map_err(|e| errors.push(format!("Failed to open log file: {}", e)));
+        let exporter = telemetry
+            .otlp_endpoint
+            .as_ref()
+            .map(|endpoint| {
+                opentelemetry_otlp::SpanExporter::builder()
+                    .with_tonic()
+                    .with_endpoint(endpoint)
+                    .build()
+            })
+            .transpose()
+            .map_err(|e| errors.push(format!("Invalid telemetry.otlp_endpoint: {}", e)));
+        let span_filter = EnvFilter::try_new(&telemetry.filter)
+            .map_err(|e| errors.push(format!("Invalid telemetry.filter: {}", e)));
+
+        let (Ok(log_filter), Ok(log_file), Ok(exporter), Ok(span_filter)) =
+            (log_filter, log_file, exporter, span_filter)
+        else {
+            tracing_subscriber::fmt().init();
+            for error in errors {
+                tracing::error!("{}", error);
+            }
+            return Err(ApiError::ConfigError);
+        };
+
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+
+        let mut provider = TracerProvider::builder()
+            .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+                telemetry.sample_ratio,
+            ))))
+            .with_resource(Resource::new([KeyValue::new(
+                "service.name",
+                telemetry.service_name.clone(),
+            )]));
+        if let Some(exporter) = exporter {
+            provider = provider.with_batch_exporter(exporter, runtime::Tokio);
+        }
+        let provider = provider.build();
+
+        let mut log_layers = vec![log_layer(logging.format, std::io::stdout, true)];
+        let log_guard = log_file.map(|appender| {
+            let (writer, guard) = tracing_appender::non_blocking(appender);
+            log_layers.push(log_layer(logging.format, writer, false));
+            guard
+        });
+
+        tracing_subscriber::registry()
+            .with(log_layers.with_filter(log_filter))
+            .with(
+                tracing_opentelemetry::layer()
+                    .with_tracer(provider.tracer("ptolemy"))
+                    .with_filter(span_filter),
+            )
+            .init();
+
+        if let Some(endpoint) = &telemetry.otlp_endpoint {
+            tracing::info!("Exporting spans to {}", endpoint);
+        }
+
+        Ok(Self {
+            provider,
+            log_guard,
+        })
+    }
+
+    /// Flushes spans that haven't been exported yet, then log lines that
+    /// haven't been written to the log file.
+    pub async fn shutdown(self) {
+        let Self {
+            provider,
+            log_guard,
+        } = self;
+
+        match tokio::task::spawn_blocking(move || provider.shutdown()).await {
+            Ok(Err(e)) => tracing::error!("Failed to flush spans: {}", e),
+            Err(e) => tracing::error!("Failed to flush spans: {}", e),
+            Ok(Ok(())) => {}
+        }
+
+        drop(log_guard);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_continues_traceparent() {
+        opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+        let provider = TracerProvider::builder().build();
+        let subscriber = tracing_subscriber::registry()
+            .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("test")));
+
+        let request = Request::builder()
+            .uri("/ptolemy.RecordPublisher/Publish")
+            .header(
+                "traceparent",
+                "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            )
+            .body(())
+            .unwrap();
+
+        tracing::subscriber::with_default(subscriber, || {
+            let context = grpc_span(&request).context();
+            let span_context = context.span().span_context().clone();
+
+            assert_eq!(
+                span_context.trace_id().to_string(),
+                "4bf92f3577b34da6a3ce929d0e0e4736"
+            );
+            assert!(span_context.is_sampled());
+        });
+    }
+}
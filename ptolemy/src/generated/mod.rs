@@ -1,3 +1,10 @@
 #![allow(clippy::all)]
 
 pub mod record_publisher;
+
+/// Encoded descriptors of `record_publisher.proto`, for gRPC reflection.
+pub const RECORD_PUBLISHER_DESCRIPTOR_SET: &[u8] = include_bytes!("record_publisher_descriptor.bin");
+
+/// Encoded descriptors of the OTLP trace collector service, with its imports,
+/// built from the protos vendored in `proto/opentelemetry`.
+pub const OTLP_TRACE_DESCRIPTOR_SET: &[u8] = include_bytes!("otlp_trace_descriptor.bin");
@@ -22,6 +22,9 @@ pub struct RecordPublishJob {
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct Record {
+    /// Set by the server from the credentials the record was published with.
+    #[prost(string, optional, tag = "10")]
+    pub workspace_id: ::core::option::Option<::prost::alloc::string::String>,
     #[prost(oneof = "record::RecordData", tags = "4, 5, 6, 7, 8, 9")]
     pub record_data: ::core::option::Option<record::RecordData>,
 }
@@ -80,6 +83,13 @@ pub struct RuntimeRecord {
     pub error_type: ::core::option::Option<::prost::alloc::string::String>,
     #[prost(string, optional, tag = "8")]
     pub error_content: ::core::option::Option<::prost::alloc::string::String>,
+    /// Microseconds since the Unix epoch. Used instead of the float seconds
+    /// above when set, as a float can't hold sub-second times at current
+    /// epochs.
+    #[prost(int64, optional, tag = "9")]
+    pub start_time_us: ::core::option::Option<i64>,
+    #[prost(int64, optional, tag = "10")]
+    pub end_time_us: ::core::option::Option<i64>,
 }
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct InputRecord {
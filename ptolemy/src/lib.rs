@@ -4,5 +4,8 @@ pub mod models;
 pub mod prelude;
 pub mod writer;
 
+#[cfg(test)]
+pub(crate) mod test_util;
+
 #[rustfmt::skip]
 pub mod generated;
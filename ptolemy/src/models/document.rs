@@ -0,0 +1,208 @@
+use super::record::{Feedback, Input, Output, IOF};
+use crate::error::ParseError;
+use crate::models::{Event, Id, Metadata, Record, Runtime, Tier, JSON};
+use chrono::NaiveDateTime;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DocumentEvent {
+    #[serde(default)]
+    pub id: Option<Id>,
+    #[serde(default)]
+    pub subject_id: Option<Id>,
+    #[serde(default)]
+    pub parent_id: Option<Id>,
+    pub name: String,
+    #[serde(default)]
+    pub parameters: Option<JSON>,
+    #[serde(default)]
+    pub version: Option<String>,
+    #[serde(default)]
+    pub environment: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct DocumentRuntime {
+    pub start_time: NaiveDateTime,
+    pub end_time: NaiveDateTime,
+    #[serde(default)]
+    pub error_type: Option<String>,
+    #[serde(default)]
+    pub error_content: Option<String>,
+}
+
+/// One event with its runtime, IO, feedback and metadata nested inside it,
+/// as in `docs/sample/sample_log.json`. Timestamps are ISO 8601 in UTC.
+#[derive(Debug, Clone, Deserialize)]
+pub struct TraceDocument {
+    pub tier: Tier,
+    pub event: DocumentEvent,
+    pub runtime: DocumentRuntime,
+    #[serde(default)]
+    pub inputs: BTreeMap<String, JSON>,
+    #[serde(default)]
+    pub outputs: BTreeMap<String, JSON>,
+    #[serde(default)]
+    pub feedback: BTreeMap<String, JSON>,
+    #[serde(default)]
+    pub metadata: BTreeMap<String, String>,
+}
+
+fn new_id() -> Id {
+    Uuid::new_v4().into()
+}
+
+impl TraceDocument {
+    /// Expands the document into an event followed by its other records,
+    /// generating any ids it leaves out. Without a subject, the event's parent
+    /// is taken as the subject, as the client does for top level events.
+    pub fn into_records(self) -> Result<Vec<Record>, ParseError> {
+        let TraceDocument {
+            tier,
+            event,
+            runtime,
+            inputs,
+            outputs,
+            feedback,
+            metadata,
+        } = self;
+
+        let event_id = event.id.unwrap_or_else(new_id);
+        let (subject_id, parent_id) = match (event.subject_id, event.parent_id) {
+            (Some(subject_id), Some(parent_id)) => (subject_id, parent_id),
+            (Some(id), None) | (None, Some(id)) => (id, id),
+            (None, None) => {
+                let id = new_id();
+                (id, id)
+            }
+        };
+
+        if runtime.end_time < runtime.start_time {
+            return Err(ParseError::BadTimestamp);
+        }
+
+        let mut records = vec![
+            Record::Event(Event {
+                tier: tier.clone(),
+                subject_id,
+                parent_id,
+                id: event_id,
+                name: event.name,
+                parameters: event.parameters,
+                version: event.version,
+                environment: event.environment,
+            }),
+            Record::Runtime(Runtime {
+                tier: tier.clone(),
+                subject_id,
+                event_id,
+                id: new_id(),
+                start_time: runtime.start_time,
+                end_time: runtime.end_time,
+                error_type: runtime.error_type,
+                error_content: runtime.error_content,
+            }),
+        ];
+
+        // Null fields are left out, as if they weren't set.
+        let iof = |fields: BTreeMap<String, JSON>| {
+            fields
+                .into_iter()
+                .filter(|(_, value)| !value.0.is_null())
+                .map(|(name, value)| {
+                    IOF::from_json(tier.clone(), subject_id, event_id, new_id(), name, value)
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+
+        records.extend(iof(inputs)?.into_iter().map(|i| Record::Input(Input(i))));
+        records.extend(iof(outputs)?.into_iter().map(|o| Record::Output(Output(o))));
+        records.extend(
+            iof(feedback)?
+                .into_iter()
+                .map(|f| Record::Feedback(Feedback(f))),
+        );
+        records.extend(metadata.into_iter().map(|(field_name, field_value)| {
+            Record::Metadata(Metadata {
+                tier: tier.clone(),
+                subject_id,
+                event_id,
+                id: new_id(),
+                field_name,
+                field_value,
+            })
+        }));
+
+        Ok(records)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::generated::record_publisher;
+    use crate::models::RecordType;
+
+    #[test]
+    fn test_sample_documents() {
+        let doc: TraceDocument =
+            serde_json::from_str(include_str!("../../../docs/sample/sample_log.json")).unwrap();
+        let event_id = doc.event.id.unwrap();
+        let parent_id = doc.event.parent_id.unwrap();
+        let (n_inputs, n_outputs, n_feedback, n_metadata) = (
+            doc.inputs.len(),
+            doc.outputs.len(),
+            doc.feedback.len(),
+            doc.metadata.len(),
+        );
+
+        let records = doc.into_records().unwrap();
+        assert_eq!(
+            records.len(),
+            2 + n_inputs + n_outputs + n_feedback + n_metadata
+        );
+        assert!(matches!(
+            &records[0],
+            Record::Event(e) if e.id == event_id && e.parent_id == parent_id && e.subject_id == parent_id
+        ));
+        assert_eq!(records[1].record_type(), RecordType::Runtime);
+
+        // The runtime is published with the document's exact timestamps.
+        let published: Vec<Record> = records
+            .iter()
+            .map(|r| {
+                let proto = record_publisher::Record::try_from(r.clone()).unwrap();
+                proto.try_into().unwrap()
+            })
+            .collect();
+        let time = |s: &str| s.parse::<NaiveDateTime>().unwrap();
+        assert!(matches!(
+            &published[1],
+            Record::Runtime(r) if r.start_time == time("2024-12-11T16:12:37.561243")
+                && r.end_time == time("2024-12-11T16:12:50.231619")
+        ));
+        assert_eq!(
+            records
+                .iter()
+                .filter(|r| r.record_type() == RecordType::Input)
+                .count(),
+            n_inputs
+        );
+
+        // Every generated id is distinct.
+        let mut ids: Vec<_> = records.iter().map(|r| r.id().as_uuid()).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), records.len());
+
+        let doc: TraceDocument =
+            serde_json::from_str(include_str!("../../../docs/sample/sample_log_error.json"))
+                .unwrap();
+        assert!(matches!(
+            &doc.into_records().unwrap()[1],
+            Record::Runtime(r) if r.error_type.as_deref() == Some("ValueError")
+        ));
+    }
+}
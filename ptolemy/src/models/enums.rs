@@ -19,6 +19,22 @@ serialize_enum!(
     [String, Int, Float, Bool, JSON, Null]
 );
 
+#[derive(Clone, Debug, PartialEq)]
+pub enum RecordType {
+    Event,
+    Runtime,
+    Input,
+    Output,
+    Feedback,
+    Metadata,
+}
+
+serialize_enum!(
+    RecordType,
+    SnakeCase,
+    [Event, Runtime, Input, Output, Feedback, Metadata]
+);
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum Tier {
     System,
@@ -1,9 +1,11 @@
+mod document;
 mod enums;
 mod id;
 mod json;
 mod record;
 
-pub use enums::{FieldValueType, Tier};
+pub use document::{DocumentEvent, DocumentRuntime, TraceDocument};
+pub use enums::{FieldValueType, RecordType, Tier};
 pub use id::Id;
 pub use json::JSON;
-pub use record::{Event, Metadata, Record, Runtime, IOF};
+pub use record::{Event, Metadata, PublishedRecord, Record, Runtime, IOF};
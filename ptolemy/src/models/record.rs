@@ -1,12 +1,12 @@
 use super::super::error::ParseError;
 use crate::{
     generated::record_publisher::{self, record::RecordData},
-    models::{FieldValueType, Id, Tier, JSON},
+    models::{FieldValueType, Id, RecordType, Tier, JSON},
 };
 use chrono::{naive::serde::ts_microseconds, DateTime, NaiveDateTime};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "record_type", rename_all = "lowercase")]
 pub enum Record {
     Event(Event),
@@ -28,6 +28,17 @@ impl Record {
             Record::Metadata(m) => m.id,
         }
     }
+
+    pub fn record_type(&self) -> RecordType {
+        match self {
+            Record::Event(_) => RecordType::Event,
+            Record::Runtime(_) => RecordType::Runtime,
+            Record::Input(_) => RecordType::Input,
+            Record::Output(_) => RecordType::Output,
+            Record::Feedback(_) => RecordType::Feedback,
+            Record::Metadata(_) => RecordType::Metadata,
+        }
+    }
 }
 
 impl TryFrom<record_publisher::Record> for Record {
@@ -45,7 +56,95 @@ impl TryFrom<record_publisher::Record> for Record {
     }
 }
 
+impl TryFrom<Record> for record_publisher::Record {
+    type Error = ParseError;
+
+    fn try_from(value: Record) -> Result<Self, Self::Error> {
+        let record_data = match value {
+            Record::Event(e) => RecordData::Event(record_publisher::EventRecord {
+                tier: e.tier.proto().into(),
+                subject_id: e.subject_id.to_string(),
+                parent_id: e.parent_id.to_string(),
+                id: e.id.to_string(),
+                name: e.name,
+                parameters: e.parameters.map(Into::into),
+                version: e.version,
+                environment: e.environment,
+            }),
+            Record::Runtime(r) => RecordData::Runtime(record_publisher::RuntimeRecord {
+                tier: r.tier.proto().into(),
+                subject_id: r.subject_id.to_string(),
+                event_id: r.event_id.to_string(),
+                id: r.id.to_string(),
+                start_time: unix_timestamp(r.start_time),
+                end_time: unix_timestamp(r.end_time),
+                error_type: r.error_type,
+                error_content: r.error_content,
+                start_time_us: Some(r.start_time.and_utc().timestamp_micros()),
+                end_time_us: Some(r.end_time.and_utc().timestamp_micros()),
+            }),
+            Record::Input(i) => RecordData::Input(record_publisher::InputRecord {
+                tier: i.tier.proto().into(),
+                subject_id: i.subject_id.to_string(),
+                event_id: i.event_id.to_string(),
+                id: i.id.to_string(),
+                field_value: Some(i.field_value()?),
+                field_name: i.0.field_name,
+            }),
+            Record::Output(o) => RecordData::Output(record_publisher::OutputRecord {
+                tier: o.tier.proto().into(),
+                subject_id: o.subject_id.to_string(),
+                event_id: o.event_id.to_string(),
+                id: o.id.to_string(),
+                field_value: Some(o.field_value()?),
+                field_name: o.0.field_name,
+            }),
+            Record::Feedback(f) => RecordData::Feedback(record_publisher::FeedbackRecord {
+                tier: f.tier.proto().into(),
+                subject_id: f.subject_id.to_string(),
+                event_id: f.event_id.to_string(),
+                id: f.id.to_string(),
+                field_value: Some(f.field_value()?),
+                field_name: f.0.field_name,
+            }),
+            Record::Metadata(m) => RecordData::Metadata(record_publisher::MetadataRecord {
+                tier: m.tier.proto().into(),
+                subject_id: m.subject_id.to_string(),
+                event_id: m.event_id.to_string(),
+                id: m.id.to_string(),
+                field_name: m.field_name,
+                field_value: m.field_value,
+            }),
+        };
+
+        Ok(record_publisher::Record {
+            workspace_id: None,
+            record_data: Some(record_data),
+        })
+    }
+}
+
+/// A record along with the workspace it was published to.
 #[derive(Debug, Clone, Serialize)]
+pub struct PublishedRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub workspace_id: Option<String>,
+    #[serde(flatten)]
+    pub record: Record,
+}
+
+impl TryFrom<record_publisher::Record> for PublishedRecord {
+    type Error = ParseError;
+
+    fn try_from(mut value: record_publisher::Record) -> Result<Self, Self::Error> {
+        Ok(Self {
+            workspace_id: value.workspace_id.take(),
+            record: value.try_into()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Event {
     pub tier: Tier,
     pub subject_id: Id,
@@ -86,7 +185,7 @@ impl TryFrom<record_publisher::EventRecord> for Event {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Runtime {
     pub tier: Tier,
     pub subject_id: Id,
@@ -118,15 +217,15 @@ impl TryFrom<record_publisher::RuntimeRecord> for Runtime {
                 .try_into()
                 .map_err(|_| ParseError::InvalidUuid)?,
             id: value.id.try_into().map_err(|_| ParseError::UndefinedTier)?,
-            start_time: datetime_from_unix_timestamp(value.start_time)?,
-            end_time: datetime_from_unix_timestamp(value.end_time)?,
+            start_time: runtime_timestamp(value.start_time_us, value.start_time)?,
+            end_time: runtime_timestamp(value.end_time_us, value.end_time)?,
             error_type: value.error_type,
             error_content: value.error_content,
         })
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct IOF {
     pub tier: Tier,
     pub subject_id: Id,
@@ -141,9 +240,9 @@ pub struct IOF {
     pub field_value_json: Option<JSON>,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(transparent)]
-pub struct Input(IOF);
+pub struct Input(pub(super) IOF);
 
 impl std::ops::Deref for Input {
     type Target = IOF;
@@ -176,9 +275,9 @@ impl TryFrom<record_publisher::InputRecord> for Input {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(transparent)]
-pub struct Output(IOF);
+pub struct Output(pub(super) IOF);
 
 impl std::ops::Deref for Output {
     type Target = IOF;
@@ -211,9 +310,9 @@ impl TryFrom<record_publisher::OutputRecord> for Output {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(transparent)]
-pub struct Feedback(IOF);
+pub struct Feedback(pub(super) IOF);
 
 impl std::ops::Deref for Feedback {
     type Target = IOF;
@@ -247,6 +346,22 @@ impl TryFrom<record_publisher::FeedbackRecord> for Feedback {
 }
 
 impl IOF {
+    /// The value in the field matching `field_value_type`.
+    pub fn field_value(&self) -> Result<prost_types::Value, ParseError> {
+        let value = match self.field_value_type {
+            FieldValueType::String => self.field_value_str.clone().map(serde_json::Value::from),
+            FieldValueType::Int => self.field_value_int.map(serde_json::Value::from),
+            FieldValueType::Float => self.field_value_float.map(serde_json::Value::from),
+            FieldValueType::Bool => self.field_value_bool.map(serde_json::Value::from),
+            FieldValueType::JSON => self.field_value_json.clone().map(|j| j.0),
+            FieldValueType::Null => return Err(ParseError::UnexpectedNull),
+        };
+
+        value
+            .map(|v| JSON(v).into())
+            .ok_or(ParseError::MissingField)
+    }
+
     fn new(
         tier: Tier,
         subject_id: Id,
@@ -260,6 +375,17 @@ impl IOF {
             .try_into()
             .map_err(|_| ParseError::BadJSON)?;
 
+        Self::from_json(tier, subject_id, event_id, id, field_name, field_value)
+    }
+
+    pub(super) fn from_json(
+        tier: Tier,
+        subject_id: Id,
+        event_id: Id,
+        id: Id,
+        field_name: String,
+        field_value: JSON,
+    ) -> Result<Self, ParseError> {
         let field_value_type = field_value.field_value_type();
 
         let (
@@ -311,7 +437,7 @@ impl IOF {
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Metadata {
     pub tier: Tier,
     pub subject_id: Id,
@@ -345,6 +471,26 @@ impl TryFrom<record_publisher::MetadataRecord> for Metadata {
     }
 }
 
+fn unix_timestamp(t: NaiveDateTime) -> f32 {
+    (t.and_utc().timestamp_micros() as f64 / 1e6) as f32
+}
+
+/// Prefers microseconds, falling back to the float seconds clients that
+/// predate them send.
+fn runtime_timestamp(micros: Option<i64>, seconds: f32) -> Result<NaiveDateTime, ParseError> {
+    let Some(micros) = micros else {
+        return datetime_from_unix_timestamp(seconds);
+    };
+
+    match DateTime::from_timestamp_micros(micros) {
+        Some(t) => Ok(t.naive_utc()),
+        None => {
+            tracing::error!("Invalid timestamp: {}us", micros);
+            Err(ParseError::BadTimestamp)
+        }
+    }
+}
+
 fn datetime_from_unix_timestamp(ts: f32) -> Result<NaiveDateTime, ParseError> {
     match DateTime::from_timestamp(ts.trunc() as i64, (ts.fract() * 1e9) as u32) {
         Some(t) => Ok(t.naive_utc()),
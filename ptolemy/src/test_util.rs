@@ -0,0 +1,26 @@
+use std::path::{Path, PathBuf};
+
+/// A uniquely named path under the system temp dir, removed with everything
+/// in it on drop. The directory isn't created, as most stores make their own.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(prefix: &str) -> Self {
+        Self(std::env::temp_dir().join(format!("ptolemy-{}-{}", prefix, uuid::Uuid::new_v4())))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// The path as a string, for config fields.
+    pub fn path_string(&self) -> String {
+        self.0.to_string_lossy().to_string()
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
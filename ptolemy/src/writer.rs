@@ -1,9 +1,40 @@
-use serde::Serialize;
-use tokio::sync::mpsc;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::future::Future;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot, Mutex, Notify};
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
+
+pub type BatchResult = Result<(), WriterError>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum WriterError {
+    Closed,
+    BatchFailed(String),
+    OverflowFull,
+    OverflowFailed(String),
+}
+
+impl std::fmt::Display for WriterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WriterError::Closed => write!(f, "writer is closed"),
+            WriterError::BatchFailed(e) => write!(f, "batch failed: {}", e),
+            WriterError::OverflowFull => write!(f, "overflow is full"),
+            WriterError::OverflowFailed(e) => write!(f, "failed to spill to disk: {}", e),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub enum Message<T> {
     Write(T),
+    WriteAck(T, oneshot::Sender<BatchResult>),
     Flush,
     Shutdown,
 }
@@ -11,7 +42,7 @@ pub enum Message<T> {
 impl<T> Message<T> {
     pub fn unwrap(self) -> T {
         match self {
-            Message::Write(t) => t,
+            Message::Write(t) | Message::WriteAck(t, _) => t,
             _ => panic!("This should never happen!!!"),
         }
     }
@@ -26,42 +57,463 @@ where
         S: serde::Serializer,
     {
         match self {
-            Message::Write(t) => t.serialize(serializer),
+            Message::Write(t) | Message::WriteAck(t, _) => t.serialize(serializer),
             Message::Shutdown => serializer.serialize_str("shutdown"),
             Message::Flush => serializer.serialize_str("flush"),
         }
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct WriterConfig {
+    /// Capacity of the channel between `write` callers and the writer task.
+    pub buffer_size: usize,
+    /// Number of buffered records that triggers a flush.
+    pub batch_size: usize,
+    /// Maximum time a record may sit in the buffer before it is flushed.
+    /// `None` disables time-based flushing.
+    pub flush_interval: Option<Duration>,
+}
+
+impl Default for WriterConfig {
+    fn default() -> Self {
+        Self {
+            buffer_size: 1024,
+            batch_size: 100,
+            flush_interval: Some(Duration::from_millis(500)),
+        }
+    }
+}
+
+/// Records waiting to be handed to the batch handler, along with the
+/// senders of any callers waiting on the result of that batch.
+struct Batch<T> {
+    records: Vec<T>,
+    acks: Vec<oneshot::Sender<BatchResult>>,
+    deadline: Option<Instant>,
+}
+
+impl<T> Batch<T> {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            records: Vec::with_capacity(capacity),
+            acks: Vec::new(),
+            deadline: None,
+        }
+    }
+
+    fn push(&mut self, msg: Message<T>, flush_interval: Option<Duration>) {
+        if self.records.is_empty() {
+            self.deadline = flush_interval.map(|i| Instant::now() + i);
+        }
+
+        match msg {
+            Message::Write(t) => self.records.push(t),
+            Message::WriteAck(t, ack) => {
+                self.records.push(t);
+                self.acks.push(ack);
+            }
+            _ => (),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.records.len()
+    }
+
+    fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+
+    async fn flush<F, Fut, E>(&mut self, handler: &F)
+    where
+        F: Fn(Vec<T>) -> Fut,
+        Fut: Future<Output = Result<(), E>>,
+        E: std::fmt::Display,
+    {
+        self.deadline = None;
+
+        if self.records.is_empty() {
+            return;
+        }
+
+        let records = std::mem::take(&mut self.records);
+        let result = handler(records).await.map_err(|e| {
+            tracing::error!("Failed to write batch: {}", e);
+            WriterError::BatchFailed(e.to_string())
+        });
+
+        for ack in self.acks.drain(..) {
+            // The caller may have stopped waiting; that's fine.
+            let _ = ack.send(result.clone());
+        }
+    }
+}
+
+async fn sleep_until(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OverflowConfig {
+    /// Directory spill files are written to.
+    pub path: String,
+    /// Disk space spill files may take up before writes are rejected.
+    #[serde(default = "default_overflow_max_bytes")]
+    pub max_bytes: u64,
+}
+
+fn default_overflow_max_bytes() -> u64 {
+    1024 * 1024 * 1024
+}
+
+impl OverflowConfig {
+    /// The same settings, with spill files kept in a subdirectory.
+    pub fn subdir(&self, name: &str) -> Self {
+        Self {
+            path: Path::new(&self.path)
+                .join(name)
+                .to_string_lossy()
+                .to_string(),
+            max_bytes: self.max_bytes,
+        }
+    }
+}
+
+/// Records that can be spilled to disk when a writer's buffer is full.
+pub trait Spill: Sized {
+    fn spill(&self) -> Vec<u8>;
+
+    fn unspill(bytes: &[u8]) -> Option<Self>;
+}
+
+const SPILL_FILE_SIZE: u64 = 4 * 1024 * 1024;
+
+#[derive(Debug)]
+struct SpillFile {
+    path: PathBuf,
+    size: u64,
+    // Records written to the file, counted in `pending` until drained.
+    frames: usize,
+    // Kept open while records are being spilled to the file.
+    handle: Option<std::fs::File>,
+    // Set once the drain task starts reading the file; writes go to a new one.
+    sealed: bool,
+}
+
+#[derive(Debug, Default)]
+struct OverflowState {
+    files: VecDeque<SpillFile>,
+    next_file: u64,
+    // Spilled records not yet handed back to the writer task. While this is
+    // non-zero every write is spilled so records keep their order.
+    pending: usize,
+}
+
+#[derive(Debug)]
+struct Overflow<T> {
+    dir: PathBuf,
+    max_bytes: u64,
+    // Held across spill file I/O, which runs on the blocking pool, so spills
+    // stay in order.
+    state: Mutex<OverflowState>,
+    bytes: AtomicU64,
+    spilled: Notify,
+    // Woken once every spilled record has been handed back.
+    drained: Notify,
+    total_spilled_bytes: AtomicU64,
+    encode: fn(&T) -> Vec<u8>,
+    decode: fn(&[u8]) -> Option<T>,
+}
+
+fn read_frames(path: &Path) -> std::io::Result<Vec<Vec<u8>>> {
+    let mut buf = Vec::new();
+    std::fs::File::open(path)?.read_to_end(&mut buf)?;
+
+    let mut frames = Vec::new();
+    let mut rest = buf.as_slice();
+    while rest.len() >= 4 {
+        let len = u32::from_le_bytes(rest[..4].try_into().unwrap()) as usize;
+        if rest.len() < 4 + len {
+            tracing::warn!("Ignoring torn spill entry in {}", path.display());
+            break;
+        }
+        frames.push(rest[4..4 + len].to_vec());
+        rest = &rest[4 + len..];
+    }
+
+    Ok(frames)
+}
+
+impl<T: Spill> Overflow<T> {
+    /// Opens the spill directory, picking up files left behind by a previous
+    /// run so they are drained before anything new.
+    fn open(config: &OverflowConfig) -> std::io::Result<Self> {
+        let dir = PathBuf::from(&config.path);
+        std::fs::create_dir_all(&dir)?;
+
+        let mut ids: Vec<(u64, PathBuf)> = std::fs::read_dir(&dir)?
+            .filter_map(|entry| {
+                let path = entry.ok()?.path();
+                if path.extension()? != "spill" {
+                    return None;
+                }
+                let id = path.file_stem()?.to_str()?.parse().ok()?;
+                Some((id, path))
+            })
+            .collect();
+        ids.sort();
+
+        let mut state = OverflowState {
+            next_file: ids.last().map(|(id, _)| id + 1).unwrap_or_default(),
+            ..Default::default()
+        };
+
+        let mut bytes = 0;
+        for (_, path) in ids {
+            let size = std::fs::metadata(&path)?.len();
+            let frames = read_frames(&path)?.len();
+            state.pending += frames;
+            bytes += size;
+            // Never append to a file from a previous run; its tail may be torn.
+            state.files.push_back(SpillFile {
+                path,
+                size,
+                frames,
+                handle: None,
+                sealed: true,
+            });
+        }
+
+        if state.pending > 0 {
+            tracing::info!(
+                "Found {} spilled records in {}",
+                state.pending,
+                dir.display()
+            );
+        }
+
+        Ok(Self {
+            dir,
+            max_bytes: config.max_bytes,
+            state: Mutex::new(state),
+            bytes: AtomicU64::new(bytes),
+            spilled: Notify::new(),
+            drained: Notify::new(),
+            total_spilled_bytes: AtomicU64::new(0),
+            encode: T::spill,
+            decode: T::unspill,
+        })
+    }
+}
+
+impl<T> Overflow<T> {
+    async fn spill(&self, state: &mut OverflowState, t: &T) -> Result<(), WriterError> {
+        let payload = (self.encode)(t);
+        let mut frame = Vec::with_capacity(4 + payload.len());
+        frame.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&payload);
+        let size = frame.len() as u64;
+
+        if self.bytes.load(Ordering::Relaxed) + size > self.max_bytes {
+            tracing::error!("Overflow in {} is full", self.dir.display());
+            return Err(WriterError::OverflowFull);
+        }
+
+        let needs_file = state
+            .files
+            .back()
+            .is_none_or(|f| f.sealed || f.size >= SPILL_FILE_SIZE);
+
+        if needs_file {
+            let path = self.dir.join(format!("{:020}.spill", state.next_file));
+            state.next_file += 1;
+            state.files.push_back(SpillFile {
+                path,
+                size: 0,
+                frames: 0,
+                handle: None,
+                sealed: false,
+            });
+        }
+
+        let file = state.files.back_mut().unwrap();
+        let (path, handle, good_size) = (file.path.clone(), file.handle.take(), file.size);
+        let write = move || {
+            let mut handle = match handle {
+                Some(handle) => handle,
+                None => match std::fs::OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(&path)
+                {
+                    Ok(handle) => handle,
+                    Err(e) => return (None, Err(e)),
+                },
+            };
+            // Cut a partly written frame off so the next one starts on a
+            // frame boundary. If that fails too, the file is abandoned.
+            match handle.write_all(&frame) {
+                Ok(()) => (Some(handle), Ok(())),
+                Err(e) => match handle.set_len(good_size) {
+                    Ok(()) => (Some(handle), Err(e)),
+                    Err(_) => (None, Err(e)),
+                },
+            }
+        };
+
+        let (handle, written) = tokio::task::spawn_blocking(write)
+            .await
+            .unwrap_or_else(|e| (None, Err(std::io::Error::other(e))));
+        file.sealed = handle.is_none();
+        file.handle = handle;
+        written.map_err(|e| {
+            tracing::error!("Failed to spill to {}: {}", file.path.display(), e);
+            WriterError::OverflowFailed(e.to_string())
+        })?;
+
+        if state.pending == 0 {
+            tracing::warn!("Writer buffer is full, spilling to {}", self.dir.display());
+        }
+
+        file.size += size;
+        file.frames += 1;
+        state.pending += 1;
+        self.bytes.fetch_add(size, Ordering::Relaxed);
+        self.total_spilled_bytes.fetch_add(size, Ordering::Relaxed);
+        self.spilled.notify_one();
+
+        Ok(())
+    }
+
+    /// Seals the oldest spill file and reads its records back. Records that
+    /// can't be read back are dropped.
+    async fn read_head(&self) -> Option<Vec<T>> {
+        let (path, written) = {
+            let mut state = self.state.lock().await;
+            let head = state.files.front_mut()?;
+            head.sealed = true;
+            head.handle = None;
+            (head.path.clone(), head.frames)
+        };
+
+        let read = tokio::task::spawn_blocking({
+            let path = path.clone();
+            move || read_frames(&path)
+        })
+        .await
+        .map_err(std::io::Error::other)
+        .and_then(|frames| frames);
+
+        let frames = match read {
+            Ok(frames) => frames,
+            Err(e) => {
+                tracing::error!("Failed to read {}: {}", path.display(), e);
+                Vec::new()
+            }
+        };
+
+        let records: Vec<T> = frames.iter().filter_map(|f| (self.decode)(f)).collect();
+        if records.len() < written {
+            tracing::error!(
+                "Dropping {} unreadable records from {}",
+                written - records.len(),
+                path.display()
+            );
+            self.state.lock().await.pending -= written - records.len();
+        }
+
+        Some(records)
+    }
+
+    async fn drained_one(&self) {
+        self.state.lock().await.pending -= 1;
+    }
+
+    async fn remove_head(&self) {
+        let Some(head) = self.state.lock().await.files.pop_front() else {
+            return;
+        };
+
+        self.bytes.fetch_sub(head.size, Ordering::Relaxed);
+        if let Err(e) = tokio::fs::remove_file(&head.path).await {
+            tracing::error!("Failed to remove {}: {}", head.path.display(), e);
+        }
+    }
+
+    /// Hands spilled records back to the writer task, oldest file first,
+    /// waiting for buffer space as it goes.
+    async fn drain(self: Arc<Self>, tx: mpsc::Sender<Message<T>>) {
+        loop {
+            let Some(records) = self.read_head().await else {
+                self.spilled.notified().await;
+                continue;
+            };
+
+            for record in records {
+                if tx.send(Message::Write(record)).await.is_err() {
+                    return;
+                }
+                self.drained_one().await;
+            }
+
+            self.remove_head().await;
+
+            if self.state.lock().await.pending == 0 {
+                tracing::info!("Drained overflow in {}", self.dir.display());
+                self.drained.notify_waiters();
+            }
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Writer<T> {
     pub tx: mpsc::Sender<Message<T>>,
+    handle: Mutex<Option<JoinHandle<()>>>,
+    overflow: Option<Arc<Overflow<T>>>,
+    drain_handle: std::sync::Mutex<Option<JoinHandle<()>>>,
 }
 
 impl<T> Writer<T>
 where
-    T: Send + 'static + std::fmt::Debug + Serialize,
+    T: Send + 'static + std::fmt::Debug,
 {
-    pub fn new<F>(func: F, buffer_size: usize, batch_size: usize) -> Self
+    /// Spawns a writer task that buffers records and hands them to `handler`
+    /// in batches. A batch is flushed once it reaches `batch_size`, once its
+    /// oldest record has waited `flush_interval`, on an explicit `flush`, or
+    /// on shutdown.
+    pub fn new<F, Fut, E>(handler: F, config: WriterConfig) -> Self
     where
-        F: Fn(Vec<T>) + Send + 'static,
+        F: Fn(Vec<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: std::fmt::Display,
     {
-        let (tx, mut rx) = mpsc::channel(buffer_size);
+        let (tx, mut rx) = mpsc::channel(config.buffer_size);
 
-        tokio::spawn(async move {
+        let handle = tokio::spawn(async move {
             tracing::info!("Starting writer");
 
-            let mut buffer = Vec::with_capacity(batch_size);
-            while let Some(msg) = rx.recv().await {
-                match &msg {
-                    Message::Shutdown => break,
-                    Message::Flush => {
-                        func(buffer.drain(..).map(|m: Message<T>| m.unwrap()).collect());
+            let mut batch = Batch::with_capacity(config.batch_size);
+            loop {
+                let msg = tokio::select! {
+                    msg = rx.recv() => msg,
+                    _ = sleep_until(batch.deadline), if !batch.is_empty() => {
+                        batch.flush(&handler).await;
+                        continue;
                     }
-                    Message::Write(_) => {
-                        buffer.push(msg);
-                        if buffer.len() == batch_size {
-                            func(buffer.drain(..).map(|msg| msg.unwrap()).collect());
+                };
+
+                match msg {
+                    None | Some(Message::Shutdown) => break,
+                    Some(Message::Flush) => batch.flush(&handler).await,
+                    Some(msg) => {
+                        batch.push(msg, config.flush_interval);
+                        if batch.len() >= config.batch_size {
+                            batch.flush(&handler).await;
                         }
                     }
                 };
@@ -69,38 +521,144 @@ where
 
             tracing::debug!("Shutting down writer");
 
-            if !buffer.is_empty() {
-                func(buffer.drain(..).map(|msg| msg.unwrap()).collect());
-            }
+            batch.flush(&handler).await;
 
             tracing::debug!("Writer shutdown");
         });
 
-        Self { tx }
+        Self {
+            tx,
+            handle: Mutex::new(Some(handle)),
+            overflow: None,
+            drain_handle: std::sync::Mutex::new(None),
+        }
     }
 
-    pub async fn write(&self, t: T) {
-        match self.tx.send(Message::Write(t)).await {
-            Ok(_) => (),
-            Err(e) => {
-                tracing::error!("Failed to write: {}", e);
+    /// Like `new`, but once the buffer is full `write` spills records to
+    /// `overflow.path` instead of waiting. Spilled records are fed back to
+    /// the writer in order as buffer space frees up, and are picked up again
+    /// after a restart if the writer stops before draining them.
+    pub fn with_overflow<F, Fut, E>(
+        handler: F,
+        config: WriterConfig,
+        overflow: &OverflowConfig,
+    ) -> std::io::Result<Self>
+    where
+        T: Spill,
+        F: Fn(Vec<T>) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<(), E>> + Send,
+        E: std::fmt::Display,
+    {
+        let overflow = Arc::new(Overflow::open(overflow)?);
+        let mut writer = Self::new(handler, config);
+
+        let drain = tokio::spawn(overflow.clone().drain(writer.tx.clone()));
+        *writer.drain_handle.get_mut().unwrap() = Some(drain);
+        writer.overflow = Some(overflow);
+
+        Ok(writer)
+    }
+
+    /// Messages waiting in the buffer for the writer task.
+    pub fn queue_depth(&self) -> usize {
+        self.tx.max_capacity() - self.tx.capacity()
+    }
+
+    /// Bytes currently spilled to disk.
+    pub fn spilled_bytes(&self) -> u64 {
+        self.overflow
+            .as_ref()
+            .map(|o| o.bytes.load(Ordering::Relaxed))
+            .unwrap_or_default()
+    }
+
+    /// Bytes spilled to disk since the writer started.
+    pub fn total_spilled_bytes(&self) -> u64 {
+        self.overflow
+            .as_ref()
+            .map(|o| o.total_spilled_bytes.load(Ordering::Relaxed))
+            .unwrap_or_default()
+    }
+
+    pub async fn write(&self, t: T) -> Result<(), WriterError> {
+        if let Some(overflow) = &self.overflow {
+            let mut state = overflow.state.lock().await;
+            if state.pending > 0 {
+                return overflow.spill(&mut state, &t).await;
             }
+
+            return match self.tx.try_send(Message::Write(t)) {
+                Ok(()) => Ok(()),
+                Err(mpsc::error::TrySendError::Full(msg)) => {
+                    overflow.spill(&mut state, &msg.unwrap()).await
+                }
+                Err(mpsc::error::TrySendError::Closed(_)) => Err(WriterError::Closed),
+            };
+        }
+
+        self.tx.send(Message::Write(t)).await.map_err(|e| {
+            tracing::error!("Failed to write: {}", e);
+            WriterError::Closed
+        })
+    }
+
+    /// Writes a record and waits for the result of the batch it was
+    /// flushed in. These are never spilled; while the overflow has records
+    /// they wait for it to drain, so they stay behind them.
+    pub async fn write_with_ack(&self, t: T) -> BatchResult {
+        let (ack_tx, ack_rx) = oneshot::channel();
+        let msg = Message::WriteAck(t, ack_tx);
+
+        let sent = match &self.overflow {
+            Some(overflow) => loop {
+                let state = overflow.state.lock().await;
+                if state.pending == 0 {
+                    // Holding the lock keeps writes from spilling ahead.
+                    break self.tx.send(msg).await;
+                }
+
+                let drained = overflow.drained.notified();
+                drop(state);
+                tokio::select! {
+                    _ = drained => {}
+                    _ = self.tx.closed() => return Err(WriterError::Closed),
+                }
+            },
+            None => self.tx.send(msg).await,
+        };
+
+        if let Err(e) = sent {
+            tracing::error!("Failed to write: {}", e);
+            return Err(WriterError::Closed);
         }
+
+        ack_rx.await.map_err(|_| WriterError::Closed)?
     }
 
-    pub async fn write_many(&self, t: impl IntoIterator<Item = T>) {
+    pub async fn write_many(&self, t: impl IntoIterator<Item = T>) -> Result<(), WriterError> {
         for t in t {
-            self.write(t).await;
+            self.write(t).await?;
         }
+
+        Ok(())
     }
 
+    /// Asks the writer to flush what it has buffered and stop, then waits for
+    /// the writer task to finish. Records still spilled are left on disk for the next run.
     pub async fn shutdown(&self) {
-        match self.tx.send(Message::Shutdown).await {
-            Ok(_) => (),
-            Err(e) => {
-                tracing::error!("Failed to shutdown: {}", e);
+        if let Some(drain) = self.drain_handle.lock().unwrap().take() {
+            drain.abort();
+        }
+
+        if let Err(e) = self.tx.send(Message::Shutdown).await {
+            tracing::error!("Failed to shutdown: {}", e);
+        }
+
+        if let Some(handle) = self.handle.lock().await.take() {
+            if let Err(e) = handle.await {
+                tracing::error!("Writer task failed: {}", e);
             }
-        };
+        }
     }
 
     pub async fn flush(&self) {
@@ -112,3 +670,217 @@ where
         };
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TempDir;
+    use std::sync::{Arc, Mutex};
+
+    type Batches = Arc<Mutex<Vec<Vec<usize>>>>;
+
+    fn collecting_writer(config: WriterConfig) -> (Writer<usize>, Batches) {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let sink = batches.clone();
+
+        let writer = Writer::new(
+            move |batch: Vec<usize>| {
+                let sink = sink.clone();
+                async move {
+                    sink.lock().unwrap().push(batch);
+                    Ok::<(), String>(())
+                }
+            },
+            config,
+        );
+
+        (writer, batches)
+    }
+
+    #[tokio::test]
+    async fn test_flushes_on_batch_size() {
+        let (writer, batches) = collecting_writer(WriterConfig {
+            buffer_size: 16,
+            batch_size: 2,
+            flush_interval: None,
+        });
+
+        writer.write_many([1, 2, 3]).await.unwrap();
+        assert_eq!(writer.write_with_ack(4).await, Ok(()));
+
+        assert_eq!(*batches.lock().unwrap(), vec![vec![1, 2], vec![3, 4]]);
+    }
+
+    #[tokio::test]
+    async fn test_flushes_on_interval() {
+        let (writer, batches) = collecting_writer(WriterConfig {
+            buffer_size: 16,
+            batch_size: 100,
+            flush_interval: Some(Duration::from_millis(20)),
+        });
+
+        writer.write(1).await.unwrap();
+        tokio::time::sleep(Duration::from_millis(100)).await;
+
+        assert_eq!(*batches.lock().unwrap(), vec![vec![1]]);
+    }
+
+    #[tokio::test]
+    async fn test_shutdown_drains_buffer() {
+        let (writer, batches) = collecting_writer(WriterConfig {
+            buffer_size: 16,
+            batch_size: 100,
+            flush_interval: None,
+        });
+
+        writer.write_many([1, 2]).await.unwrap();
+        writer.shutdown().await;
+
+        assert_eq!(*batches.lock().unwrap(), vec![vec![1, 2]]);
+        assert_eq!(writer.write(3).await, Err(WriterError::Closed));
+    }
+
+    #[tokio::test]
+    async fn test_ack_reports_batch_failure() {
+        let writer = Writer::new(
+            |_: Vec<usize>| async { Err("sink unavailable") },
+            WriterConfig {
+                buffer_size: 16,
+                batch_size: 1,
+                flush_interval: None,
+            },
+        );
+
+        assert_eq!(
+            writer.write_with_ack(1).await,
+            Err(WriterError::BatchFailed("sink unavailable".to_string()))
+        );
+    }
+
+    impl Spill for usize {
+        fn spill(&self) -> Vec<u8> {
+            self.to_le_bytes().to_vec()
+        }
+
+        fn unspill(bytes: &[u8]) -> Option<Self> {
+            Some(usize::from_le_bytes(bytes.try_into().ok()?))
+        }
+    }
+
+    fn overflow_config(dir: &TempDir, max_bytes: u64) -> OverflowConfig {
+        OverflowConfig {
+            path: dir.path_string(),
+            max_bytes,
+        }
+    }
+
+    /// A writer whose handler blocks until `gate` is opened.
+    fn stalled_writer(overflow: &OverflowConfig) -> (Writer<usize>, Batches, Arc<Notify>) {
+        let batches = Arc::new(Mutex::new(Vec::new()));
+        let gate = Arc::new(Notify::new());
+        let (sink, open) = (batches.clone(), gate.clone());
+
+        let writer = Writer::with_overflow(
+            move |batch: Vec<usize>| {
+                let (sink, open) = (sink.clone(), open.clone());
+                async move {
+                    open.notified().await;
+                    open.notify_one();
+                    sink.lock().unwrap().push(batch);
+                    Ok::<(), String>(())
+                }
+            },
+            WriterConfig {
+                buffer_size: 2,
+                batch_size: 1,
+                flush_interval: None,
+            },
+            overflow,
+        )
+        .unwrap();
+
+        (writer, batches, gate)
+    }
+
+    #[tokio::test]
+    async fn test_overflow_drains_in_order() {
+        let dir = TempDir::new("overflow");
+        let config = overflow_config(&dir, 1024);
+        let (writer, batches, gate) = stalled_writer(&config);
+
+        for i in 0..10 {
+            writer.write(i).await.unwrap();
+        }
+        assert!(writer.spilled_bytes() > 0);
+
+        gate.notify_one();
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while batches.lock().unwrap().len() < 10 {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+
+        let received: Vec<usize> = batches.lock().unwrap().concat();
+        assert_eq!(received, (0..10).collect::<Vec<_>>());
+        assert_eq!(writer.spilled_bytes(), 0);
+        assert!(writer.total_spilled_bytes() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_acked_writes_wait_for_overflow() {
+        let dir = TempDir::new("overflow");
+        let config = overflow_config(&dir, 1024);
+        let (writer, batches, gate) = stalled_writer(&config);
+
+        for i in 0..10 {
+            writer.write(i).await.unwrap();
+        }
+        assert!(writer.spilled_bytes() > 0);
+
+        let (acked, _) = tokio::time::timeout(
+            Duration::from_secs(5),
+            futures::future::join(writer.write_with_ack(10), async { gate.notify_one() }),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(acked, Ok(()));
+        let received: Vec<usize> = batches.lock().unwrap().concat();
+        assert_eq!(received, (0..=10).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_unreadable_spill_file_is_dropped() {
+        let dir = TempDir::new("overflow");
+        let config = overflow_config(&dir, 1024);
+        let path = Path::new(&config.path).join(format!("{:020}.spill", 0));
+        let frame = |i: usize| [(8u32).to_le_bytes().to_vec(), i.spill()].concat();
+        std::fs::create_dir_all(&config.path).unwrap();
+        std::fs::write(&path, [frame(1), frame(2)].concat()).unwrap();
+
+        let overflow = Overflow::<usize>::open(&config).unwrap();
+        assert_eq!(overflow.state.lock().await.pending, 2);
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(overflow.read_head().await, Some(Vec::new()));
+        overflow.remove_head().await;
+
+        assert_eq!(overflow.state.lock().await.pending, 0);
+        assert_eq!(overflow.bytes.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn test_overflow_rejects_writes_over_cap() {
+        // Room for two spilled records.
+        let dir = TempDir::new("overflow");
+        let config = overflow_config(&dir, 2 * 12);
+        let (writer, _, _) = stalled_writer(&config);
+
+        let results: Vec<_> = futures::future::join_all((0..8).map(|i| writer.write(i))).await;
+
+        assert!(results.contains(&Err(WriterError::OverflowFull)));
+        assert_eq!(writer.spilled_bytes(), 24);
+    }
+}